@@ -0,0 +1,47 @@
+use std::{fmt::Debug, sync::Mutex};
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::extension::LockResultExt;
+
+/// Source of the current time, replaceable in tests.
+pub trait Clock: Debug + Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock that only moves when told to.
+#[derive(Debug)]
+pub struct MockClock {
+    now: Mutex<DateTime<Utc>>,
+}
+
+impl MockClock {
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self {
+            now: Mutex::new(now),
+        }
+    }
+
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.now.lock().ignore_poison() = now;
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().ignore_poison() += duration;
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().ignore_poison()
+    }
+}
@@ -0,0 +1,89 @@
+//! Environment variables read by the `from_env` constructors.
+
+use std::env::VarError;
+
+use binance::config::Config;
+
+pub const API_KEY: &str = "BINANCE_API_KEY";
+pub const SECRET_KEY: &str = "BINANCE_SECRET_KEY";
+
+/// REST endpoint, e.g. `https://testnet.binance.vision`, the production one when unset
+pub const API_URL: &str = "BINANCE_API_URL";
+
+/// Where the `from_env_with` constructors read a variable, `std::env::var` for `from_env`.
+pub(crate) type Lookup<'a> = &'a dyn Fn(&str) -> Result<String, VarError>;
+
+/// The value of `name`, an error naming the variable when it is missing or blank.
+pub(crate) fn required(lookup: Lookup, name: &str) -> Result<String, String> {
+    match lookup(name) {
+        Ok(value) if !value.trim().is_empty() => Ok(value),
+        Ok(_) => Err(format!("environment variable {} is empty", name)),
+        Err(VarError::NotPresent) => Err(format!("environment variable {} is not set", name)),
+        Err(e) => Err(format!("environment variable {} {}", name, e)),
+    }
+}
+
+/// Binance config with the endpoint of [`API_URL`] when set.
+pub(crate) fn config(lookup: Lookup) -> Result<Config, String> {
+    let config = Config::default();
+    match lookup(API_URL) {
+        Err(VarError::NotPresent) => Ok(config),
+        _ => Ok(config.set_rest_api_endpoint(required(lookup, API_URL)?)),
+    }
+}
+
+#[cfg(test)]
+mod tests_env {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{market::client::MarketClient, spot::client::SpotClient, tests_general::btc_spot};
+
+    fn lookup(variables: &[(&str, &str)]) -> impl Fn(&str) -> Result<String, VarError> {
+        let variables: HashMap<String, String> = variables
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+
+        move |name| variables.get(name).cloned().ok_or(VarError::NotPresent)
+    }
+
+    #[test]
+    fn test_from_env() {
+        let error = SpotClient::from_env_with(btc_spot(), None, lookup(&[])).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "environment variable BINANCE_API_KEY is not set"
+        );
+
+        let keys = [(API_KEY, "api-key"), (SECRET_KEY, " ")];
+        let error = SpotClient::from_env_with(btc_spot(), None, lookup(&keys)).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "environment variable BINANCE_SECRET_KEY is empty"
+        );
+
+        let keys = [(API_KEY, "api-key"), (SECRET_KEY, "secret-key")];
+        let client = SpotClient::from_env_with(btc_spot(), None, lookup(&keys)).unwrap();
+        assert_eq!(format!("{:?}", client).contains("BTCUSDT"), true);
+        assert!(MarketClient::from_env_with(lookup(&keys)).is_ok());
+
+        let variables = [keys[0], keys[1], (API_URL, "")];
+        let error = MarketClient::from_env_with(lookup(&variables))
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "environment variable BINANCE_API_URL is empty"
+        );
+        assert!(SpotClient::from_env_with(btc_spot(), None, lookup(&variables)).is_err());
+
+        let variables = [
+            keys[0],
+            keys[1],
+            (API_URL, "https://testnet.binance.vision"),
+        ];
+        assert!(SpotClient::from_env_with(btc_spot(), None, lookup(&variables)).is_ok());
+        assert!(MarketClient::from_env_with(lookup(&variables)).is_ok());
+    }
+}
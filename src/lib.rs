@@ -1,5 +1,13 @@
+pub mod clock;
+#[cfg(feature = "client")]
+pub mod env;
+#[cfg(feature = "client")]
+pub mod limiter;
+#[cfg(feature = "client")]
+pub mod market;
 pub mod spot;
 pub mod strategy;
+pub mod treasurer;
 
 mod extension;
 
@@ -12,4 +20,282 @@ pub mod noun {
     pub type Quantity = Decimal;
     pub type Commission = Decimal;
     pub type Amount = Decimal;
+
+    /// Why a checked operation could not produce a value.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum ArithError {
+        Overflow,
+        Underflow,
+        DivideByZero,
+    }
+
+    impl std::error::Error for ArithError {}
+
+    impl std::fmt::Display for ArithError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                Self::Overflow => write!(f, "decimal overflow"),
+                Self::Underflow => write!(f, "decimal underflow"),
+                Self::DivideByZero => write!(f, "decimal divide by zero"),
+            }
+        }
+    }
+
+    pub fn checked_add(lhs: Decimal, rhs: Decimal) -> Result<Decimal, ArithError> {
+        lhs.checked_add(rhs)
+            .ok_or_else(|| out_of_range(lhs.is_sign_negative()))
+    }
+
+    pub fn checked_sub(lhs: Decimal, rhs: Decimal) -> Result<Decimal, ArithError> {
+        lhs.checked_sub(rhs)
+            .ok_or_else(|| out_of_range(lhs.is_sign_negative()))
+    }
+
+    pub fn checked_mul(lhs: Decimal, rhs: Decimal) -> Result<Decimal, ArithError> {
+        lhs.checked_mul(rhs)
+            .ok_or_else(|| out_of_range(lhs.is_sign_negative() != rhs.is_sign_negative()))
+    }
+
+    pub fn checked_div(lhs: Decimal, rhs: Decimal) -> Result<Decimal, ArithError> {
+        if rhs.is_zero() {
+            return Err(ArithError::DivideByZero);
+        }
+
+        lhs.checked_div(rhs)
+            .ok_or_else(|| out_of_range(lhs.is_sign_negative() != rhs.is_sign_negative()))
+    }
+
+    /// Strip insignificant trailing zeros, for logs and reports.
+    pub fn normalize_for_display(value: &Decimal) -> Decimal {
+        value.normalize()
+    }
+
+    /// How a value is cut down to a scale.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+    pub enum Rounding {
+        /// Drop the extra digits, rounding toward zero
+        Truncate,
+
+        /// Round half to even, the default of `Decimal::round_dp`
+        #[default]
+        HalfEven,
+
+        /// Round half away from zero
+        HalfUp,
+    }
+
+    impl Rounding {
+        pub fn apply(&self, value: Decimal, scale: Precision) -> Decimal {
+            let strategy = match self {
+                Self::Truncate => rust_decimal::RoundingStrategy::ToZero,
+                Self::HalfEven => rust_decimal::RoundingStrategy::MidpointNearestEven,
+                Self::HalfUp => rust_decimal::RoundingStrategy::MidpointAwayFromZero,
+            };
+
+            value.round_dp_with_strategy(scale, strategy)
+        }
+    }
+
+    /// Maximum scale and rounding applied to computed financial values.
+    #[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+    pub struct DecimalContext {
+        pub scale: Precision,
+        pub rounding: Rounding,
+    }
+
+    impl DecimalContext {
+        pub const fn new(scale: Precision, rounding: Rounding) -> Self {
+            Self { scale, rounding }
+        }
+
+        /// Round to the scale of the context.
+        pub fn round(&self, value: Decimal) -> Decimal {
+            self.rounding.apply(value, self.scale)
+        }
+
+        /// Round to `scale`, never keeping more digits than the context allows.
+        pub fn round_dp(&self, value: Decimal, scale: Precision) -> Decimal {
+            self.rounding.apply(value, scale.min(self.scale))
+        }
+    }
+
+    impl Default for DecimalContext {
+        fn default() -> Self {
+            // 28 is the largest scale a `Decimal` can hold
+            Self::new(28, Rounding::default())
+        }
+    }
+
+    // A result too far below zero is an underflow, too far above is an overflow
+    fn out_of_range(is_negative: bool) -> ArithError {
+        match is_negative {
+            true => ArithError::Underflow,
+            false => ArithError::Overflow,
+        }
+    }
+
+    #[cfg(test)]
+    mod tests_noun {
+        use super::*;
+
+        #[test]
+        fn test_checked_add() {
+            let result = checked_add(Decimal::ONE, Decimal::TWO);
+            assert_eq!(result, Ok(Decimal::from(3)));
+            assert_eq!(
+                checked_add(Decimal::MAX, Decimal::ONE),
+                Err(ArithError::Overflow)
+            );
+            assert_eq!(
+                checked_add(Decimal::MIN, Decimal::NEGATIVE_ONE),
+                Err(ArithError::Underflow)
+            );
+        }
+
+        #[test]
+        fn test_checked_sub() {
+            assert_eq!(
+                checked_sub(Decimal::ONE, Decimal::TWO),
+                Ok(Decimal::NEGATIVE_ONE)
+            );
+            assert_eq!(
+                checked_sub(Decimal::MAX, Decimal::NEGATIVE_ONE),
+                Err(ArithError::Overflow)
+            );
+            assert_eq!(
+                checked_sub(Decimal::MIN, Decimal::ONE),
+                Err(ArithError::Underflow)
+            );
+        }
+
+        #[test]
+        fn test_checked_mul() {
+            assert_eq!(
+                checked_mul(Decimal::TWO, Decimal::TWO),
+                Ok(Decimal::from(4))
+            );
+            assert_eq!(
+                checked_mul(Decimal::MAX, Decimal::TWO),
+                Err(ArithError::Overflow)
+            );
+            assert_eq!(
+                checked_mul(Decimal::MAX, Decimal::from(-2)),
+                Err(ArithError::Underflow)
+            );
+        }
+
+        #[test]
+        fn test_decimal_context() {
+            let value = Decimal::new(1234565, 6);
+
+            let truncate = DecimalContext::new(4, Rounding::Truncate);
+            assert_eq!(truncate.round(value), Decimal::new(12345, 4));
+            assert_eq!(truncate.round_dp(value, 5), Decimal::new(12345, 4));
+            assert_eq!(truncate.round_dp(value, 2), Decimal::new(123, 2));
+
+            let half_up = DecimalContext::new(6, Rounding::HalfUp);
+            assert_eq!(half_up.round_dp(value, 5), Decimal::new(123457, 5));
+
+            let half_even = DecimalContext::default();
+            assert_eq!(half_even.round_dp(value, 5), Decimal::new(123456, 5));
+            assert_eq!(half_even.round(value), value);
+        }
+
+        #[test]
+        fn test_checked_div() {
+            assert_eq!(
+                checked_div(Decimal::TEN, Decimal::TWO),
+                Ok(Decimal::from(5))
+            );
+            assert_eq!(
+                checked_div(Decimal::ONE, Decimal::ZERO),
+                Err(ArithError::DivideByZero)
+            );
+            let tiny = Decimal::new(1, 28);
+            assert_eq!(checked_div(Decimal::MAX, tiny), Err(ArithError::Overflow));
+            assert_eq!(checked_div(Decimal::MIN, tiny), Err(ArithError::Underflow));
+        }
+    }
+}
+
+/// Everything needed for typical usage, `use rust_binance::prelude::*;`
+pub mod prelude {
+    pub use crate::noun::*;
+    pub use crate::spot::{Spot, SpotFilter};
+    pub use crate::strategy::{
+        decision::{Decision, DecisionLog, SkipReason},
+        grid::{Grid, GridOptions, RangeHigh, ReloadReport, Spacing},
+        limit::{Limit, LimitPosition},
+        recenter::{EmaGrid, EmaGridOptions},
+        replay::{PriceReplay, ReplayEnd},
+        round_trip::RoundTrip,
+        snapshot::{StrategyDiff, StrategySnapshot},
+        AmountPoint, ErrorDirective, ErrorHandler, Exchanger, Interval, PinFutureResult,
+        PricePoint, QuantityPoint, Range, RangeError, Strategy, StrategyEvent, TradeSide,
+        TrapOutcome,
+    };
+    pub use crate::treasurer::Prosperity;
+
+    #[cfg(feature = "client")]
+    pub use crate::{
+        limiter::{RateLimiter, UsedWeight},
+        market::client::{MarketClient, PriceAggregate, PriceCache},
+        spot::{
+            client::{MarketNotional, RetryPolicy, SpotClient, SpotClientOption},
+            router::{Fill, OrderIntent, OrderKind, OrderRouter},
+        },
+        strategy::{
+            pool::RunnerPool,
+            runner::StrategyRunner,
+            store::{FileStateStore, StateStore},
+        },
+    };
+}
+
+/// Helpers shared by the tests of every module.
+#[cfg(test)]
+pub(crate) mod tests_general {
+    use crate::noun::*;
+    use crate::spot::{QuantityRounding, Spot};
+
+    /// Parsed from the shortest representation of `value`, so `0.1` is exactly `0.1`.
+    pub(crate) fn decimal(value: f64) -> Decimal {
+        dec(&value.to_string())
+    }
+
+    /// Exact decimal literal, e.g. `dec("0.0115684")`.
+    pub(crate) fn dec(value: &str) -> Decimal {
+        Decimal::from_str_exact(value).unwrap()
+    }
+
+    pub(crate) fn btc_spot() -> Spot {
+        Spot {
+            symbol: String::from("BTCUSDT"),
+            transaction_quantity_precision: 5,
+            step_size: None,
+            quantity_precision: 7, // BTC Precision
+            amount_precision: 8,   // USDT Precision
+            minimum_transaction_amount: decimal(5.0),
+            selling_quantity_rounding: QuantityRounding::FloorToStep,
+            decimal_context: DecimalContext::default(),
+            tick_size: None,
+            minimum_quantity: None,
+            buying_commission: decimal(0.001),
+            selling_commission: decimal(0.001),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_prelude {
+    use crate::prelude::*;
+
+    #[test]
+    fn test_prelude() {
+        let range = Range(Decimal::from(50), Decimal::from(90));
+        let grid = Grid::new(Decimal::from(100), range, 4, Some(GridOptions::default()));
+
+        assert_eq!(grid.is_all_short(), true);
+        assert_eq!(PricePoint::new(Decimal::ONE).value(), &Decimal::ONE);
+    }
 }
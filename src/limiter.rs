@@ -0,0 +1,340 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::{DateTime, Timelike, Utc};
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+use crate::{
+    clock::{Clock, SystemClock},
+    extension::LockResultExt,
+};
+
+/// Header carrying the request weight used in the current minute.
+pub const USED_WEIGHT_HEADER: &str = "x-mbx-used-weight-1m";
+
+/// Request weight of the endpoints called by the clients, as listed by Binance.
+pub mod weight {
+    pub const PRICE: u32 = 2;
+    pub const AVERAGE_PRICE: u32 = 2;
+    pub const BOOK_TICKER: u32 = 2;
+    pub const KLINES: u32 = 2;
+    pub const ORDER: u32 = 1;
+    pub const TEST_ORDER: u32 = 1;
+    pub const ACCOUNT: u32 = 20;
+    pub const DUST_TRANSFER: u32 = 10;
+    pub const SYSTEM_STATUS: u32 = 1;
+}
+
+/// Server-side request weight as reported by Binance, shared by every client of one api key.
+///
+/// Binance resets the weight at every minute boundary. Past `threshold` the calls are
+/// slowed down proportionally, at `limit` they wait for the next minute.
+///
+/// Between two reports the weight of the requests sent is added with [`UsedWeight::record`],
+/// a report from the headers replaces that tally with the server-side usage.
+#[derive(Debug)]
+pub struct UsedWeight {
+    limit: u32,
+    threshold: u32,
+    clock: Arc<dyn Clock>,
+
+    /// Last reported weight and when it was reported
+    used: Mutex<Option<(u32, DateTime<Utc>)>>,
+}
+
+impl UsedWeight {
+    /// Binance spot request weight limit per minute.
+    pub const DEFAULT_LIMIT: u32 = 1200;
+
+    /// Slows down from 80% of `limit`.
+    pub fn new(limit: u32) -> Self {
+        Self {
+            limit,
+            threshold: limit / 5 * 4,
+            clock: Arc::new(SystemClock),
+            used: Mutex::new(None),
+        }
+    }
+
+    pub fn with_threshold(mut self, threshold: u32) -> Self {
+        self.threshold = threshold.min(self.limit);
+        self
+    }
+
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn observe(&self, used: u32) {
+        *self.used.lock().ignore_poison() = Some((used, self.clock.now()));
+    }
+
+    /// Add the weight of a request sent in the current minute.
+    pub fn record(&self, weight: u32) {
+        let now = self.clock.now();
+        let mut used = self.used.lock().ignore_poison();
+        let current = Self::current(*used, &now);
+        *used = Some((current.saturating_add(weight), now));
+    }
+
+    /// Record the used weight from response headers, other headers are ignored.
+    pub fn observe_headers<'a, I>(&self, headers: I)
+    where
+        I: IntoIterator<Item = (&'a str, &'a str)>,
+    {
+        let used = headers
+            .into_iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(USED_WEIGHT_HEADER))
+            .and_then(|(_, value)| value.trim().parse::<u32>().ok());
+
+        if let Some(used) = used {
+            self.observe(used);
+        }
+    }
+
+    /// Weight used in the current minute, a report from a previous minute has expired.
+    pub fn used(&self) -> u32 {
+        let now = self.clock.now();
+        Self::current(*self.used.lock().ignore_poison(), &now)
+    }
+
+    /// How long to wait before the next request.
+    pub fn delay(&self) -> Duration {
+        let used = self.used();
+        if used <= self.threshold {
+            return Duration::ZERO;
+        }
+
+        let now = self.clock.now();
+        let elapsed = u64::from(now.second()) * 1000 + u64::from(now.nanosecond() / 1_000_000);
+        let remaining = Duration::from_millis(60_000_u64.saturating_sub(elapsed));
+
+        let over = used - self.threshold;
+        let span = (self.limit - self.threshold).max(1);
+        match over >= span {
+            true => remaining,
+            false => remaining.mul_f64(f64::from(over) / f64::from(span)),
+        }
+    }
+
+    /// Sleep for [`UsedWeight::delay`].
+    pub async fn throttle(&self) {
+        let delay = self.delay();
+        if !delay.is_zero() {
+            warn!(
+                "used weight {} of {}, throttle for {:?}",
+                self.used(),
+                self.limit,
+                delay
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    fn current(used: Option<(u32, DateTime<Utc>)>, now: &DateTime<Utc>) -> u32 {
+        match used {
+            Some((used, observed_at)) if Self::window(&observed_at) == Self::window(now) => used,
+            _ => 0,
+        }
+    }
+
+    fn window(time: &DateTime<Utc>) -> i64 {
+        time.timestamp().div_euclid(60)
+    }
+}
+
+impl Default for UsedWeight {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_LIMIT)
+    }
+}
+
+/// Client-side token bucket of request weight, shared by every client of one api key.
+///
+/// Where [`UsedWeight`] reacts to the weight Binance reports, this spaces the requests
+/// out before they are sent. The bucket holds up to `capacity` weight and refills
+/// continuously at `weight` per `window`, a request waits until its weight is available.
+/// Waiting requests are served in the order they arrived.
+#[derive(Debug)]
+pub struct RateLimiter {
+    weight: u32,
+    window: Duration,
+    capacity: u32,
+
+    /// Held while a request waits, so the next ones queue behind it
+    bucket: tokio::sync::Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    available: f64,
+    refilled_at: Instant,
+}
+
+impl RateLimiter {
+    /// Binance spot request weight limit per minute.
+    pub const DEFAULT_WEIGHT: u32 = UsedWeight::DEFAULT_LIMIT;
+    pub const DEFAULT_WINDOW: Duration = Duration::from_secs(60);
+
+    /// `weight` per `window`, bursts of up to `weight` at once.
+    pub fn new(weight: u32, window: Duration) -> Self {
+        let weight = weight.max(1);
+        Self {
+            weight,
+            window,
+            capacity: weight,
+            bucket: tokio::sync::Mutex::new(Bucket {
+                available: f64::from(weight),
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Limit the bursts to `capacity`, starting with a bucket of that much.
+    pub fn with_capacity(mut self, capacity: u32) -> Self {
+        self.capacity = capacity.max(1);
+        self.bucket.get_mut().available = f64::from(self.capacity);
+        self
+    }
+
+    /// Wait until `weight` is available and take it, a weight above the capacity takes
+    /// the whole bucket.
+    pub async fn acquire(&self, weight: u32) {
+        let weight = f64::from(weight.min(self.capacity));
+        let mut bucket = self.bucket.lock().await;
+
+        loop {
+            self.refill(&mut bucket);
+            if bucket.available >= weight {
+                bucket.available -= weight;
+                return;
+            }
+
+            let wait = Duration::from_secs_f64((weight - bucket.available) / self.rate());
+            debug!("rate limited, wait {:?} for weight {}", wait, weight);
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    // Weight refilled per second
+    fn rate(&self) -> f64 {
+        f64::from(self.weight) / self.window.as_secs_f64().max(f64::EPSILON)
+    }
+
+    fn refill(&self, bucket: &mut Bucket) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.available = (bucket.available + elapsed * self.rate()).min(f64::from(self.capacity));
+        bucket.refilled_at = now;
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_WEIGHT, Self::DEFAULT_WINDOW)
+    }
+}
+
+#[cfg(test)]
+mod tests_used_weight {
+    use chrono::TimeZone;
+
+    use crate::clock::MockClock;
+
+    use super::*;
+
+    #[test]
+    fn test_delay() {
+        // 15 seconds into the minute
+        let clock = Arc::new(MockClock::new(
+            Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 15).unwrap(),
+        ));
+        let used_weight = UsedWeight::new(1200).with_clock(clock.clone());
+        assert_eq!(used_weight.delay(), Duration::ZERO);
+
+        used_weight.observe_headers([
+            ("Content-Type", "application/json"),
+            ("X-MBX-USED-WEIGHT-1M", "900"),
+        ]);
+        assert_eq!(used_weight.used(), 900);
+        assert_eq!(used_weight.delay(), Duration::ZERO);
+
+        // Halfway between the threshold 960 and the limit
+        used_weight.observe(1080);
+        assert_eq!(used_weight.delay(), Duration::from_millis(22_500));
+
+        // At the limit, wait for the next minute
+        used_weight.observe(1200);
+        assert_eq!(used_weight.delay(), Duration::from_secs(45));
+
+        // The weight resets with the minute
+        clock.advance(chrono::Duration::try_seconds(50).unwrap());
+        assert_eq!(used_weight.used(), 0);
+        assert_eq!(used_weight.delay(), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_record() {
+        let clock = Arc::new(MockClock::new(
+            Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 15).unwrap(),
+        ));
+        let used_weight = UsedWeight::new(1200).with_clock(clock.clone());
+        used_weight.record(20);
+        used_weight.record(1);
+        assert_eq!(used_weight.used(), 21);
+
+        // A report replaces the tally, the next requests add to it
+        used_weight.observe_headers([("x-mbx-used-weight-1m", "1100")]);
+        used_weight.record(2);
+        assert_eq!(used_weight.used(), 1102);
+
+        // The tally of a previous minute has expired
+        clock.advance(chrono::Duration::try_seconds(50).unwrap());
+        used_weight.record(2);
+        assert_eq!(used_weight.used(), 2);
+    }
+}
+
+#[cfg(test)]
+mod tests_rate_limiter {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_acquire() {
+        // One weight every 10 milliseconds, no bursts
+        let limiter = Arc::new(RateLimiter::new(100, Duration::from_secs(1)).with_capacity(1));
+        let start = Instant::now();
+
+        let mut handles = Vec::new();
+        for _ in 0..5 {
+            let limiter = limiter.clone();
+            handles.push(tokio::spawn(async move {
+                limiter.acquire(1).await;
+                Instant::now()
+            }));
+        }
+
+        let mut acquired = Vec::new();
+        for handle in handles {
+            acquired.push(handle.await.unwrap());
+        }
+        acquired.sort();
+
+        for pair in acquired.windows(2) {
+            let gap = pair[1].duration_since(pair[0]);
+            assert!(gap >= Duration::from_millis(9), "{:?}", gap);
+        }
+        assert!(start.elapsed() >= Duration::from_millis(40));
+
+        // A heavier request waits for its whole weight
+        let limiter = RateLimiter::new(100, Duration::from_secs(1)).with_capacity(5);
+        limiter.acquire(5).await;
+        let start = Instant::now();
+        limiter.acquire(2).await;
+        assert!(start.elapsed() >= Duration::from_millis(19));
+    }
+}
@@ -0,0 +1,579 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use binance::{
+    api::Binance,
+    market::Market,
+    rest_model::{self, KlineSummaries, KlineSummary},
+    wallet::Wallet,
+};
+use rust_decimal::prelude::FromPrimitive;
+use tracing::{debug, warn};
+
+use super::error::MarketClientError;
+use crate::{
+    env,
+    extension::LockResultExt,
+    limiter::{weight, RateLimiter},
+    noun::*,
+};
+
+pub type MarketClientResult<T> = Result<T, MarketClientError>;
+
+// ===== Market Client =====
+pub struct MarketClient {
+    pub market: Market,
+    pub wallet: Wallet,
+
+    /// Shared by every client trading the same symbols, `price` fetches at most once per TTL
+    price_cache: Option<Arc<PriceCache>>,
+
+    /// Open time of the first kline by symbol, a listing never moves
+    listing_times: Mutex<HashMap<Symbol, i64>>,
+
+    /// Client-side budget of request weight, shared with the other clients of the api key
+    rate_limiter: Option<Arc<RateLimiter>>,
+}
+
+// ===== Price Cache =====
+type PriceSlot = Arc<tokio::sync::Mutex<Option<(Instant, Price)>>>;
+
+/// Last prices by symbol, kept for a short TTL.
+///
+/// Calls for the same symbol wait on each other, so near-simultaneous calls share one fetch.
+#[derive(Debug)]
+pub struct PriceCache {
+    ttl: Duration,
+    slots: Mutex<HashMap<Symbol, PriceSlot>>,
+}
+
+impl PriceCache {
+    pub const DEFAULT_TTL: Duration = Duration::from_millis(250);
+
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            slots: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// The cached price of `symbol` when fresh, the result of `fetch` otherwise.
+    /// A failed fetch is not cached.
+    pub async fn get_or_fetch<F>(&self, symbol: &Symbol, fetch: F) -> MarketClientResult<Price>
+    where
+        F: Future<Output = MarketClientResult<Price>>,
+    {
+        let slot = self
+            .slots
+            .lock()
+            .ignore_poison()
+            .entry(symbol.clone())
+            .or_default()
+            .clone();
+
+        let mut slot = slot.lock().await;
+        if let Some((fetched_at, price)) = *slot {
+            if fetched_at.elapsed() < self.ttl {
+                debug!("cached price {} of {}", price, symbol);
+                return Ok(price);
+            }
+        }
+
+        let price = fetch.await?;
+        *slot = Some((Instant::now(), price));
+
+        Ok(price)
+    }
+}
+
+impl Default for PriceCache {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_TTL)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SystemStatus {
+    Normal,
+    Maintenance,
+}
+
+/// How [`MarketClient::aggregated_price`] combines the available sources.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum PriceAggregate {
+    /// Median of the available sources, a single outlier among three never wins
+    #[default]
+    Median,
+
+    /// Book mid when it lies within `max_deviation` (a ratio) of the median, the median otherwise
+    BookMidPreferred { max_deviation: Decimal },
+}
+
+/// Prices of one symbol from each endpoint, `None` when the endpoint failed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PriceSources {
+    pub last: Option<Price>,
+    pub average: Option<Price>,
+    pub book_mid: Option<Price>,
+}
+
+impl PriceSources {
+    pub fn aggregate(&self, aggregate: PriceAggregate) -> Option<Price> {
+        let mut prices: Vec<Price> = [self.last, self.average, self.book_mid]
+            .into_iter()
+            .flatten()
+            .collect();
+        prices.sort();
+
+        let middle = prices.len() / 2;
+        let median = match prices.len() {
+            0 => return None,
+            n if n % 2 == 0 => (prices[middle - 1] + prices[middle]) / Decimal::TWO,
+            _ => prices[middle],
+        };
+
+        // A zero median has no relative deviation, the book mid counts as too far off
+        let deviation = |book_mid: Price| (book_mid - median).checked_div(median);
+        match (aggregate, self.book_mid) {
+            (PriceAggregate::BookMidPreferred { max_deviation }, Some(book_mid))
+                if deviation(book_mid).is_some_and(|e| e.abs() <= max_deviation) =>
+            {
+                Some(book_mid)
+            }
+            _ => Some(median),
+        }
+    }
+}
+
+impl MarketClient {
+    pub fn new() -> Self {
+        Self {
+            market: Market::new(None, None),
+            wallet: Wallet::new(None, None),
+            price_cache: None,
+            listing_times: Mutex::new(HashMap::new()),
+            rate_limiter: None,
+        }
+    }
+
+    /// Against the endpoint of `BINANCE_API_URL` when set, see [`crate::env`].
+    pub fn from_env() -> MarketClientResult<Self> {
+        Self::from_env_with(|name| std::env::var(name))
+    }
+
+    /// Same as [`MarketClient::from_env`] with the variables read through `lookup`.
+    pub fn from_env_with<F>(lookup: F) -> MarketClientResult<Self>
+    where
+        F: Fn(&str) -> Result<String, std::env::VarError>,
+    {
+        let config = env::config(&lookup).map_err(MarketClientError::Environment)?;
+
+        Ok(Self {
+            market: Market::new_with_config(None, None, &config),
+            wallet: Wallet::new_with_config(None, None, &config),
+            ..Self::new()
+        })
+    }
+
+    pub fn with_price_cache(mut self, price_cache: Arc<PriceCache>) -> Self {
+        self.price_cache = Some(price_cache);
+        self
+    }
+
+    /// Take the weight of every request from `rate_limiter` before sending it.
+    pub fn with_rate_limiter(mut self, rate_limiter: Arc<RateLimiter>) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    async fn throttle(&self, weight: u32) {
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.acquire(weight).await;
+        }
+    }
+}
+
+impl Default for MarketClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MarketClient {
+    pub async fn price(&self, symbol: &Symbol) -> MarketClientResult<Price> {
+        match &self.price_cache {
+            Some(cache) => cache.get_or_fetch(symbol, self.fetch_price(symbol)).await,
+            None => self.fetch_price(symbol).await,
+        }
+    }
+
+    async fn fetch_price(&self, symbol: &Symbol) -> MarketClientResult<Price> {
+        self.throttle(weight::PRICE).await;
+        match self.market.get_price(symbol).await {
+            Ok(v) => Self::decimal(symbol, v.price),
+            Err(e) => Err(MarketClientError::Exchange {
+                context: format!("failed to get price of {}", symbol),
+                source: Box::new(e),
+            }),
+        }
+    }
+
+    /// Combine the last price, the average price and the book mid of `symbol`.
+    ///
+    /// A failed endpoint is left out, the call only fails when every endpoint does.
+    pub async fn aggregated_price(
+        &self,
+        symbol: &Symbol,
+        aggregate: PriceAggregate,
+    ) -> MarketClientResult<Price> {
+        let (last, average, book_mid) = tokio::join!(
+            self.price(symbol),
+            self.average_price(symbol),
+            self.book_mid_price(symbol)
+        );
+
+        let mut first_error = None;
+        let mut source = |result: MarketClientResult<Price>| match result {
+            Ok(price) => Some(price),
+            Err(e) => {
+                warn!("price source of {} unavailable: {}", symbol, e);
+                first_error.get_or_insert(e);
+                None
+            }
+        };
+        let sources = PriceSources {
+            last: source(last),
+            average: source(average),
+            book_mid: source(book_mid),
+        };
+
+        match sources.aggregate(aggregate) {
+            Some(price) => Ok(price),
+            None => Err(first_error.unwrap_or_else(|| {
+                MarketClientError::Client(format!("no price source of {}", symbol))
+            })),
+        }
+    }
+
+    pub async fn average_price(&self, symbol: &Symbol) -> MarketClientResult<Price> {
+        self.throttle(weight::AVERAGE_PRICE).await;
+        match self.market.get_average_price(symbol).await {
+            Ok(v) => Self::decimal(symbol, v.price),
+            Err(e) => Err(MarketClientError::Exchange {
+                context: format!("failed to get average price of {}", symbol),
+                source: Box::new(e),
+            }),
+        }
+    }
+
+    /// Midpoint of the best bid and ask.
+    pub async fn book_mid_price(&self, symbol: &Symbol) -> MarketClientResult<Price> {
+        self.throttle(weight::BOOK_TICKER).await;
+        match self.market.get_book_ticker(symbol).await {
+            Ok(v) => {
+                let bid = Self::decimal(symbol, v.bid_price)?;
+                let ask = Self::decimal(symbol, v.ask_price)?;
+                Ok((bid + ask) / Decimal::TWO)
+            }
+            Err(e) => Err(MarketClientError::Exchange {
+                context: format!("failed to get book ticker of {}", symbol),
+                source: Box::new(e),
+            }),
+        }
+    }
+
+    /// Close price of the 1 minute kline covering `timestamp` in milliseconds.
+    pub async fn historical_price_at(
+        &self,
+        symbol: &Symbol,
+        timestamp: i64,
+    ) -> MarketClientResult<Price> {
+        let start_time = u64::try_from(timestamp - timestamp % 60_000).map_err(|_| {
+            MarketClientError::Kline(format!("invalid timestamp {} of {}", timestamp, symbol))
+        })?;
+
+        self.throttle(weight::KLINES).await;
+        match self
+            .market
+            .get_klines(symbol, "1m", 1, start_time, None)
+            .await
+        {
+            Ok(klines) => Self::kline_price_at(symbol, klines, timestamp),
+            Err(e) => Err(MarketClientError::Exchange {
+                context: format!("failed to get klines of {}", symbol),
+                source: Box::new(e),
+            }),
+        }
+    }
+
+    /// Milliseconds of the first kline of `symbol`, when it started trading.
+    pub async fn symbol_listing_time(&self, symbol: &Symbol) -> MarketClientResult<i64> {
+        if let Some(listing_time) = self.listing_times.lock().ignore_poison().get(symbol) {
+            return Ok(*listing_time);
+        }
+
+        // Klines from the epoch start at the first one
+        self.throttle(weight::KLINES).await;
+        let listing_time = match self.market.get_klines(symbol, "1m", 1, 0, None).await {
+            Ok(klines) => Self::listing_time_from(symbol, klines)?,
+            Err(e) => {
+                return Err(MarketClientError::Exchange {
+                    context: format!("failed to get first kline of {}", symbol),
+                    source: Box::new(e),
+                })
+            }
+        };
+
+        debug!("{} listed at {}", symbol, listing_time);
+        self.listing_times
+            .lock()
+            .ignore_poison()
+            .insert(symbol.clone(), listing_time);
+
+        Ok(listing_time)
+    }
+
+    /// `start` in milliseconds moved up to the listing of `symbol`, e.g. for a backtest.
+    pub async fn clamp_to_listing(&self, symbol: &Symbol, start: i64) -> MarketClientResult<i64> {
+        Ok(start.max(self.symbol_listing_time(symbol).await?))
+    }
+
+    pub async fn system_status(&self) -> MarketClientResult<SystemStatus> {
+        self.throttle(weight::SYSTEM_STATUS).await;
+        match self.wallet.system_status().await {
+            Ok(status) => Self::system_status_from(&status),
+            Err(e) => Err(MarketClientError::Exchange {
+                context: String::from("failed to get system status"),
+                source: Box::new(e),
+            }),
+        }
+    }
+
+    fn system_status_from(status: &rest_model::SystemStatus) -> MarketClientResult<SystemStatus> {
+        match status.status {
+            0 => Ok(SystemStatus::Normal),
+            1 => Ok(SystemStatus::Maintenance),
+            _ => Err(MarketClientError::Client(format!(
+                "unknown system status {} {}",
+                status.status, status.msg
+            ))),
+        }
+    }
+
+    fn listing_time_from(symbol: &Symbol, klines: KlineSummaries) -> MarketClientResult<i64> {
+        let KlineSummaries::AllKlineSummaries(klines) = klines;
+
+        match klines.iter().map(|e| e.open_time).min() {
+            Some(open_time) => Ok(open_time),
+            None => Err(MarketClientError::Kline(format!("no kline of {}", symbol))),
+        }
+    }
+
+    fn kline_price_at(
+        symbol: &Symbol,
+        klines: KlineSummaries,
+        timestamp: i64,
+    ) -> MarketClientResult<Price> {
+        let KlineSummaries::AllKlineSummaries(klines) = klines;
+
+        // Klines start after the requested time when the symbol was not listed yet
+        let covering = |e: &&KlineSummary| e.open_time <= timestamp && timestamp <= e.close_time;
+        match klines.iter().find(covering) {
+            Some(kline) => Self::decimal(symbol, kline.close),
+            None => Err(MarketClientError::Kline(format!(
+                "no kline of {} covering {}, possibly before listing",
+                symbol, timestamp
+            ))),
+        }
+    }
+
+    fn decimal(symbol: &Symbol, value: f64) -> MarketClientResult<Decimal> {
+        Decimal::from_f64(value).ok_or_else(|| MarketClientError::Decimal {
+            symbol: symbol.clone(),
+            value: value.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests_market_client {
+    use super::*;
+
+    fn kline(open_time: i64, close: f64) -> KlineSummary {
+        KlineSummary {
+            open_time,
+            open: 43100.0,
+            high: 43200.0,
+            low: 43000.0,
+            close,
+            volume: 12.5,
+            close_time: open_time + 59_999,
+            quote_asset_volume: 539_375.0,
+            number_of_trades: 1024,
+            taker_buy_base_asset_volume: 6.0,
+            taker_buy_quote_asset_volume: 258_900.0,
+        }
+    }
+
+    #[test]
+    fn test_kline_price_at() {
+        let symbol = String::from("BTCUSDT");
+
+        let klines = KlineSummaries::AllKlineSummaries(vec![kline(1_700_000_040_000, 43145.42)]);
+        let price = MarketClient::kline_price_at(&symbol, klines, 1_700_000_071_234).unwrap();
+        assert_eq!(price, Decimal::from_f64(43145.42).unwrap());
+
+        let klines = KlineSummaries::AllKlineSummaries(vec![kline(1_700_000_040_000, 43145.42)]);
+        let error = MarketClient::kline_price_at(&symbol, klines, 1_600_000_000_000).unwrap_err();
+        assert!(matches!(error, MarketClientError::Kline(_)));
+
+        let klines = KlineSummaries::AllKlineSummaries(vec![]);
+        assert!(MarketClient::kline_price_at(&symbol, klines, 1_600_000_000_000).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_symbol_listing_time() {
+        let symbol = String::from("BTCUSDT");
+        let klines = KlineSummaries::AllKlineSummaries(vec![kline(1_502_942_400_000, 4261.48)]);
+        let listing_time = MarketClient::listing_time_from(&symbol, klines).unwrap();
+        assert_eq!(listing_time, 1_502_942_400_000);
+
+        let klines = KlineSummaries::AllKlineSummaries(vec![]);
+        assert!(MarketClient::listing_time_from(&symbol, klines).is_err());
+
+        // Answered from the cache, without a request
+        let client = MarketClient::new();
+        client
+            .listing_times
+            .lock()
+            .unwrap()
+            .insert(symbol.clone(), listing_time);
+        assert_eq!(
+            client
+                .clamp_to_listing(&symbol, 1_500_000_000_000)
+                .await
+                .unwrap(),
+            1_502_942_400_000
+        );
+        assert_eq!(
+            client
+                .clamp_to_listing(&symbol, 1_700_000_000_000)
+                .await
+                .unwrap(),
+            1_700_000_000_000
+        );
+    }
+
+    #[tokio::test]
+    async fn test_price_cache() {
+        let cache = Arc::new(PriceCache::new(Duration::from_millis(250)));
+        let symbol = String::from("BTCUSDT");
+        let fetches = Arc::new(Mutex::new(0));
+        let fetch = |value: f64| {
+            let fetches = fetches.clone();
+            async move {
+                *fetches.lock().unwrap() += 1;
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                Ok(Decimal::from_f64(value).unwrap())
+            }
+        };
+
+        // Two rapid calls, the second waits for the first fetch and reuses it
+        let (first, second) = tokio::join!(
+            cache.get_or_fetch(&symbol, fetch(43145.42)),
+            cache.get_or_fetch(&symbol, fetch(43150.0))
+        );
+        assert_eq!(first.unwrap(), second.unwrap());
+        assert_eq!(*fetches.lock().unwrap(), 1);
+
+        // Other symbols have their own entry
+        let other = String::from("ETHUSDT");
+        cache.get_or_fetch(&other, fetch(2250.0)).await.unwrap();
+        assert_eq!(*fetches.lock().unwrap(), 2);
+
+        // Expired, fetched again
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let price = cache.get_or_fetch(&symbol, fetch(43150.0)).await.unwrap();
+        assert_eq!(price, Decimal::from_f64(43150.0).unwrap());
+        assert_eq!(*fetches.lock().unwrap(), 3);
+
+        // A failure is not cached
+        let failed = cache.get_or_fetch(&other, async {
+            Err(MarketClientError::Client(String::from("timeout")))
+        });
+        assert!(failed.await.is_err());
+        cache.get_or_fetch(&other, fetch(2251.0)).await.unwrap();
+        assert_eq!(*fetches.lock().unwrap(), 4);
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let price = |value: f64| Some(Decimal::from_f64(value).unwrap());
+
+        // The stale last price is far off, the median ignores it
+        let sources = PriceSources {
+            last: price(39000.0),
+            average: price(43140.0),
+            book_mid: price(43150.5),
+        };
+        assert_eq!(sources.aggregate(PriceAggregate::Median), price(43140.0));
+
+        let book_mid_preferred = PriceAggregate::BookMidPreferred {
+            max_deviation: Decimal::from_f64(0.001).unwrap(),
+        };
+        assert_eq!(sources.aggregate(book_mid_preferred), price(43150.5));
+
+        // An outlier book mid is rejected in favour of the median
+        let sources = PriceSources {
+            last: price(43145.0),
+            average: price(43140.0),
+            book_mid: price(47000.0),
+        };
+        assert_eq!(sources.aggregate(book_mid_preferred), price(43145.0));
+
+        let sources = PriceSources {
+            last: price(0.0),
+            average: price(0.0),
+            book_mid: price(0.5),
+        };
+        assert_eq!(sources.aggregate(book_mid_preferred), price(0.0));
+
+        // One endpoint down, the remaining two are averaged
+        let sources = PriceSources {
+            last: price(43145.0),
+            average: None,
+            book_mid: price(43151.0),
+        };
+        assert_eq!(sources.aggregate(PriceAggregate::Median), price(43148.0));
+
+        assert_eq!(
+            PriceSources::default().aggregate(PriceAggregate::Median),
+            None
+        );
+    }
+
+    #[test]
+    fn test_system_status_from() {
+        let response = r#"{"status": 1, "msg": "system maintenance"}"#;
+        let status: rest_model::SystemStatus = serde_json::from_str(response).unwrap();
+        let status = MarketClient::system_status_from(&status).unwrap();
+        assert_eq!(status, SystemStatus::Maintenance);
+
+        let response = r#"{"status": 0, "msg": "normal"}"#;
+        let status: rest_model::SystemStatus = serde_json::from_str(response).unwrap();
+        let status = MarketClient::system_status_from(&status).unwrap();
+        assert_eq!(status, SystemStatus::Normal);
+
+        let response = r#"{"status": 7, "msg": "unknown"}"#;
+        let status: rest_model::SystemStatus = serde_json::from_str(response).unwrap();
+        assert!(MarketClient::system_status_from(&status).is_err());
+    }
+}
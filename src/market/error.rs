@@ -0,0 +1,46 @@
+use std::{error::Error, fmt::Display};
+
+use crate::noun::*;
+
+#[derive(Debug)]
+pub enum MarketClientError {
+    Client(String),
+    Decimal {
+        symbol: Symbol,
+        value: String,
+    },
+    Kline(String),
+
+    /// A variable read by `from_env` is missing or blank
+    Environment(String),
+
+    /// A request to the exchange failed, `context` tells which one
+    Exchange {
+        context: String,
+        source: Box<binance::errors::Error>,
+    },
+}
+
+impl Error for MarketClientError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Exchange { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl Display for MarketClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Client(e) => write!(f, "{}", e),
+            Self::Decimal { symbol, value } => {
+                write!(f, "{} price {} to decimal error", symbol, value)
+            }
+            Self::Kline(e) => write!(f, "{}", e),
+            Self::Environment(e) => write!(f, "{}", e),
+            // The cause is left to `source`, so reporters walking the chain print it once
+            Self::Exchange { context, .. } => write!(f, "{}", context),
+        }
+    }
+}
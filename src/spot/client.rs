@@ -1,16 +1,33 @@
-use std::sync::Arc;
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
 use binance::{
     account::{Account, OrderRequest},
     api::Binance,
+    config::Config,
     market::Market,
+    rest_model::{OrderStatus, Transaction},
 };
 use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use serde::{Deserialize, Serialize};
+use tracing::{error, warn};
 
-use super::{error::SpotClientError, timestamp_millis, Spot, SpotBuying, SpotSelling};
+use super::{
+    error::{DecimalField, SpotClientError, TIMESTAMP_OUTSIDE_RECV_WINDOW},
+    router::{BinanceRouter, DustConversion, Fill, OrderIntent, OrderKind, OrderRouter},
+    timestamp_millis, Spot, SpotBuying, SpotSelling,
+};
 use crate::{
+    env,
+    limiter::{weight, RateLimiter, UsedWeight},
     noun::*,
-    strategy::{AmountPoint, Exchanger, PinFutureResult, PricePoint, QuantityPoint},
+    strategy::{AmountPoint, Exchanger, PinFutureResult, PricePoint, QuantityPoint, TradeSide},
 };
 
 type SpotClientResult<T> = Result<T, SpotClientError>;
@@ -20,6 +37,18 @@ pub struct SpotClient {
     spot: Spot,
     option: Option<SpotClientOption>,
 
+    /// Set by `arm_production` once the credentials are verified
+    armed: AtomicBool,
+
+    /// Venue the production orders are placed on
+    router: Arc<dyn OrderRouter>,
+
+    /// Server-side weight of the api key, requests slow down as it nears the limit
+    used_weight: Option<Arc<UsedWeight>>,
+
+    /// Client-side budget of request weight, shared with the other clients of the api key
+    rate_limiter: Option<Arc<RateLimiter>>,
+
     pub market: Market,
     pub client: Account,
 }
@@ -31,20 +60,300 @@ impl SpotClient {
         spot: Spot,
         option: Option<SpotClientOption>,
     ) -> Self {
-        let client = Account::new(Some(api_key.clone()), Some(secret_key.clone()));
-        let market = Market::new(None, None);
+        Self::new_with_config(api_key, secret_key, spot, option, &Config::default())
+    }
+
+    /// Read the api keys from `BINANCE_API_KEY` and `BINANCE_SECRET_KEY` and the
+    /// endpoint from `BINANCE_API_URL`, see [`crate::env`].
+    pub fn from_env(spot: Spot, option: Option<SpotClientOption>) -> SpotClientResult<Self> {
+        Self::from_env_with(spot, option, |name| std::env::var(name))
+    }
+
+    /// Same as [`SpotClient::from_env`] with the variables read through `lookup`.
+    pub fn from_env_with<F>(
+        spot: Spot,
+        option: Option<SpotClientOption>,
+        lookup: F,
+    ) -> SpotClientResult<Self>
+    where
+        F: Fn(&str) -> Result<String, std::env::VarError>,
+    {
+        let required = |name| env::required(&lookup, name).map_err(SpotClientError::Environment);
+        let api_key = required(env::API_KEY)?;
+        let secret_key = required(env::SECRET_KEY)?;
+        let config = env::config(&lookup).map_err(SpotClientError::Environment)?;
+
+        Ok(Self::new_with_config(
+            api_key, secret_key, spot, option, &config,
+        ))
+    }
+
+    /// Same as [`SpotClient::new`] against the endpoint of `config`, e.g. the testnet.
+    pub fn new_with_config(
+        api_key: String,
+        secret_key: String,
+        spot: Spot,
+        option: Option<SpotClientOption>,
+        config: &Config,
+    ) -> Self {
+        let is_production = option.as_ref().is_some_and(|e| e.is_production);
+        if is_production && (is_placeholder_key(&api_key) || is_placeholder_key(&secret_key)) {
+            error!(
+                "production mode for {} with placeholder api keys, signed requests will fail",
+                spot.symbol()
+            );
+        }
+
+        let client = Account::new_with_config(Some(api_key), Some(secret_key), config);
+        let market = Market::new_with_config(None, None, config);
         Self {
             spot,
             option,
+            armed: AtomicBool::new(false),
+            router: Arc::new(BinanceRouter::new(client.clone())),
+            used_weight: None,
+            rate_limiter: None,
             client,
             market,
         }
     }
+
+    /// Place the production orders through `router` in place of Binance.
+    pub fn with_router(mut self, router: Arc<dyn OrderRouter>) -> Self {
+        self.router = router;
+        self
+    }
+
+    /// Throttle the requests on the weight used in the current minute.
+    ///
+    /// Every request records its weight, the binance crate does not expose the response
+    /// headers so feed the `X-MBX-USED-WEIGHT-1M` reports to [`UsedWeight::observe_headers`]
+    /// from wherever they are available.
+    pub fn with_used_weight(mut self, used_weight: Arc<UsedWeight>) -> Self {
+        self.used_weight = Some(used_weight);
+        self
+    }
+
+    /// Take the weight of every request from `rate_limiter` before sending it.
+    pub fn with_rate_limiter(mut self, rate_limiter: Arc<RateLimiter>) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+}
+
+// The account client signs with the api keys, they are only ever printed redacted
+impl std::fmt::Debug for SpotClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SpotClient")
+            .field("symbol", self.spot.symbol())
+            .field("is_production", &self.is_production())
+            .field("is_armed", &self.is_armed())
+            .field("api_key", &"***")
+            .field("secret_key", &"***")
+            .finish_non_exhaustive()
+    }
+}
+
+// Empty keys and the "null" literal used across tests are never real credentials
+fn is_placeholder_key(key: &str) -> bool {
+    let key = key.trim();
+
+    key.is_empty() || key.eq_ignore_ascii_case("null")
 }
 
+/// Runtime options of a [`SpotClient`], loadable from a config file.
+///
+/// Holds no secrets, the api keys are given to the client directly.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
 pub struct SpotClientOption {
     // Note that when true all transactions will be submitted to the exchange
     pub is_production: bool,
+
+    /// Which asset the trading commission is deducted from
+    pub commission_deduction: CommissionDeduction,
+
+    /// Warn when a fetched price carries digits beyond this precision, flagging float noise
+    pub strict_price_precision: Option<Precision>,
+
+    /// How the `MIN_NOTIONAL` filter of the symbol treats market orders
+    pub market_notional: MarketNotional,
+
+    /// Retries of the requests failing with a transient error, none by default
+    pub retry_policy: RetryPolicy,
+}
+
+/// Retries with an exponential backoff, `base_delay` before the first retry and doubling after.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Run `f` until it succeeds, fails with an error `is_retryable` rejects or the
+    /// retries run out, the last error is returned then.
+    pub async fn retry<T, F, Fut>(
+        &self,
+        is_retryable: fn(&SpotClientError) -> bool,
+        mut f: F,
+    ) -> SpotClientResult<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = SpotClientResult<T>>,
+    {
+        let mut retries = 0;
+        loop {
+            match f().await {
+                Err(e) if retries < self.max_retries && is_retryable(&e) => {
+                    let delay = self.delay(retries, &e);
+                    retries += 1;
+                    warn!(
+                        "{}, retry {} of {} in {:?}",
+                        e, retries, self.max_retries, delay
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Backoff before the retry following `retries` earlier ones, at least what a rate
+    /// limiting `error` asks for.
+    pub fn delay(&self, retries: u32, error: &SpotClientError) -> Duration {
+        let backoff = self.base_delay.saturating_mul(2u32.saturating_pow(retries));
+        match error.retry_after() {
+            Some(retry_after) => backoff.max(retry_after),
+            None => backoff,
+        }
+    }
+}
+
+/// Basis of the minimum notional check for market orders.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+pub enum MarketNotional {
+    /// Check against the price the order is placed at
+    #[default]
+    OrderPrice,
+
+    /// `applyToMarket` is set, Binance checks against the average price over `avgPriceMins`
+    AveragePrice,
+
+    /// `applyToMarket` is not set, market orders are never checked
+    Exempt,
+}
+
+/// Endpoint a price was served by.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PriceSource {
+    /// Last trade price, `/api/v3/ticker/price`
+    Last,
+
+    /// Average price over `avgPriceMins`, `/api/v3/avgPrice`
+    Average,
+}
+
+/// A price together with where it came from and how long it took.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PriceQuote {
+    pub price: Price,
+    pub source: PriceSource,
+
+    /// Milliseconds since the epoch when the response arrived
+    pub fetched_at: i64,
+    pub latency_ms: u64,
+}
+
+impl PriceQuote {
+    /// Time the request `f` made to `source`.
+    pub async fn measure<F, E>(source: PriceSource, f: F) -> Result<Self, E>
+    where
+        F: Future<Output = Result<Price, E>>,
+    {
+        let start = Instant::now();
+        let price = f.await?;
+        let latency_ms = u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX);
+
+        Ok(Self {
+            price,
+            source,
+            fetched_at: timestamp_millis(),
+            latency_ms,
+        })
+    }
+}
+
+/// Totals over the fills of a single order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FillSummary {
+    pub quantity: Quantity,
+    pub quote_quantity: Amount,
+
+    /// Commission paid per asset in the order first seen, e.g. `BNB` when paying fees with BNB
+    pub commissions: Vec<(Symbol, Commission)>,
+}
+
+impl FillSummary {
+    pub fn from_transaction(
+        symbol: &Symbol,
+        transaction: &Transaction,
+    ) -> Result<Self, SpotClientError> {
+        let decimal = |field: DecimalField, value: f64| {
+            Decimal::from_f64(value).ok_or_else(|| SpotClientError::Decimal {
+                symbol: symbol.clone(),
+                field,
+                value: value.to_string(),
+            })
+        };
+        let mut summary = Self::default();
+
+        for fill in transaction.fills.iter() {
+            let price = decimal(DecimalField::Price, fill.price)?;
+            let quantity = decimal(DecimalField::Quantity, fill.qty)?;
+            let commission = decimal(DecimalField::Commission, fill.commission)?;
+
+            summary.quantity = checked_add(summary.quantity, quantity)?;
+            summary.quote_quantity =
+                checked_add(summary.quote_quantity, checked_mul(price, quantity)?)?;
+
+            match summary
+                .commissions
+                .iter_mut()
+                .find(|(asset, _)| asset == &fill.commission_asset)
+            {
+                Some((_, total)) => *total = checked_add(*total, commission)?,
+                None => summary
+                    .commissions
+                    .push((fill.commission_asset.clone(), commission)),
+            }
+        }
+
+        Ok(summary)
+    }
+
+    /// Average fill price weighted by quantity.
+    pub fn average_price(&self) -> Option<Price> {
+        self.quote_quantity.checked_div(self.quantity)
+    }
+
+    pub fn commission(&self, asset: &str) -> Commission {
+        self.commissions
+            .iter()
+            .filter(|(e, _)| e == asset)
+            .map(|(_, commission)| *commission)
+            .sum()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+pub enum CommissionDeduction {
+    /// Deducted from the asset received, base on buying and quote on selling
+    #[default]
+    Received,
+
+    /// Deducted from the quote asset on both buying and selling
+    Quote,
 }
 
 impl SpotClient {
@@ -55,69 +364,275 @@ impl SpotClient {
         }
     }
 
+    /// Verify the credentials through the router, a signed account request on Binance,
+    /// required once before a production client places any real order.
+    pub async fn arm_production(&self) -> SpotClientResult<()> {
+        self.throttle(weight::ACCOUNT).await;
+        self.router.verify().await?;
+
+        self.armed.store(true, Ordering::SeqCst);
+
+        Ok(())
+    }
+
+    pub fn is_armed(&self) -> bool {
+        self.armed.load(Ordering::SeqCst)
+    }
+
+    async fn throttle(&self, weight: u32) {
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.acquire(weight).await;
+        }
+
+        if let Some(used_weight) = &self.used_weight {
+            used_weight.throttle().await;
+            used_weight.record(weight);
+        }
+    }
+
+    fn check_armed(&self) -> SpotClientResult<()> {
+        match self.is_armed() {
+            true => Ok(()),
+            false => Err(SpotClientError::NotArmed),
+        }
+    }
+
+    pub fn commission_deduction(&self) -> CommissionDeduction {
+        match &self.option {
+            Some(v) => v.commission_deduction,
+            None => CommissionDeduction::default(),
+        }
+    }
+
+    pub fn market_notional(&self) -> MarketNotional {
+        match &self.option {
+            Some(v) => v.market_notional,
+            None => MarketNotional::default(),
+        }
+    }
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        match &self.option {
+            Some(v) => v.retry_policy,
+            None => RetryPolicy::default(),
+        }
+    }
+
+    pub fn strict_price_precision(&self) -> Option<Precision> {
+        match &self.option {
+            Some(v) => v.strict_price_precision,
+            None => None,
+        }
+    }
+
     pub async fn price(&self) -> SpotClientResult<Price> {
-        match self.market.get_price(self.spot.symbol()).await {
-            Ok(v) => {
-                let price = Decimal::from_f64(v.price)
-                    .ok_or(SpotClientError::Decimal(v.price.to_string()))?;
+        Ok(self.price_quote().await?.price)
+    }
 
-                Ok(price)
+    /// Last price with its source and latency, for diagnosing slow symbols.
+    ///
+    /// The latency is of the last attempt when a transient error was retried.
+    pub async fn price_quote(&self) -> SpotClientResult<PriceQuote> {
+        let policy = self.retry_policy();
+        policy
+            .retry(SpotClientError::is_transient, || async {
+                self.throttle(weight::PRICE).await;
+                PriceQuote::measure(PriceSource::Last, async {
+                    match self.market.get_price(self.spot.symbol()).await {
+                        Ok(v) => self.price_from_f64(v.price),
+                        Err(e) => Err(SpotClientError::Exchange {
+                            context: format!("failed to get price of {}", self.spot.symbol()),
+                            source: Box::new(e),
+                        }),
+                    }
+                })
+                .await
+            })
+            .await
+    }
+
+    /// Average price over the window Binance uses for `avgPriceMins`.
+    pub async fn market_average_price(&self) -> SpotClientResult<Price> {
+        Ok(self.average_price_quote().await?.price)
+    }
+
+    pub async fn average_price_quote(&self) -> SpotClientResult<PriceQuote> {
+        let policy = self.retry_policy();
+        policy
+            .retry(SpotClientError::is_transient, || async {
+                self.throttle(weight::AVERAGE_PRICE).await;
+                PriceQuote::measure(PriceSource::Average, async {
+                    match self.market.get_average_price(self.spot.symbol()).await {
+                        Ok(v) => self.price_from_f64(v.price),
+                        Err(e) => Err(SpotClientError::Exchange {
+                            context: format!(
+                                "failed to get average price of {}",
+                                self.spot.symbol()
+                            ),
+                            source: Box::new(e),
+                        }),
+                    }
+                })
+                .await
+            })
+            .await
+    }
+
+    fn price_from_f64(&self, value: f64) -> SpotClientResult<Price> {
+        let price = Decimal::from_f64(value).ok_or_else(|| SpotClientError::Decimal {
+            symbol: self.spot.symbol().clone(),
+            field: DecimalField::Price,
+            value: value.to_string(),
+        })?;
+
+        if let Some(precision) = self.strict_price_precision() {
+            let discrepancy = (price - price.round_dp(precision)).abs();
+            if !discrepancy.is_zero() || price.to_f64() != Some(value) {
+                warn!(
+                    "suspect {} price {} from feed, discrepancy {} beyond precision {}",
+                    self.spot.symbol(),
+                    value,
+                    discrepancy,
+                    precision
+                );
             }
-            Err(e) => Err(SpotClientError::Price(e.to_string())),
         }
+
+        Ok(price)
     }
 
     pub async fn buy(&self, price: &Price, amount: &Amount) -> SpotClientResult<SpotBuying> {
-        let buying_quantity = self.spot.buying_quantity_by_amount(price, amount);
-        self.is_allow_transaction(price, &buying_quantity)?;
+        let buying_quantity = self.buying_quantity_by_amount(price, amount)?;
+        self.buy_at_precision(price, buying_quantity, OrderKind::Market)
+            .await
+    }
+
+    /// Rest a buy order at `price` rounded to the tick size rather than crossing the spread.
+    ///
+    /// An order still resting is not filled yet, the buying is computed from the rounded price.
+    pub async fn buy_limit(&self, price: &Price, amount: &Amount) -> SpotClientResult<SpotBuying> {
+        let price = self.spot.price_to_tick(price);
+        let buying_quantity = self.buying_quantity_by_amount(&price, amount)?;
+        self.buy_at_precision(&price, buying_quantity, OrderKind::Limit { price })
+            .await
+    }
+
+    /// Buy a fixed `quantity` rather than the quantity an amount affords.
+    pub async fn buy_with_quantity(
+        &self,
+        price: &Price,
+        quantity: &Quantity,
+    ) -> SpotClientResult<SpotBuying> {
+        let buying_quantity = self.spot.transaction_quantity_with_precision(quantity);
+        self.buy_at_precision(price, buying_quantity, OrderKind::Market)
+            .await
+    }
+
+    // With the commission paid in quote on top of the order, the quantity is sized so that
+    // both together stay within `amount`
+    fn buying_quantity_by_amount(
+        &self,
+        price: &Price,
+        amount: &Amount,
+    ) -> SpotClientResult<Quantity> {
+        if self.commission_deduction() == CommissionDeduction::Received {
+            return Ok(self.spot.buying_quantity_by_amount(price, amount));
+        }
+
+        let net = checked_div(*amount, Decimal::ONE + self.spot.buying_commission)?;
+        let quantity = self.spot.buying_quantity_by_amount(price, &net);
+
+        // The rounded commission can still tip it over by a unit of the amount precision
+        let spent = self.spot.buying_spent_amount(price, &quantity)?;
+        match self.spot.buying_amount_with_commission(&spent)? > *amount {
+            true => Ok((quantity - self.spot.transaction_step()).max(Decimal::ZERO)),
+            false => Ok(quantity),
+        }
+    }
+
+    // `buying_quantity` is already at the transaction precision
+    async fn buy_at_precision(
+        &self,
+        price: &Price,
+        buying_quantity: Quantity,
+        kind: OrderKind,
+    ) -> SpotClientResult<SpotBuying> {
+        Self::validate_quantity(&buying_quantity)?;
+        self.is_allow_transaction(&kind, price, &buying_quantity)
+            .await?;
 
         if self.is_production() {
-            let buy = self
-                .client
-                .place_order(OrderRequest {
-                    symbol: self.spot.symbol().clone(),
-                    side: binance::rest_model::OrderSide::Buy,
-                    order_type: binance::rest_model::OrderType::Market,
-                    quantity: Some(buying_quantity.to_f64().unwrap()),
-                    price: None,
-                    ..OrderRequest::default()
-                })
-                .await;
+            self.check_armed()?;
+            let fill = self
+                .place(self.order_intent(TradeSide::Buy, &buying_quantity, kind))
+                .await?;
 
-            if let Err(e) = buy {
-                return Err(SpotClientError::Trading(e.to_string()));
+            if !fill.is_empty() {
+                return self.buying_from_fill(&fill);
             }
         }
 
-        Ok(self.calculator_buying(price, &buying_quantity))
+        self.calculator_buying(price, &buying_quantity)
     }
 
     pub async fn sell(&self, price: &Price, quantity: &Quantity) -> SpotClientResult<SpotSelling> {
-        let selling_quantity = self.spot.transaction_quantity_with_precision(quantity);
-        self.is_allow_transaction(price, &selling_quantity)?;
+        self.sell_with_kind(price, quantity, OrderKind::Market)
+            .await
+    }
+
+    /// Rest a sell order at `price` rounded to the tick size rather than crossing the spread.
+    ///
+    /// An order still resting is not filled yet, the selling is computed from the rounded price.
+    pub async fn sell_limit(
+        &self,
+        price: &Price,
+        quantity: &Quantity,
+    ) -> SpotClientResult<SpotSelling> {
+        let price = self.spot.price_to_tick(price);
+        self.sell_with_kind(&price, quantity, OrderKind::Limit { price })
+            .await
+    }
+
+    async fn sell_with_kind(
+        &self,
+        price: &Price,
+        quantity: &Quantity,
+        kind: OrderKind,
+    ) -> SpotClientResult<SpotSelling> {
+        let selling_quantity = self.spot.selling_quantity_with_precision(quantity);
+        Self::validate_quantity(&selling_quantity)?;
+        self.is_allow_transaction(&kind, price, &selling_quantity)
+            .await?;
 
         if self.is_production() {
-            let sell = self
-                .client
-                .place_order(OrderRequest {
-                    symbol: self.spot.symbol().clone(),
-                    side: binance::rest_model::OrderSide::Sell,
-                    order_type: binance::rest_model::OrderType::Market,
-                    quantity: Some(selling_quantity.to_f64().unwrap()),
-                    price: None,
-                    ..OrderRequest::default()
-                })
-                .await;
+            self.check_armed()?;
+            let fill = self
+                .place(self.order_intent(TradeSide::Sell, &selling_quantity, kind))
+                .await?;
 
-            if let Err(e) = sell {
-                return Err(SpotClientError::Trading(e.to_string()));
+            if !fill.is_empty() {
+                return self.selling_from_fill(&fill);
             }
         }
 
-        Ok(self.calculator_selling(price, &selling_quantity))
+        self.calculator_selling(price, &selling_quantity)
+    }
+
+    /// Sweep the dust balances of `assets` into BNB on the exchange, nothing is converted
+    /// outside production.
+    pub async fn convert_dust(&self, assets: &[String]) -> SpotClientResult<DustConversion> {
+        if !self.is_production() || assets.is_empty() {
+            return Ok(DustConversion::default());
+        }
+
+        self.check_armed()?;
+        self.throttle(weight::DUST_TRANSFER).await;
+        self.router.convert_dust(assets.to_vec()).await
     }
 
     pub async fn test_buy(&self, _price: &Price, quantity: &Quantity) -> SpotClientResult<()> {
+        Self::validate_quantity(quantity)?;
+        self.throttle(weight::TEST_ORDER).await;
         let buy = self
             .client
             .place_test_order(OrderRequest {
@@ -131,12 +646,14 @@ impl SpotClient {
             .await;
 
         if let Err(e) = buy {
-            return Err(SpotClientError::Trading(e.to_string()));
+            return Err(self.order_error("test buy", e));
         }
         Ok(())
     }
 
     pub async fn test_sell(&self, _price: &Price, quantity: &Quantity) -> SpotClientResult<()> {
+        Self::validate_quantity(quantity)?;
+        self.throttle(weight::TEST_ORDER).await;
         let buy = self
             .client
             .place_test_order(OrderRequest {
@@ -150,49 +667,243 @@ impl SpotClient {
             .await;
 
         if let Err(e) = buy {
-            return Err(SpotClientError::Trading(e.to_string()));
+            return Err(self.order_error("test sell", e));
         }
         Ok(())
     }
 
-    fn calculator_buying(&self, price: &Price, buying_quantity: &Quantity) -> SpotBuying {
-        let spent = self.spot.buying_spent_amount(price, buying_quantity);
-        let quantity_after_commission = self.spot.buying_quantity_with_commission(buying_quantity);
+    fn calculator_buying(
+        &self,
+        price: &Price,
+        buying_quantity: &Quantity,
+    ) -> SpotClientResult<SpotBuying> {
+        let spent = self.spot.buying_spent_amount(price, buying_quantity)?;
+        let (spent, quantity_after_commission) = match self.commission_deduction() {
+            CommissionDeduction::Received => (
+                spent,
+                self.spot.buying_quantity_with_commission(buying_quantity)?,
+            ),
+            CommissionDeduction::Quote => (
+                self.spot.buying_amount_with_commission(&spent)?,
+                *buying_quantity,
+            ),
+        };
 
-        SpotBuying {
+        Ok(SpotBuying {
             spent,
-            price: price.clone(),
-            quantity: buying_quantity.clone(),
+            price: *price,
+            quantity: *buying_quantity,
             quantity_after_commission,
             timestamp: timestamp_millis(),
-        }
+        })
     }
 
-    fn calculator_selling(&self, price: &Price, selling_quantity: &Quantity) -> SpotSelling {
-        let selling_income = self.spot.selling_income_amount(price, selling_quantity);
-        let income_after_commission = self.spot.selling_amount_with_commission(&selling_income);
-
-        SpotSelling {
-            price: price.clone(),
-            quantity: selling_quantity.clone(),
+    fn calculator_selling(
+        &self,
+        price: &Price,
+        selling_quantity: &Quantity,
+    ) -> SpotClientResult<SpotSelling> {
+        let selling_income = self.spot.selling_income_amount(price, selling_quantity)?;
+        let income_after_commission = self.spot.selling_amount_with_commission(&selling_income)?;
+
+        Ok(SpotSelling {
+            price: *price,
+            quantity: *selling_quantity,
             income: selling_income,
             income_after_commission,
             timestamp: timestamp_millis(),
+        })
+    }
+
+    /// Buying record from what the exchange actually filled.
+    ///
+    /// Commission paid in the base asset reduces the quantity held, in the quote asset
+    /// it adds to the amount spent, any other asset such as BNB leaves both untouched.
+    pub fn buying_from_fills(&self, transaction: &Transaction) -> SpotClientResult<SpotBuying> {
+        self.buying_from_fill(&Fill::from_transaction(self.spot.symbol(), transaction)?)
+    }
+
+    pub fn buying_from_fill(&self, fill: &Fill) -> SpotClientResult<SpotBuying> {
+        let summary = &fill.summary;
+        let base_commission = self.base_commission(summary);
+        let quote_commission = self.quote_commission(summary);
+
+        Ok(SpotBuying {
+            price: self.average_price(summary)?,
+            quantity: summary.quantity,
+            spent: checked_add(summary.quote_quantity, quote_commission)?,
+            quantity_after_commission: checked_sub(summary.quantity, base_commission)?,
+            timestamp: fill.timestamp,
+        })
+    }
+
+    /// Selling record from what the exchange actually filled.
+    pub fn selling_from_fills(&self, transaction: &Transaction) -> SpotClientResult<SpotSelling> {
+        self.selling_from_fill(&Fill::from_transaction(self.spot.symbol(), transaction)?)
+    }
+
+    pub fn selling_from_fill(&self, fill: &Fill) -> SpotClientResult<SpotSelling> {
+        let summary = &fill.summary;
+        let quote_commission = self.quote_commission(summary);
+
+        Ok(SpotSelling {
+            price: self.average_price(summary)?,
+            quantity: summary.quantity,
+            income: summary.quote_quantity,
+            income_after_commission: checked_sub(summary.quote_quantity, quote_commission)?,
+            timestamp: fill.timestamp,
+        })
+    }
+
+    /// Part of `amount` a partial fill of its market buy at `price` left unspent, zero when
+    /// the quantity it affords filled entirely.
+    pub fn unfilled_amount(&self, price: &Price, amount: &Amount, buying: &SpotBuying) -> Amount {
+        let requested = self.spot.buying_quantity_by_amount(price, amount);
+        match buying.quantity < requested && &buying.spent < amount {
+            true => amount - buying.spent,
+            false => Decimal::ZERO,
         }
     }
 
-    fn is_allow_transaction(&self, price: &Price, quantity: &Quantity) -> SpotClientResult<()> {
-        if !self
-            .spot
-            .is_reached_minimum_transaction_limit(price, quantity)
-        {
-            return Err(SpotClientError::Trading(String::from(
-                "Minimum transaction amount not reached",
-            )));
+    pub fn fill_summary(&self, transaction: &Transaction) -> SpotClientResult<FillSummary> {
+        FillSummary::from_transaction(self.spot.symbol(), transaction)
+    }
+
+    fn average_price(&self, summary: &FillSummary) -> SpotClientResult<Price> {
+        summary.average_price().ok_or_else(|| {
+            SpotClientError::Trading(format!("no quantity filled for {}", self.spot.symbol()))
+        })
+    }
+
+    // Symbols carry no asset separator, the base asset prefixes it and the quote asset ends it
+    fn base_commission(&self, summary: &FillSummary) -> Commission {
+        let symbol = self.spot.symbol();
+        let commissions = summary.commissions.iter();
+        commissions
+            .filter(|(asset, _)| symbol != asset && symbol.starts_with(asset.as_str()))
+            .map(|(_, commission)| *commission)
+            .sum()
+    }
+
+    fn quote_commission(&self, summary: &FillSummary) -> Commission {
+        let symbol = self.spot.symbol();
+        let commissions = summary.commissions.iter();
+        commissions
+            .filter(|(asset, _)| symbol != asset && symbol.ends_with(asset.as_str()))
+            .map(|(_, commission)| *commission)
+            .sum()
+    }
+
+    /// Place through the router, an order rejected with `-1021` is retried once with a receive
+    /// window covering the measured clock drift.
+    ///
+    /// Resent by the retry policy only when rejected before any execution, see
+    /// [`SpotClientError::is_rejected_transient`].
+    async fn place(&self, intent: OrderIntent) -> SpotClientResult<Fill> {
+        let policy = self.retry_policy();
+        policy
+            .retry(SpotClientError::is_rejected_transient, || {
+                self.place_once(intent.clone())
+            })
+            .await
+    }
+
+    async fn place_once(&self, intent: OrderIntent) -> SpotClientResult<Fill> {
+        self.throttle(weight::ORDER).await;
+        match self.router.place(intent.clone()).await {
+            Err(e) if e.exchange_code() == Some(TIMESTAMP_OUTSIDE_RECV_WINDOW) => {
+                warn!(
+                    "{} outside the receive window, check the clock and retry",
+                    e
+                );
+                let recv_window = self.router.check_clock_drift().await?;
+                self.throttle(weight::ORDER).await;
+                self.router
+                    .place(OrderIntent {
+                        recv_window,
+                        ..intent
+                    })
+                    .await
+            }
+            result => result,
+        }
+    }
+
+    fn order_intent(&self, side: TradeSide, quantity: &Quantity, kind: OrderKind) -> OrderIntent {
+        OrderIntent {
+            symbol: self.spot.symbol().clone(),
+            side,
+            quantity: self.spot.normalize_for_order(quantity),
+            kind,
+            recv_window: None,
+        }
+    }
+
+    fn order_error(&self, order: &str, source: binance::errors::Error) -> SpotClientError {
+        SpotClientError::Exchange {
+            context: format!("failed to place {} order of {}", order, self.spot.symbol()),
+            source: Box::new(source),
+        }
+    }
+
+    // An order may be accepted by the api but not by the matching engine
+    pub(super) fn verify_transaction(transaction: &Transaction) -> SpotClientResult<()> {
+        match transaction.status {
+            OrderStatus::Rejected | OrderStatus::Expired => Err(SpotClientError::Trading(format!(
+                "order {} of {} was {:?}",
+                transaction.order_id, transaction.symbol, transaction.status
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    async fn is_allow_transaction(
+        &self,
+        kind: &OrderKind,
+        price: &Price,
+        quantity: &Quantity,
+    ) -> SpotClientResult<()> {
+        let average_price = match (kind, self.market_notional()) {
+            (OrderKind::Market, MarketNotional::AveragePrice) => {
+                Some(self.market_average_price().await?)
+            }
+            _ => None,
+        };
+
+        self.is_allow_notional(kind, price, average_price.as_ref(), quantity)
+    }
+
+    // Truncation can leave nothing to trade, the exchange rejects that with a vague error
+    fn validate_quantity(quantity: &Quantity) -> SpotClientResult<()> {
+        if quantity.is_zero() {
+            return Err(SpotClientError::Validation(String::from("zero quantity")));
         }
 
         Ok(())
     }
+
+    fn is_allow_notional(
+        &self,
+        kind: &OrderKind,
+        price: &Price,
+        average_price: Option<&Price>,
+        quantity: &Quantity,
+    ) -> SpotClientResult<()> {
+        // The market notional settings only apply to market orders, a limit order is
+        // always checked at its own price
+        let notional_price = match (kind, self.market_notional()) {
+            (OrderKind::Limit { price }, _) => Some(price),
+            (OrderKind::Market, MarketNotional::OrderPrice) => Some(price),
+            (OrderKind::Market, MarketNotional::AveragePrice) => {
+                Some(average_price.unwrap_or(price))
+            }
+            (OrderKind::Market, MarketNotional::Exempt) => None,
+        };
+
+        self.spot
+            .validate_filters(kind, notional_price, quantity)
+            .map_err(SpotClientError::Filter)
+    }
 }
 
 impl Exchanger for SpotClient {
@@ -201,9 +912,12 @@ impl Exchanger for SpotClient {
             let client: Arc<SpotClient> = self.clone();
 
             let f = async move {
-                let quantity = client.buy(&price, &amount).await?.quantity_after_commission;
+                let buying = client.buy(&price, &amount).await?;
+                let unfilled = client.unfilled_amount(&price, &amount, &buying);
 
-                Ok(QuantityPoint::new(quantity))
+                Ok(QuantityPoint::try_new(buying.quantity_after_commission)?
+                    .with_unfilled(unfilled)
+                    .with_spent(buying.spent))
             };
 
             Box::pin(f)
@@ -222,7 +936,7 @@ impl Exchanger for SpotClient {
                     .await?
                     .income_after_commission;
 
-                Ok(AmountPoint::new(income))
+                AmountPoint::try_new(income)
             };
 
             Box::pin(f)
@@ -320,13 +1034,687 @@ mod tests_count_leak {
 mod tests_client {
     use tracing_test::traced_test;
 
-    use super::super::tests_general::*;
+    use chrono::{TimeZone, Utc};
+
+    use super::super::{
+        error::RATE_LIMIT_DELAY,
+        router::{DriftFuture, DustFuture, Fill, OrderIntent, OrderRouter, RouterFuture},
+        tests_general::*,
+        SpotFilter,
+    };
     use super::*;
+    use crate::clock::MockClock;
+    use binance::rest_model::{OrderSide, OrderType, TimeInForce};
 
     fn simple_client(spot: Spot) -> SpotClient {
         SpotClient::new(String::from("null"), String::from("null"), spot, None)
     }
 
+    #[test]
+    #[traced_test]
+    fn test_placeholder_keys() {
+        simple_client(btc_spot());
+        assert!(!logs_contain("placeholder api keys"));
+
+        let production = || SpotClientOption {
+            is_production: true,
+            ..SpotClientOption::default()
+        };
+        SpotClient::new(
+            String::from("null"),
+            String::from("null"),
+            btc_spot(),
+            Some(production()),
+        );
+        assert!(logs_contain(
+            "production mode for BTCUSDT with placeholder api keys"
+        ));
+
+        assert_eq!(is_placeholder_key(""), true);
+        assert_eq!(is_placeholder_key(" NULL "), true);
+        assert_eq!(is_placeholder_key("Xq7TzR2bWm"), false);
+    }
+
+    #[test]
+    fn test_debug_redaction() {
+        let api_key = "vmPUZE6mv9SD5VNHk4HlWFsOr6aKE2zvsw0MuIgwCIPy6utIco14y7Ju91duEh8A";
+        let secret_key = "NhqPtmdSJYdKjVHjA7PZj4Mge3R5YNiP1e3UZjInClVN65XAbvqqM6A7H5fATj0j";
+        let client = SpotClient::new(
+            String::from(api_key),
+            String::from(secret_key),
+            btc_spot(),
+            None,
+        );
+
+        let debug = format!("{:?}", client);
+        assert_eq!(debug.contains("BTCUSDT"), true);
+        assert_eq!(debug.contains("is_production: false"), true);
+        for key in [api_key, secret_key] {
+            for part in key.as_bytes().windows(4) {
+                let part = std::str::from_utf8(part).unwrap();
+                assert!(!debug.contains(part), "{} leaks {}", debug, part);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_price_quote_measure() {
+        let delayed = async {
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            Ok::<Price, SpotClientError>(decimal(43145.42))
+        };
+        let quote = PriceQuote::measure(PriceSource::Last, delayed)
+            .await
+            .unwrap();
+
+        assert_eq!(quote.price, decimal(43145.42));
+        assert_eq!(quote.source, PriceSource::Last);
+        assert!(quote.latency_ms >= 20, "{} ms", quote.latency_ms);
+        assert!(quote.fetched_at > 0);
+
+        let failed = async { Err::<Price, _>(SpotClientError::Price(String::from("down"))) };
+        assert!(PriceQuote::measure(PriceSource::Average, failed)
+            .await
+            .is_err());
+    }
+
+    /// Captures every intent and fills it with the canned `fill`.
+    #[derive(Default)]
+    struct MockRouter {
+        intents: std::sync::Mutex<Vec<OrderIntent>>,
+        fill: Fill,
+    }
+
+    impl OrderRouter for MockRouter {
+        fn place(&self, intent: OrderIntent) -> RouterFuture<'_> {
+            self.intents.lock().unwrap().push(intent);
+            let fill = self.fill.clone();
+
+            Box::pin(async move { Ok(fill) })
+        }
+    }
+
+    /// Rejects the first `rejections` orders with `-1021`, the drift check answers `recv_window`.
+    #[derive(Default)]
+    struct DriftRouter {
+        rejections: usize,
+        recv_window: Option<u64>,
+        intents: std::sync::Mutex<Vec<OrderIntent>>,
+        drift_checks: std::sync::atomic::AtomicUsize,
+    }
+
+    impl DriftRouter {
+        fn recv_windows(&self) -> Vec<Option<u64>> {
+            let intents = self.intents.lock().unwrap();
+            intents.iter().map(|e| e.recv_window).collect()
+        }
+    }
+
+    impl OrderRouter for DriftRouter {
+        fn place(&self, intent: OrderIntent) -> RouterFuture<'_> {
+            let mut intents = self.intents.lock().unwrap();
+            let rejected = intents.len() < self.rejections;
+            intents.push(intent.clone());
+
+            Box::pin(async move {
+                if rejected {
+                    let response = r#"{"code": -1021, "msg": "Timestamp for this request is outside of the recvWindow."}"#;
+                    return Err(SpotClientError::Exchange {
+                        context: format!("failed to place order of {}", intent.symbol),
+                        source: Box::new(binance::errors::Error::BinanceError {
+                            response: serde_json::from_str(response).unwrap(),
+                        }),
+                    });
+                }
+
+                Ok(Fill {
+                    summary: FillSummary {
+                        quantity: intent.quantity,
+                        quote_quantity: intent.quantity * decimal(43145.42),
+                        commissions: Vec::new(),
+                    },
+                    timestamp: 1507725176595,
+                })
+            })
+        }
+
+        fn check_clock_drift(&self) -> DriftFuture<'_> {
+            self.drift_checks.fetch_add(1, Ordering::SeqCst);
+            let recv_window = self.recv_window;
+            Box::pin(async move { Ok(recv_window) })
+        }
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_recv_window_retry() {
+        let production = |router: Arc<DriftRouter>| {
+            let option = SpotClientOption {
+                is_production: true,
+                ..SpotClientOption::default()
+            };
+            let client = SpotClient::new(String::new(), String::new(), btc_spot(), Some(option))
+                .with_router(router);
+            client.armed.store(true, Ordering::SeqCst);
+            client
+        };
+
+        // Checked the clock once and the retry filled within the widened window
+        let router = Arc::new(DriftRouter {
+            rejections: 1,
+            recv_window: Some(7500),
+            ..DriftRouter::default()
+        });
+        let buying = production(router.clone())
+            .buy(&decimal(43145.42), &decimal(500.0))
+            .await
+            .unwrap();
+        assert_eq!(buying.quantity, decimal(0.01158));
+        assert_eq!(router.recv_windows(), vec![None, Some(7500)]);
+        assert_eq!(router.drift_checks.load(Ordering::SeqCst), 1);
+        assert!(logs_contain(
+            "outside the receive window, check the clock and retry"
+        ));
+
+        // Retried only once
+        let router = Arc::new(DriftRouter {
+            rejections: 2,
+            ..DriftRouter::default()
+        });
+        let error = production(router.clone())
+            .sell(&decimal(43145.42), &decimal(0.01158))
+            .await
+            .unwrap_err();
+        assert_eq!(error.exchange_code(), Some(TIMESTAMP_OUTSIDE_RECV_WINDOW));
+        assert_eq!(router.recv_windows(), vec![None, None]);
+        assert_eq!(router.drift_checks.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_recv_window_for_drift() {
+        // Behind the server, the window grows by the drift up to the Binance maximum
+        assert_eq!(
+            BinanceRouter::recv_window_for_drift(5000, -2500),
+            Some(7500)
+        );
+        assert_eq!(
+            BinanceRouter::recv_window_for_drift(5000, -90_000),
+            Some(60_000)
+        );
+
+        // Ahead within the tolerance the default window is enough
+        assert_eq!(BinanceRouter::recv_window_for_drift(5000, 800), Some(5000));
+        assert_eq!(BinanceRouter::recv_window_for_drift(5000, 1500), None);
+
+        let mut intent = OrderIntent {
+            symbol: String::from("BTCUSDT"),
+            side: TradeSide::Sell,
+            quantity: decimal(0.01158),
+            kind: OrderKind::Market,
+            recv_window: None,
+        };
+        assert_eq!(
+            BinanceRouter::order_request(&intent).unwrap().recv_window,
+            None
+        );
+        intent.recv_window = Some(7500);
+        let request = BinanceRouter::order_request(&intent).unwrap();
+        assert_eq!(request.recv_window, Some(7500));
+    }
+
+    fn exchange_error(code: i32) -> SpotClientError {
+        let response = format!(r#"{{"code": {}, "msg": "rejected"}}"#, code);
+        SpotClientError::Exchange {
+            context: String::from("failed to get price of BTCUSDT"),
+            source: Box::new(binance::errors::Error::BinanceError {
+                response: serde_json::from_str(&response).unwrap(),
+            }),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_policy() {
+        let policy = RetryPolicy {
+            max_retries: 3,
+            base_delay: Duration::from_millis(1),
+        };
+        let attempts = std::sync::atomic::AtomicUsize::new(0);
+
+        // Disconnected twice, the third attempt succeeds
+        let price = policy
+            .retry(SpotClientError::is_transient, || async {
+                match attempts.fetch_add(1, Ordering::SeqCst) {
+                    0 | 1 => Err(exchange_error(-1001)),
+                    _ => Ok(decimal(43145.42)),
+                }
+            })
+            .await;
+        assert_eq!(price.unwrap(), decimal(43145.42));
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+        // A trading rejection is returned at once
+        attempts.store(0, Ordering::SeqCst);
+        let error = policy
+            .retry(SpotClientError::is_transient, || async {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err::<Price, _>(SpotClientError::Trading(String::from(
+                    "insufficient balance",
+                )))
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "insufficient balance");
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+
+        // Gives up after the retries with the last error
+        attempts.store(0, Ordering::SeqCst);
+        let error = policy
+            .retry(SpotClientError::is_transient, || async {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err::<Price, _>(exchange_error(-1007))
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(error.exchange_code(), Some(-1007));
+        assert_eq!(attempts.load(Ordering::SeqCst), 4);
+
+        // An order timing out may have filled, it is never resent
+        assert_eq!(exchange_error(-1007).is_rejected_transient(), false);
+        assert_eq!(exchange_error(-1001).is_rejected_transient(), false);
+        assert_eq!(exchange_error(-1003).is_rejected_transient(), true);
+        assert_eq!(RetryPolicy::default().max_retries, 0);
+
+        // A rate limit waits at least the floor, a ban until its end
+        assert_eq!(
+            policy.delay(2, &exchange_error(-1001)),
+            Duration::from_millis(4)
+        );
+        assert_eq!(policy.delay(0, &exchange_error(-1003)), RATE_LIMIT_DELAY);
+        let until = Utc::now().timestamp_millis() + 60_000;
+        let response = format!(
+            r#"{{"code": -1003, "msg": "Way too many requests; IP banned until {}."}}"#,
+            until
+        );
+        let banned = SpotClientError::Exchange {
+            context: String::from("failed to place order of BTCUSDT"),
+            source: Box::new(binance::errors::Error::BinanceError {
+                response: serde_json::from_str(&response).unwrap(),
+            }),
+        };
+        let delay = policy.delay(0, &banned);
+        assert!(delay > Duration::from_secs(50) && delay <= Duration::from_secs(60));
+    }
+
+    #[tokio::test]
+    async fn test_router() {
+        let router = Arc::new(MockRouter {
+            fill: Fill {
+                summary: FillSummary {
+                    quantity: decimal(0.01158),
+                    quote_quantity: decimal(499.5),
+                    commissions: vec![(String::from("BTC"), decimal(0.00001158))],
+                },
+                timestamp: 1507725176595,
+            },
+            ..MockRouter::default()
+        });
+        let option = SpotClientOption {
+            is_production: true,
+            ..SpotClientOption::default()
+        };
+        let client = SpotClient::new(String::new(), String::new(), btc_spot(), Some(option))
+            .with_router(router.clone());
+        client.armed.store(true, Ordering::SeqCst);
+
+        let buying = client
+            .buy(&decimal(43145.42), &decimal(500.0))
+            .await
+            .unwrap();
+        assert_eq!(
+            *router.intents.lock().unwrap(),
+            vec![OrderIntent {
+                symbol: String::from("BTCUSDT"),
+                side: TradeSide::Buy,
+                quantity: dec("0.01158"),
+                kind: OrderKind::Market,
+                recv_window: None,
+            }]
+        );
+        assert_eq!(buying.quantity, decimal(0.01158));
+        assert_eq!(buying.spent, decimal(499.5));
+        assert_eq!(buying.quantity_after_commission, decimal(0.01156842));
+        assert_eq!(buying.timestamp, 1507725176595);
+
+        let selling = client
+            .sell(&decimal(43145.42), &decimal(0.01156842))
+            .await
+            .unwrap();
+        assert_eq!(router.intents.lock().unwrap()[1].side, TradeSide::Sell);
+        assert_eq!(router.intents.lock().unwrap()[1].quantity, dec("0.01156"));
+        assert_eq!(selling.income, decimal(499.5));
+    }
+
+    #[tokio::test]
+    async fn test_partial_fill() {
+        use crate::strategy::{limit::LimitPosition, Range};
+        let range = |a: f64, b: f64| Range::try_new(decimal(a), decimal(b)).unwrap();
+
+        // Half of the 0.01158 BTC the 500 affords at 43145.42 fills
+        let router = Arc::new(MockRouter {
+            fill: Fill {
+                summary: FillSummary {
+                    quantity: decimal(0.00579),
+                    quote_quantity: decimal(249.75),
+                    commissions: Vec::new(),
+                },
+                timestamp: 1507725176595,
+            },
+            ..MockRouter::default()
+        });
+        let option = SpotClientOption {
+            is_production: true,
+            ..SpotClientOption::default()
+        };
+        let client = SpotClient::new(String::new(), String::new(), btc_spot(), Some(option))
+            .with_router(router.clone());
+        client.armed.store(true, Ordering::SeqCst);
+        let client = Arc::new(client);
+
+        let quantity_point = client.spawn_buy()(decimal(43145.42), decimal(500.0))
+            .await
+            .unwrap();
+        assert_eq!(quantity_point.value(), &decimal(0.00579));
+        assert_eq!(quantity_point.unfilled(), &decimal(250.25));
+
+        let position = LimitPosition::new(
+            decimal(500.0),
+            range(40000.0, 44000.0),
+            range(46000.0, 48000.0),
+            None,
+        );
+        position
+            .buy(client.spawn_buy(), decimal(43145.42), decimal(500.0))
+            .await
+            .unwrap();
+        assert_eq!(position.is_partially_filled(), true);
+        assert_eq!(position.cost(), Some(decimal(249.75)));
+
+        // Filled entirely, a buy of the full quantity leaves nothing
+        let client = simple_client(btc_spot());
+        let buying = client
+            .buy(&decimal(43145.42), &decimal(500.0))
+            .await
+            .unwrap();
+        assert_eq!(
+            client.unfilled_amount(&decimal(43145.42), &decimal(500.0), &buying),
+            Decimal::ZERO
+        );
+    }
+
+    #[tokio::test]
+    async fn test_limit_order() {
+        let client = simple_client(btc_spot());
+        let price = decimal(43145.42);
+
+        // Outside production computed from the requested price, as a market order
+        let buying = client.buy_limit(&price, &decimal(500.0)).await.unwrap();
+        assert_eq!(buying, client.buy(&price, &decimal(500.0)).await.unwrap());
+        let selling = client
+            .sell_limit(&price, &decimal(0.0115684))
+            .await
+            .unwrap();
+        assert_eq!(
+            selling,
+            client.sell(&price, &decimal(0.0115684)).await.unwrap()
+        );
+
+        // Off the tick, computed from the rounded price
+        let client = simple_client(Spot {
+            tick_size: Some(decimal(0.5)),
+            ..btc_spot()
+        });
+        let buying = client
+            .buy_limit(&decimal(43145.3), &decimal(500.0))
+            .await
+            .unwrap();
+        assert_eq!(
+            buying,
+            client
+                .buy(&decimal(43145.5), &decimal(500.0))
+                .await
+                .unwrap()
+        );
+
+        // A market order crosses the spread, the price it is sized at needs no tick
+        assert!(client.buy(&decimal(43145.3), &decimal(500.0)).await.is_ok());
+
+        let intent = client.order_intent(
+            TradeSide::Buy,
+            &decimal(0.01158),
+            OrderKind::Limit { price },
+        );
+        let request = BinanceRouter::order_request(&intent).unwrap();
+        assert_eq!(request.symbol, "BTCUSDT");
+        assert_eq!(matches!(request.side, OrderSide::Buy), true);
+        assert_eq!(matches!(request.order_type, OrderType::Limit), true);
+        assert_eq!(request.time_in_force, Some(TimeInForce::GTC));
+        assert_eq!(request.price, Some(43145.42));
+        assert_eq!(request.quantity, Some(0.01158));
+
+        let intent = client.order_intent(TradeSide::Sell, &decimal(0.01156), OrderKind::Market);
+        let request = BinanceRouter::order_request(&intent).unwrap();
+        assert_eq!(matches!(request.side, OrderSide::Sell), true);
+        assert_eq!(matches!(request.order_type, OrderType::Market), true);
+        assert_eq!(request.time_in_force, None);
+        assert_eq!(request.price, None);
+    }
+
+    /// Captures the assets of every dust conversion and answers with a canned transfer.
+    #[derive(Default)]
+    struct DustRouter {
+        assets: std::sync::Mutex<Vec<Vec<Symbol>>>,
+    }
+
+    impl OrderRouter for DustRouter {
+        fn place(&self, _intent: OrderIntent) -> RouterFuture<'_> {
+            Box::pin(async { Err(SpotClientError::Trading(String::from("no orders"))) })
+        }
+
+        fn convert_dust(&self, assets: Vec<Symbol>) -> DustFuture<'_> {
+            self.assets.lock().unwrap().push(assets);
+            let response = r#"{
+                "totalServiceCharge": "0.02102542",
+                "totalTransferred": "1.05127099",
+                "transferResult": [
+                    {
+                        "amount": "0.03000000",
+                        "fromAsset": "ETH",
+                        "operateTime": 1563368549307,
+                        "serviceChargeAmount": "0.00500000",
+                        "tranId": 2970932918,
+                        "transferedAmount": "0.25000000"
+                    },
+                    {
+                        "amount": "0.09000000",
+                        "fromAsset": "LTC",
+                        "operateTime": 1563368549404,
+                        "serviceChargeAmount": "0.01548000",
+                        "tranId": 2970932918,
+                        "transferedAmount": "0.77400000"
+                    }
+                ]
+            }"#;
+            let transfer = serde_json::from_str(response).unwrap();
+
+            Box::pin(async move { DustConversion::from_transfer(&transfer) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_convert_dust() {
+        let assets = vec![String::from("ETH"), String::from("LTC")];
+        let router = Arc::new(DustRouter::default());
+
+        // Nothing leaves a client outside production
+        let client = simple_client(btc_spot()).with_router(router.clone());
+        let conversion = client.convert_dust(&assets).await.unwrap();
+        assert_eq!(conversion, DustConversion::default());
+        assert_eq!(router.assets.lock().unwrap().is_empty(), true);
+
+        let option = SpotClientOption {
+            is_production: true,
+            ..SpotClientOption::default()
+        };
+        let client = SpotClient::new(String::new(), String::new(), btc_spot(), Some(option))
+            .with_router(router.clone());
+        let error = client.convert_dust(&assets).await.unwrap_err();
+        assert_eq!(error.to_string(), "production mode is not armed");
+
+        client.armed.store(true, Ordering::SeqCst);
+        let conversion = client.convert_dust(&assets).await.unwrap();
+        assert_eq!(*router.assets.lock().unwrap(), vec![assets]);
+        assert_eq!(conversion.transferred, decimal(1.05127099));
+        assert_eq!(conversion.service_charge, decimal(0.02102542));
+        assert_eq!(
+            conversion.converted,
+            vec![
+                (String::from("ETH"), decimal(0.25)),
+                (String::from("LTC"), decimal(0.774))
+            ]
+        );
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_used_weight_throttle() {
+        // 300 milliseconds before the weight resets
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 59).unwrap()
+            + chrono::Duration::try_milliseconds(700).unwrap();
+        let used_weight = Arc::new(UsedWeight::new(1200).with_clock(Arc::new(MockClock::new(now))));
+        let option = SpotClientOption {
+            is_production: true,
+            ..SpotClientOption::default()
+        };
+        let client = SpotClient::new(String::new(), String::new(), btc_spot(), Some(option))
+            .with_router(Arc::new(MockRouter::default()))
+            .with_used_weight(used_weight.clone());
+        client.armed.store(true, Ordering::SeqCst);
+
+        let start = std::time::Instant::now();
+        client
+            .buy(&decimal(43145.42), &decimal(500.0))
+            .await
+            .unwrap();
+        assert!(start.elapsed() < std::time::Duration::from_millis(300));
+        assert_eq!(used_weight.used(), weight::ORDER);
+
+        // At the limit, the order waits for the next minute
+        used_weight.observe_headers([("x-mbx-used-weight-1m", "1200")]);
+        let start = std::time::Instant::now();
+        client
+            .buy(&decimal(43145.42), &decimal(500.0))
+            .await
+            .unwrap();
+        assert!(start.elapsed() >= std::time::Duration::from_millis(300));
+        assert!(logs_contain("used weight 1200 of 1200"));
+    }
+
+    #[test]
+    fn test_option_serde() {
+        let option = SpotClientOption {
+            is_production: true,
+            commission_deduction: CommissionDeduction::Quote,
+            strict_price_precision: Some(2),
+            market_notional: MarketNotional::AveragePrice,
+            retry_policy: RetryPolicy {
+                max_retries: 3,
+                base_delay: Duration::from_millis(500),
+            },
+        };
+
+        let json = serde_json::to_string(&option).unwrap();
+        assert_eq!(
+            json,
+            r#"{"is_production":true,"commission_deduction":"Quote","strict_price_precision":2,"market_notional":"AveragePrice","retry_policy":{"max_retries":3,"base_delay":{"secs":0,"nanos":500000000}}}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<SpotClientOption>(&json).unwrap(),
+            option
+        );
+
+        // Missing fields fall back to their defaults
+        let option: SpotClientOption = serde_json::from_str(r#"{"is_production":true}"#).unwrap();
+        assert_eq!(option.is_production, true);
+        assert_eq!(option.market_notional, MarketNotional::OrderPrice);
+    }
+
+    #[tokio::test]
+    async fn test_not_armed() {
+        let option = SpotClientOption {
+            is_production: true,
+            ..SpotClientOption::default()
+        };
+        let client = SpotClient::new(
+            String::from("null"),
+            String::from("null"),
+            btc_spot(),
+            Some(option),
+        );
+
+        let error = client
+            .buy(&decimal(43145.42), &decimal(500.0))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, SpotClientError::NotArmed));
+        let error = client
+            .sell(&decimal(43145.42), &decimal(0.0115684))
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "production mode is not armed");
+
+        // Armed once the router verified the credentials
+        let client = client.with_router(Arc::new(MockRouter::default()));
+        client.arm_production().await.unwrap();
+        assert!(client.check_armed().is_ok());
+        assert!(client
+            .buy(&decimal(43145.42), &decimal(500.0))
+            .await
+            .is_ok());
+
+        // Simulated trading never needs arming
+        let client = simple_client(btc_spot());
+        assert_eq!(client.is_armed(), false);
+        assert!(client
+            .buy(&decimal(43145.42), &decimal(500.0))
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_zero_quantity() {
+        let client = simple_client(btc_spot());
+
+        // 0.1 / 43145.42 truncates to zero at a transaction precision of 5
+        let error = client
+            .buy(&decimal(43145.42), &decimal(0.1))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, SpotClientError::Validation(_)));
+        assert_eq!(error.to_string(), "zero quantity");
+
+        let error = client
+            .sell(&decimal(43145.42), &decimal(0.000009))
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "zero quantity");
+
+        let error = client
+            .test_buy(&decimal(43145.42), &Decimal::ZERO)
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "zero quantity");
+    }
+
     #[tokio::test]
     async fn test_buying() {
         let client = simple_client(btc_spot());
@@ -386,60 +1774,229 @@ mod tests_client {
         assert_eq!(buying, assert);
     }
 
-    // #[tokio::test]
-    // async fn test_buying_with_quantity() {
-    //     let client = simple_client(btc_spot());
-    //     let buying = client
-    //         .buy(&decimal(43145.42), &decimal(0.0015))
-    //         .await
-    //         .unwrap();
-    //     let assert = SpotBuying {
-    //         price: decimal(43145.42),
-    //         spent: decimal(64.71813),
-    //         quantity: decimal(0.0015),
-    //         quantity_after_commission: decimal(0.0014985),
-    //     };
-    //     assert_eq!(buying, assert);
-
-    //     let client = simple_client(btc_spot());
-    //     let buying = client
-    //         .buy(&decimal(43145.42), &decimal(0.00159858))
-    //         .await
-    //         .unwrap();
-    //     let assert = SpotBuying {
-    //         price: decimal(43145.42),
-    //         spent: decimal(68.6012178),
-    //         quantity: decimal(0.00159),
-    //         quantity_after_commission: decimal(0.0015884),
-    //     };
-    //     assert_eq!(buying, assert);
-
-    //     let client = simple_client(eth_spot());
-    //     let buying = client
-    //         .buy(&decimal(2596.04), &decimal(0.079))
-    //         .await
-    //         .unwrap();
-    //     let assert = SpotBuying {
-    //         price: decimal(2596.04),
-    //         spent: decimal(205.087160),
-    //         quantity: decimal(0.0790),
-    //         quantity_after_commission: decimal(0.0789210),
-    //     };
-    //     assert_eq!(buying, assert);
-
-    //     let client = simple_client(eth_spot());
-    //     let buying = client
-    //         .buy(&decimal(2596.04), &decimal(0.0791531))
-    //         .await
-    //         .unwrap();
-    //     let assert = SpotBuying {
-    //         price: decimal(2596.04),
-    //         spent: decimal(205.346764),
-    //         quantity: decimal(0.0791),
-    //         quantity_after_commission: decimal(0.0790209),
-    //     };
-    //     assert_eq!(buying, assert);
-    // }
+    #[tokio::test]
+    async fn test_commission_deduction() {
+        let option = SpotClientOption {
+            commission_deduction: CommissionDeduction::Quote,
+            ..SpotClientOption::default()
+        };
+        let received = simple_client(btc_spot());
+        let quote = SpotClient::new(
+            String::from("null"),
+            String::from("null"),
+            btc_spot(),
+            Some(option),
+        );
+
+        let buying = received
+            .buy(&decimal(43145.42), &decimal(500.0))
+            .await
+            .unwrap();
+        let assert = SpotBuying {
+            price: decimal(43145.42),
+            spent: decimal(499.6239636),
+            quantity: decimal(0.01158),
+            quantity_after_commission: decimal(0.0115684),
+            timestamp: 0,
+        };
+        assert_eq!(buying, assert);
+
+        let buying = quote
+            .buy(&decimal(43145.42), &decimal(500.0))
+            .await
+            .unwrap();
+        // One step less, the commission on top of 0.01158 would spend 500.12358756
+        let assert = SpotBuying {
+            price: decimal(43145.42),
+            spent: decimal(499.69170191),
+            quantity: decimal(0.01157),
+            quantity_after_commission: decimal(0.01157),
+            timestamp: 0,
+        };
+        assert_eq!(buying, assert);
+
+        let selling = received
+            .sell(&decimal(42991.10), &decimal(0.00349))
+            .await
+            .unwrap();
+        assert_eq!(
+            selling,
+            quote
+                .sell(&decimal(42991.10), &decimal(0.00349))
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_commission_round_trip() {
+        let cases = [
+            (btc_spot(), decimal(43145.42), decimal(500.0)),
+            (eth_spot(), decimal(2596.04), decimal(600.50)),
+        ];
+
+        for (spot, price, investment) in cases {
+            let step = spot.transaction_step();
+            let expected = investment
+                * (Decimal::ONE - spot.buying_commission)
+                * (Decimal::ONE - spot.selling_commission);
+            let client = Arc::new(simple_client(spot));
+
+            let quantity = client.spawn_buy()(price, investment).await.unwrap();
+            let income = client.spawn_sell()(price, *quantity.value()).await.unwrap();
+
+            // Each order truncates at most one step of quantity
+            let net = *income.value();
+            assert!(net <= expected, "{} above {}", net, expected);
+            assert!(
+                expected - net <= price * step * Decimal::TWO,
+                "{} too far below {}",
+                net,
+                expected
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_buying_with_quantity() {
+        let client = simple_client(btc_spot());
+        let buying = client
+            .buy_with_quantity(&decimal(43145.42), &decimal(0.0015))
+            .await
+            .unwrap();
+        let assert = SpotBuying {
+            price: decimal(43145.42),
+            spent: decimal(64.71813),
+            quantity: decimal(0.0015),
+            quantity_after_commission: decimal(0.0014985),
+            timestamp: 0,
+        };
+        assert_eq!(buying, assert);
+
+        let client = simple_client(btc_spot());
+        let buying = client
+            .buy_with_quantity(&decimal(43145.42), &decimal(0.00159858))
+            .await
+            .unwrap();
+        let assert = SpotBuying {
+            price: decimal(43145.42),
+            spent: decimal(68.6012178),
+            quantity: decimal(0.00159),
+            quantity_after_commission: decimal(0.0015884),
+            timestamp: 0,
+        };
+        assert_eq!(buying, assert);
+
+        let client = simple_client(eth_spot());
+        let buying = client
+            .buy_with_quantity(&decimal(2596.04), &decimal(0.079))
+            .await
+            .unwrap();
+        let assert = SpotBuying {
+            price: decimal(2596.04),
+            spent: decimal(205.087160),
+            quantity: decimal(0.0790),
+            quantity_after_commission: decimal(0.0789210),
+            timestamp: 0,
+        };
+        assert_eq!(buying, assert);
+
+        let client = simple_client(eth_spot());
+        let buying = client
+            .buy_with_quantity(&decimal(2596.04), &decimal(0.0791531))
+            .await
+            .unwrap();
+        let assert = SpotBuying {
+            price: decimal(2596.04),
+            spent: decimal(205.346764),
+            quantity: decimal(0.0791),
+            quantity_after_commission: decimal(0.0790209),
+            timestamp: 0,
+        };
+        assert_eq!(buying, assert);
+    }
+
+    #[test]
+    fn test_is_allow_notional() {
+        let client = |market_notional| {
+            let option = SpotClientOption {
+                market_notional,
+                ..SpotClientOption::default()
+            };
+            SpotClient::new(String::new(), String::new(), btc_spot(), Some(option))
+        };
+
+        // 10 * 0.6 clears the minimum notional of 5, the average price 8 * 0.6 does not
+        let (last, average, quantity) = (decimal(10.0), decimal(8.0), decimal(0.6));
+
+        let order_price = client(MarketNotional::OrderPrice);
+        assert!(order_price
+            .is_allow_notional(&OrderKind::Market, &last, Some(&average), &quantity)
+            .is_ok());
+
+        let average_price = client(MarketNotional::AveragePrice);
+        assert!(average_price
+            .is_allow_notional(&OrderKind::Market, &last, Some(&average), &quantity)
+            .is_err());
+        assert!(average_price
+            .is_allow_notional(&OrderKind::Market, &average, Some(&last), &quantity)
+            .is_ok());
+
+        let exempt = client(MarketNotional::Exempt);
+        assert!(exempt
+            .is_allow_notional(&OrderKind::Market, &average, Some(&average), &quantity)
+            .is_ok());
+
+        let error = average_price
+            .is_allow_notional(&OrderKind::Market, &last, Some(&average), &quantity)
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            SpotClientError::Filter(SpotFilter::MinimumNotional)
+        ));
+        assert_eq!(
+            error.to_string(),
+            "order violates the minimum notional filter"
+        );
+
+        // A limit order is checked at its own price whatever the market notional
+        let limit = |price| OrderKind::Limit { price };
+        assert!(average_price
+            .is_allow_notional(&limit(last), &last, Some(&average), &quantity)
+            .is_ok());
+        assert!(exempt
+            .is_allow_notional(&limit(average), &average, None, &quantity)
+            .is_err());
+    }
+
+    #[test]
+    #[traced_test]
+    fn test_price_from_f64() {
+        let client = simple_client(btc_spot());
+        let price = client.price_from_f64(43145.4200000001).unwrap();
+        assert_eq!(price, decimal(43145.4200000001));
+        assert!(!logs_contain("suspect"));
+
+        let option = SpotClientOption {
+            strict_price_precision: Some(2),
+            ..SpotClientOption::default()
+        };
+        let client = SpotClient::new(
+            String::from("null"),
+            String::from("null"),
+            btc_spot(),
+            Some(option),
+        );
+
+        client.price_from_f64(43145.42).unwrap();
+        assert!(!logs_contain("suspect"));
+
+        client.price_from_f64(43145.4200000001).unwrap();
+        assert!(logs_contain("suspect BTCUSDT price 43145.4200000001"));
+
+        let error = client.price_from_f64(f64::NAN).unwrap_err();
+        assert_eq!(error.to_string(), "BTCUSDT price NaN to decimal error");
+    }
 
     #[tokio::test]
     #[traced_test]
@@ -872,6 +2429,99 @@ mod tests_client {
 //     }
 // }
 
+#[cfg(test)]
+mod tests_client_transaction {
+    use super::super::tests_general::*;
+    use super::*;
+
+    fn transaction(status: &str) -> Transaction {
+        let response = format!(
+            r#"{{
+                "symbol": "BTCUSDT",
+                "orderId": 28,
+                "clientOrderId": "6gCrw2kRUAF9CvJDGP16IP",
+                "transactTime": 1507725176595,
+                "price": "0.00000000",
+                "origQty": "0.01158000",
+                "executedQty": "0.00000000",
+                "cummulativeQuoteQty": "0.00000000",
+                "status": "{status}",
+                "timeInForce": "GTC",
+                "type": "MARKET",
+                "side": "BUY",
+                "fills": []
+            }}"#
+        );
+
+        serde_json::from_str(&response).unwrap()
+    }
+
+    #[test]
+    fn test_from_fills() {
+        let response = r#"{
+            "symbol": "BTCUSDT",
+            "orderId": 29,
+            "clientOrderId": "6gCrw2kRUAF9CvJDGP16IQ",
+            "transactTime": 1507725176595,
+            "price": "0.00000000",
+            "origQty": "0.04000000",
+            "executedQty": "0.04000000",
+            "cummulativeQuoteQty": "1726.00000000",
+            "status": "FILLED",
+            "timeInForce": "GTC",
+            "type": "MARKET",
+            "side": "BUY",
+            "fills": [
+                {"price": "43100.00", "qty": "0.01000000", "commission": "0.00001000", "commissionAsset": "BTC"},
+                {"price": "43150.00", "qty": "0.02000000", "commission": "0.00002000", "commissionAsset": "BTC"},
+                {"price": "43200.00", "qty": "0.01000000", "commission": "0.00012000", "commissionAsset": "BNB"}
+            ]
+        }"#;
+        let transaction: Transaction = serde_json::from_str(response).unwrap();
+        let client = SpotClient::new(String::new(), String::new(), btc_spot(), None);
+
+        let summary = client.fill_summary(&transaction).unwrap();
+        assert_eq!(summary.quantity, decimal(0.04));
+        assert_eq!(summary.quote_quantity, decimal(1726.0));
+        assert_eq!(summary.average_price(), Some(decimal(43150.0)));
+        assert_eq!(summary.commission("BTC"), decimal(0.00003));
+        assert_eq!(summary.commission("BNB"), decimal(0.00012));
+        assert_eq!(summary.commission("USDT"), Decimal::ZERO);
+
+        let buying = client.buying_from_fills(&transaction).unwrap();
+        assert_eq!(buying.price, decimal(43150.0));
+        assert_eq!(buying.spent, decimal(1726.0));
+        assert_eq!(buying.quantity_after_commission, decimal(0.03997));
+        assert_eq!(buying.timestamp, 1507725176595);
+
+        let response = response
+            .replace(
+                r#""commissionAsset": "BTC""#,
+                r#""commissionAsset": "USDT""#,
+            )
+            .replace(r#""0.00001000""#, r#""0.43100000""#)
+            .replace(r#""0.00002000""#, r#""0.86300000""#);
+        let transaction: Transaction = serde_json::from_str(&response).unwrap();
+        let selling = client.selling_from_fills(&transaction).unwrap();
+        assert_eq!(selling.price, decimal(43150.0));
+        assert_eq!(selling.income, decimal(1726.0));
+        assert_eq!(selling.income_after_commission, decimal(1724.706));
+    }
+
+    #[test]
+    fn test_verify_transaction() {
+        assert!(SpotClient::verify_transaction(&transaction("FILLED")).is_ok());
+        assert!(SpotClient::verify_transaction(&transaction("PARTIALLY_FILLED")).is_ok());
+
+        let error = SpotClient::verify_transaction(&transaction("REJECTED")).unwrap_err();
+        assert!(matches!(error, SpotClientError::Trading(_)));
+        assert_eq!(error.to_string(), "order 28 of BTCUSDT was Rejected");
+
+        let error = SpotClient::verify_transaction(&transaction("EXPIRED")).unwrap_err();
+        assert_eq!(error.to_string(), "order 28 of BTCUSDT was Expired");
+    }
+}
+
 #[cfg(test)]
 mod tests_client_spawn {
     use tracing_test::traced_test;
@@ -891,7 +2541,8 @@ mod tests_client_spawn {
         let quantity_point = client.spawn_buy()(decimal(72000.0), decimal(1000.0))
             .await
             .unwrap();
-        assert_eq!(quantity_point.value(), &decimal(0.0138661))
+        assert_eq!(quantity_point.value(), &decimal(0.0138661));
+        assert_eq!(quantity_point.spent(), Some(&decimal(999.36)));
     }
 
     #[tokio::test]
@@ -910,7 +2561,7 @@ mod tests_client_spawn {
 mod tests_client_limit {
     use tracing_test::traced_test;
 
-    use crate::strategy::Strategy;
+    use crate::strategy::{tests_general::simple_prices, Strategy};
 
     use super::super::tests_general::*;
     use super::*;
@@ -948,4 +2599,47 @@ mod tests_client_limit {
         assert_eq!(limit.positions()[0].buying_count(), 1);
         assert_eq!(limit.positions()[0].selling_count(), 1);
     }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_strategy_trap_grid() {
+        use crate::strategy::{grid::Grid, Range};
+        use crate::treasurer::Prosperity;
+
+        let client = Arc::new(simple_client(btc_spot()));
+        let treasurer = Arc::new(Prosperity::new(None));
+        let buy = |price: Price, amount: Amount| -> PinFutureResult<QuantityPoint> {
+            let (client, treasurer) = (client.clone(), treasurer.clone());
+            Box::pin(async move {
+                let buying = client.buy(&price, &amount).await?;
+                treasurer.transfer_out(buying.spent, Decimal::ZERO).await;
+
+                Ok(QuantityPoint::new(buying.quantity_after_commission))
+            })
+        };
+        let sell = |price: Price, quantity: Quantity| -> PinFutureResult<AmountPoint> {
+            let (client, treasurer) = (client.clone(), treasurer.clone());
+            Box::pin(async move {
+                let selling = client.sell(&price, &quantity).await?;
+                treasurer
+                    .transfer_in(selling.income_after_commission, Decimal::ZERO)
+                    .await;
+
+                Ok(AmountPoint::new(selling.income_after_commission))
+            })
+        };
+
+        let prices = vec![54.90, 64.90, 65.10, 74.90, 75.10, 85.10];
+        let price = simple_prices(prices.clone());
+        let strategy = Grid::new(dec("100"), Range(dec("50"), dec("90")), 4, None);
+
+        for _ in prices.iter() {
+            strategy.trap(&price, &buy, &sell).await.unwrap();
+        }
+
+        // Each level buys within its lower half and sells one level up: 54.90 -> 65.10,
+        // 64.90 -> 75.10 and 74.90 -> 85.10, after both commissions
+        assert_eq!(strategy.is_all_short(), true);
+        assert_eq!(treasurer.balance().await, dec("15.73824878"));
+    }
 }
@@ -1,20 +1,269 @@
+#[cfg(feature = "client")]
+use std::time::Duration;
 use std::{error::Error, fmt::Display};
 
+use super::SpotFilter;
+#[cfg(feature = "client")]
+use crate::market::error::MarketClientError;
+use crate::noun::*;
+
 #[derive(Debug)]
 pub enum SpotClientError {
     Price(String),
     Trading(String),
-    Decimal(String),
+
+    /// An order rejected before reaching the exchange
+    Validation(String),
+
+    /// Production mode without a successful `arm_production`
+    NotArmed,
+
+    /// The exchange info of a symbol is malformed or lacks a filter
+    ExchangeInfo(String),
+
+    /// A variable read by `from_env` is missing or blank
+    Environment(String),
+
+    /// An order violating an exchange filter, caught before reaching the exchange
+    Filter(SpotFilter),
+    Arith(ArithError),
+    Decimal {
+        symbol: Symbol,
+        field: DecimalField,
+        value: String,
+    },
+
+    /// A request to the exchange failed, `context` tells which one
+    #[cfg(feature = "client")]
+    Exchange {
+        context: String,
+        source: Box<binance::errors::Error>,
+    },
+}
+
+/// Binance rejected a signed request whose timestamp is outside the receive window.
+#[cfg(feature = "client")]
+pub const TIMESTAMP_OUTSIDE_RECV_WINDOW: i32 = -1021;
+
+/// Binance could not process the request, nothing was executed.
+#[cfg(feature = "client")]
+pub const DISCONNECTED: i32 = -1001;
+
+/// The request weight limit was exceeded, the request was rejected.
+#[cfg(feature = "client")]
+pub const TOO_MANY_REQUESTS: i32 = -1003;
+
+/// Least wait before retrying a request rejected with [`TOO_MANY_REQUESTS`].
+#[cfg(feature = "client")]
+pub const RATE_LIMIT_DELAY: Duration = Duration::from_secs(1);
+
+/// Binance timed out waiting for the backend, the execution status is unknown.
+#[cfg(feature = "client")]
+pub const TIMEOUT: i32 = -1007;
+
+#[cfg(feature = "client")]
+impl SpotClientError {
+    /// Error code of a request the exchange rejected, e.g. `-1021`.
+    pub fn exchange_code(&self) -> Option<i32> {
+        match self {
+            Self::Exchange { source, .. } => match source.as_ref() {
+                binance::errors::Error::BinanceError { response } => Some(response.code),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// A failure that may pass on a later attempt, a rate limit, a timeout or the network.
+    pub fn is_transient(&self) -> bool {
+        use binance::errors::Error;
+
+        match self {
+            Self::Exchange { source, .. } => match source.as_ref() {
+                Error::BinanceError { response } => {
+                    [DISCONNECTED, TOO_MANY_REQUESTS, TIMEOUT].contains(&response.code)
+                }
+                Error::ReqError(_)
+                | Error::IoError(_)
+                | Error::InternalServerError
+                | Error::ServiceUnavailable => true,
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Transient and rejected before any execution, the only failures an order is resent on.
+    ///
+    /// A timeout, a `-1001` or a network error may hide an order that filled, resending it
+    /// could trade twice. Only the rate limit rejects an order before it is processed.
+    pub fn is_rejected_transient(&self) -> bool {
+        self.exchange_code() == Some(TOO_MANY_REQUESTS)
+    }
+
+    /// Least wait before retrying a rate limited request, `None` for any other error.
+    ///
+    /// The response headers and their `Retry-After` do not reach the client, a ban names
+    /// its end in the message instead, e.g. `IP banned until 1659146400000`. Any other rate
+    /// limit waits [`RATE_LIMIT_DELAY`].
+    pub fn retry_after(&self) -> Option<Duration> {
+        use binance::errors::Error;
+
+        let Self::Exchange { source, .. } = self else {
+            return None;
+        };
+        let Error::BinanceError { response } = source.as_ref() else {
+            return None;
+        };
+        if response.code != TOO_MANY_REQUESTS {
+            return None;
+        }
+
+        let banned_until = response
+            .msg
+            .split_once("until ")
+            .and_then(|(_, rest)| rest.trim_end_matches('.').parse::<i64>().ok());
+        let remaining = banned_until
+            .map(|until| until - chrono::Utc::now().timestamp_millis())
+            .and_then(|millis| u64::try_from(millis).ok())
+            .map(Duration::from_millis);
+
+        Some(remaining.map_or(RATE_LIMIT_DELAY, |e| e.max(RATE_LIMIT_DELAY)))
+    }
 }
 
-impl Error for SpotClientError {}
+/// Which value of an order failed the conversion between `f64` and `Decimal`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DecimalField {
+    Price,
+    Quantity,
+    Commission,
+    Amount,
+}
+
+impl Error for SpotClientError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            #[cfg(feature = "client")]
+            Self::Exchange { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
 
 impl Display for SpotClientError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Price(e) => write!(f, "{}", e),
             Self::Trading(e) => write!(f, "{}", e),
-            Self::Decimal(e) => write!(f, "{} to decimal error", e),
+            Self::Validation(e) => write!(f, "{}", e),
+            Self::NotArmed => write!(f, "production mode is not armed"),
+            Self::ExchangeInfo(e) => write!(f, "{}", e),
+            Self::Environment(e) => write!(f, "{}", e),
+            Self::Filter(e) => write!(f, "order violates the {} filter", e),
+            Self::Arith(e) => write!(f, "{}", e),
+            Self::Decimal {
+                symbol,
+                field,
+                value,
+            } => write!(f, "{} {} {} to decimal error", symbol, field, value),
+            // The cause is left to `source`, so reporters walking the chain print it once
+            #[cfg(feature = "client")]
+            Self::Exchange { context, .. } => write!(f, "{}", context),
+        }
+    }
+}
+
+impl Display for DecimalField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Price => write!(f, "price"),
+            Self::Quantity => write!(f, "quantity"),
+            Self::Commission => write!(f, "commission"),
+            Self::Amount => write!(f, "amount"),
         }
     }
 }
+
+impl From<ArithError> for SpotClientError {
+    fn from(value: ArithError) -> Self {
+        Self::Arith(value)
+    }
+}
+
+#[cfg(feature = "client")]
+impl From<MarketClientError> for SpotClientError {
+    fn from(value: MarketClientError) -> Self {
+        match value {
+            MarketClientError::Client(e) => Self::Price(e),
+            MarketClientError::Decimal { symbol, value } => Self::Decimal {
+                symbol,
+                field: DecimalField::Price,
+                value,
+            },
+            MarketClientError::Kline(e) => Self::Price(e),
+            MarketClientError::Environment(e) => Self::Environment(e),
+            MarketClientError::Exchange { context, source } => Self::Exchange { context, source },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decimal_display() {
+        let error = SpotClientError::Decimal {
+            symbol: String::from("BTCUSDT"),
+            field: DecimalField::Quantity,
+            value: String::from("NaN"),
+        };
+        assert_eq!(error.to_string(), "BTCUSDT quantity NaN to decimal error");
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_from_market_client_error() {
+        let error = SpotClientError::from(MarketClientError::Client(String::from("timeout")));
+        assert!(matches!(error, SpotClientError::Price(e) if e == "timeout"));
+
+        let error = SpotClientError::from(MarketClientError::Decimal {
+            symbol: String::from("ETHUSDT"),
+            value: String::from("NaN"),
+        });
+        assert_eq!(error.to_string(), "ETHUSDT price NaN to decimal error");
+
+        let error = SpotClientError::from(MarketClientError::Kline(String::from("no kline")));
+        assert!(matches!(error, SpotClientError::Price(e) if e == "no kline"));
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_source_chain() {
+        let response =
+            r#"{"code": -1021, "msg": "Timestamp for this request is outside of the recvWindow."}"#;
+        let response: binance::errors::BinanceContentError =
+            serde_json::from_str(response).unwrap();
+        let error = SpotClientError::from(MarketClientError::Exchange {
+            context: String::from("failed to get price of BTCUSDT"),
+            source: Box::new(binance::errors::Error::BinanceError { response }),
+        });
+        assert_eq!(error.to_string(), "failed to get price of BTCUSDT");
+
+        let mut chain = Vec::new();
+        let mut current: Option<&(dyn Error + 'static)> = Some(&error);
+        while let Some(e) = current {
+            chain.push(e);
+            current = e.source();
+        }
+
+        assert_eq!(chain.len(), 3);
+        assert!(chain[1].is::<binance::errors::Error>());
+
+        let root = chain[2].downcast_ref::<binance::errors::BinanceContentError>();
+        assert_eq!(root.map(|e| e.code), Some(-1021));
+        assert_eq!(error.exchange_code(), Some(TIMESTAMP_OUTSIDE_RECV_WINDOW));
+        assert_eq!(SpotClientError::NotArmed.exchange_code(), None);
+    }
+}
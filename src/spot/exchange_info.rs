@@ -0,0 +1,282 @@
+use binance::market::Market;
+use serde::Deserialize;
+
+use super::{client::MarketNotional, error::SpotClientError, QuantityRounding, Spot};
+use crate::noun::*;
+
+const EXCHANGE_INFO: &str = "/api/v3/exchangeInfo";
+
+/// Standard spot commission rate, before any BNB or VIP discount.
+pub const DEFAULT_COMMISSION: Commission = Decimal::from_parts(1, 0, 0, false, 3);
+
+// Only the fields a `Spot` is built from. Parsed here rather than through the binance
+// crate, which drops the `NOTIONAL` filter that replaced `MIN_NOTIONAL` on most symbols.
+#[derive(Deserialize)]
+struct ExchangeInfo {
+    symbols: Vec<SymbolInfo>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SymbolInfo {
+    symbol: Symbol,
+    base_asset_precision: Precision,
+    quote_asset_precision: Precision,
+    filters: Vec<SymbolFilter>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "filterType", rename_all = "SCREAMING_SNAKE_CASE")]
+enum SymbolFilter {
+    #[serde(rename_all = "camelCase")]
+    PriceFilter { tick_size: Price },
+
+    #[serde(rename_all = "camelCase")]
+    LotSize {
+        min_qty: Quantity,
+        step_size: Quantity,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    MinNotional {
+        min_notional: Amount,
+        apply_to_market: Option<bool>,
+        avg_price_mins: Option<u32>,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    Notional {
+        min_notional: Amount,
+        apply_min_to_market: Option<bool>,
+        avg_price_mins: Option<u32>,
+    },
+
+    #[serde(other)]
+    Other,
+}
+
+impl Spot {
+    /// Query the filters of `symbol` from Binance, see [`Spot::from_exchange_info_json`].
+    pub async fn from_exchange_info(
+        symbol: &Symbol,
+        market: &Market,
+    ) -> Result<Self, SpotClientError> {
+        let json = market
+            .client
+            .get(EXCHANGE_INFO, &format!("symbol={}", symbol))
+            .await
+            .map_err(|e| SpotClientError::Exchange {
+                context: format!("failed to get exchange info of {}", symbol),
+                source: Box::new(e),
+            })?;
+
+        Self::from_exchange_info_json(symbol, &json)
+    }
+
+    /// Build `symbol` from an exchangeInfo response.
+    ///
+    /// The transaction precision and step come from `LOT_SIZE`, the minimum transaction
+    /// amount from `MIN_NOTIONAL` or `NOTIONAL` and the tick size from `PRICE_FILTER`.
+    /// Both commissions are [`DEFAULT_COMMISSION`], see [`Spot::with_commission`].
+    pub fn from_exchange_info_json(symbol: &Symbol, json: &str) -> Result<Self, SpotClientError> {
+        let info = SymbolInfo::parse(symbol, json)?;
+
+        let mut lot_size = None;
+        let mut minimum_transaction_amount = None;
+        let mut tick_size = None;
+        for filter in info.filters {
+            match filter {
+                SymbolFilter::PriceFilter { tick_size: e } => tick_size = Some(e.normalize()),
+                SymbolFilter::LotSize { min_qty, step_size } => {
+                    lot_size = Some((min_qty.normalize(), step_size.normalize()))
+                }
+                SymbolFilter::MinNotional { min_notional, .. }
+                | SymbolFilter::Notional { min_notional, .. } => {
+                    minimum_transaction_amount = Some(min_notional.normalize())
+                }
+                SymbolFilter::Other => {}
+            }
+        }
+
+        let missing = |filter: &str| {
+            SpotClientError::ExchangeInfo(format!("no {} filter for {}", filter, symbol))
+        };
+        let (minimum_quantity, step_size) = lot_size.ok_or_else(|| missing("LOT_SIZE"))?;
+        let minimum_transaction_amount =
+            minimum_transaction_amount.ok_or_else(|| missing("MIN_NOTIONAL"))?;
+
+        Ok(Self {
+            symbol: info.symbol,
+            transaction_quantity_precision: step_size.scale(),
+            step_size: Some(step_size),
+            quantity_precision: info.base_asset_precision,
+            amount_precision: info.quote_asset_precision,
+            buying_commission: DEFAULT_COMMISSION,
+            selling_commission: DEFAULT_COMMISSION,
+            minimum_transaction_amount,
+            selling_quantity_rounding: QuantityRounding::default(),
+            decimal_context: DecimalContext::default(),
+            tick_size: tick_size.filter(|e| !e.is_zero()),
+            minimum_quantity: Some(minimum_quantity).filter(|e| !e.is_zero()),
+        })
+    }
+
+    /// Override the commission rates, e.g. with a BNB or VIP discount.
+    pub fn with_commission(mut self, buying: Commission, selling: Commission) -> Self {
+        self.buying_commission = buying;
+        self.selling_commission = selling;
+        self
+    }
+}
+
+impl MarketNotional {
+    /// How the minimum notional filter of `symbol` applies to market orders.
+    ///
+    /// `applyToMarket` of `MIN_NOTIONAL` or `applyMinToMarket` of `NOTIONAL` unset exempts
+    /// market orders, an `avgPriceMins` of 0 checks against the last price instead of the
+    /// average. A missing flag counts as set.
+    pub fn from_exchange_info_json(symbol: &Symbol, json: &str) -> Result<Self, SpotClientError> {
+        let info = SymbolInfo::parse(symbol, json)?;
+        let filter = info.filters.into_iter().find_map(|e| match e {
+            SymbolFilter::MinNotional {
+                apply_to_market,
+                avg_price_mins,
+                ..
+            } => Some((apply_to_market, avg_price_mins)),
+            SymbolFilter::Notional {
+                apply_min_to_market,
+                avg_price_mins,
+                ..
+            } => Some((apply_min_to_market, avg_price_mins)),
+            _ => None,
+        });
+
+        match filter {
+            None => Err(SpotClientError::ExchangeInfo(format!(
+                "no MIN_NOTIONAL filter for {}",
+                symbol
+            ))),
+            Some((Some(false), _)) => Ok(Self::Exempt),
+            Some((_, Some(0))) => Ok(Self::OrderPrice),
+            Some(_) => Ok(Self::AveragePrice),
+        }
+    }
+}
+
+impl SymbolInfo {
+    fn parse(symbol: &Symbol, json: &str) -> Result<Self, SpotClientError> {
+        let info: ExchangeInfo = serde_json::from_str(json).map_err(|e| {
+            SpotClientError::ExchangeInfo(format!("invalid exchange info of {}: {}", symbol, e))
+        })?;
+
+        info.symbols
+            .into_iter()
+            .find(|e| &e.symbol == symbol)
+            .ok_or_else(|| SpotClientError::ExchangeInfo(format!("no exchange info of {}", symbol)))
+    }
+}
+
+#[cfg(test)]
+mod tests_exchange_info {
+    use super::super::tests_general::*;
+    use super::*;
+
+    const BTCUSDT: &str = r#"{
+        "timezone": "UTC",
+        "serverTime": 1565246363776,
+        "rateLimits": [],
+        "exchangeFilters": [],
+        "symbols": [
+            {
+                "symbol": "BTCUSDT",
+                "status": "TRADING",
+                "baseAsset": "BTC",
+                "baseAssetPrecision": 8,
+                "quoteAsset": "USDT",
+                "quotePrecision": 8,
+                "quoteAssetPrecision": 8,
+                "filters": [
+                    {
+                        "filterType": "PRICE_FILTER",
+                        "minPrice": "0.01000000",
+                        "maxPrice": "1000000.00000000",
+                        "tickSize": "0.01000000"
+                    },
+                    {
+                        "filterType": "LOT_SIZE",
+                        "minQty": "0.00001000",
+                        "maxQty": "9000.00000000",
+                        "stepSize": "0.00001000"
+                    },
+                    { "filterType": "ICEBERG_PARTS", "limit": 10 },
+                    {
+                        "filterType": "MIN_NOTIONAL",
+                        "minNotional": "5.00000000",
+                        "applyToMarket": true,
+                        "avgPriceMins": 5
+                    }
+                ]
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn test_from_exchange_info_json() {
+        let symbol = String::from("BTCUSDT");
+        let spot = Spot::from_exchange_info_json(&symbol, BTCUSDT).unwrap();
+
+        assert_eq!(spot.transaction_quantity_precision, 5);
+        assert_eq!(spot.step_size, Some(dec("0.00001")));
+        assert_eq!(spot.quantity_precision, 8);
+        assert_eq!(spot.amount_precision, 8);
+        assert_eq!(spot.minimum_transaction_amount, decimal(5.0));
+        assert_eq!(spot.tick_size, Some(dec("0.01")));
+        assert_eq!(spot.minimum_quantity, Some(dec("0.00001")));
+        assert_eq!(spot.buying_commission, decimal(0.001));
+
+        let spot = spot.with_commission(decimal(0.00075), decimal(0.00075));
+        assert_eq!(spot.selling_commission, decimal(0.00075));
+
+        // The newer NOTIONAL filter in place of MIN_NOTIONAL
+        let json = BTCUSDT
+            .replace(r#""MIN_NOTIONAL""#, r#""NOTIONAL""#)
+            .replace(r#""minNotional": "5.00000000""#, r#""minNotional": "10.0""#);
+        let spot = Spot::from_exchange_info_json(&symbol, &json).unwrap();
+        assert_eq!(spot.minimum_transaction_amount, decimal(10.0));
+
+        let error = Spot::from_exchange_info_json(&String::from("ETHUSDT"), BTCUSDT).unwrap_err();
+        assert_eq!(error.to_string(), "no exchange info of ETHUSDT");
+
+        let json = BTCUSDT.replace("LOT_SIZE", "MARKET_LOT_SIZE");
+        let error = Spot::from_exchange_info_json(&symbol, &json).unwrap_err();
+        assert_eq!(error.to_string(), "no LOT_SIZE filter for BTCUSDT");
+    }
+
+    #[test]
+    fn test_market_notional_from_exchange_info_json() {
+        let symbol = String::from("BTCUSDT");
+        let market_notional = |json: &str| MarketNotional::from_exchange_info_json(&symbol, json);
+        assert_eq!(
+            market_notional(BTCUSDT).unwrap(),
+            MarketNotional::AveragePrice
+        );
+
+        let json = BTCUSDT.replace(r#""avgPriceMins": 5"#, r#""avgPriceMins": 0"#);
+        assert_eq!(market_notional(&json).unwrap(), MarketNotional::OrderPrice);
+
+        let json = BTCUSDT.replace(r#""applyToMarket": true"#, r#""applyToMarket": false"#);
+        assert_eq!(market_notional(&json).unwrap(), MarketNotional::Exempt);
+
+        // NOTIONAL names the flag applyMinToMarket
+        let json = BTCUSDT
+            .replace(r#""MIN_NOTIONAL""#, r#""NOTIONAL""#)
+            .replace(r#""applyToMarket": true"#, r#""applyMinToMarket": false"#);
+        assert_eq!(market_notional(&json).unwrap(), MarketNotional::Exempt);
+
+        let json = BTCUSDT.replace("MIN_NOTIONAL", "MAX_NUM_ORDERS");
+        assert_eq!(
+            market_notional(&json).unwrap_err().to_string(),
+            "no MIN_NOTIONAL filter for BTCUSDT"
+        );
+    }
+}
@@ -1,20 +1,30 @@
+#[cfg(feature = "client")]
 use chrono::Utc;
-use rust_decimal::Decimal;
+use rust_decimal::{Decimal, RoundingStrategy};
 use serde::{Deserialize, Serialize};
 
 use crate::noun::*;
 
+#[cfg(feature = "client")]
 pub mod client;
 pub mod error;
+#[cfg(feature = "client")]
+pub mod exchange_info;
+#[cfg(feature = "client")]
+pub mod router;
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct Spot {
     /// Trading currency pairs
     pub symbol: Symbol,
 
-    /// Transaction precision
+    /// Transaction precision, used as the step when `step_size` is not set
     pub transaction_quantity_precision: Precision,
 
+    /// Lot size step, order quantities must be a multiple of it, e.g. `0.0015`
+    #[serde(default)]
+    pub step_size: Option<Quantity>,
+
     /// Holding quantity precision
     pub quantity_precision: Precision,
 
@@ -29,6 +39,71 @@ pub struct Spot {
 
     /// Minimum transaction amount
     pub minimum_transaction_amount: Amount,
+
+    /// Rounding direction of the selling quantity
+    #[serde(default)]
+    pub selling_quantity_rounding: QuantityRounding,
+
+    /// Rounding of computed holding quantities and commissions
+    #[serde(default)]
+    pub decimal_context: DecimalContext,
+
+    /// Price filter tick size, order prices must be a multiple of it
+    #[serde(default)]
+    pub tick_size: Option<Price>,
+
+    /// Lot size minimum quantity
+    #[serde(default)]
+    pub minimum_quantity: Option<Quantity>,
+}
+
+/// How an order meets the book.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum OrderKind {
+    /// Crosses the spread at the best available price
+    #[default]
+    Market,
+
+    /// Rests at `price` until filled, good till canceled
+    Limit { price: Price },
+}
+
+/// An exchange filter an order can violate, in the order the exchange evaluates them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpotFilter {
+    /// `PRICE_FILTER`, the price is not a multiple of the tick size
+    PriceTick,
+
+    /// `LOT_SIZE`, the quantity is below the minimum quantity
+    MinimumQuantity,
+
+    /// `LOT_SIZE`, the quantity is not a multiple of the transaction step
+    LotStep,
+
+    /// `MIN_NOTIONAL`, price times quantity is below the minimum transaction amount
+    MinimumNotional,
+}
+
+impl std::fmt::Display for SpotFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::PriceTick => write!(f, "price tick"),
+            Self::MinimumQuantity => write!(f, "minimum quantity"),
+            Self::LotStep => write!(f, "lot step"),
+            Self::MinimumNotional => write!(f, "minimum notional"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+pub enum QuantityRounding {
+    /// Truncate to the transaction precision, never sells more than held
+    #[default]
+    FloorToStep,
+
+    /// Round half away from zero to the transaction precision to fully exit a position.
+    /// Note that rounding up may exceed the quantity actually held.
+    RoundToStep,
 }
 
 impl Spot {
@@ -36,41 +111,208 @@ impl Spot {
         &self.symbol
     }
 
-    // Calculating the buying commission fee, the actual holding quantity
-    pub fn buying_quantity_with_commission(&self, quantity: &Quantity) -> Quantity {
-        (quantity * (Decimal::ONE - self.buying_commission)).round_dp(self.quantity_precision)
+    /// Calculating the buying commission fee, the actual holding quantity.
+    ///
+    /// Binance deducts the buying commission from the asset received, so the
+    /// fee is applied to the base quantity rather than the quote amount spent.
+    pub fn buying_quantity_with_commission(
+        &self,
+        quantity: &Quantity,
+    ) -> Result<Quantity, ArithError> {
+        let quantity = checked_mul(*quantity, Decimal::ONE - self.buying_commission)?;
+        Ok(self
+            .decimal_context
+            .round_dp(quantity, self.quantity_precision))
+    }
+
+    /// Smallest quantity increment of an order, `step_size` or one unit of the precision.
+    pub fn transaction_step(&self) -> Quantity {
+        match self.step_size {
+            Some(step) if step > Decimal::ZERO => step,
+            _ => Decimal::new(1, self.transaction_quantity_precision),
+        }
+    }
+
+    /// Decimals of an order quantity, derived from the transaction step.
+    pub fn transaction_precision(&self) -> Precision {
+        self.transaction_step().normalize().scale()
     }
 
     // Accurate the quantity to meet the transaction accuracy requirements
     pub fn transaction_quantity_with_precision(&self, quantity: &Quantity) -> Quantity {
-        quantity.trunc_with_scale(self.transaction_quantity_precision)
+        self.quantity_to_step(quantity)
+    }
+
+    /// Floor to a multiple of `step_size`, or of one unit of the precision without a step.
+    pub fn quantity_to_step(&self, quantity: &Quantity) -> Quantity {
+        self.round_to_step(quantity, RoundingStrategy::ToZero)
     }
 
-    // Calculate earnings after upfront selling commission fees
-    pub fn selling_amount_with_commission(&self, amount: &Amount) -> Amount {
-        let commission = (amount * self.selling_commission).round_dp(self.amount_precision);
-        amount - commission
+    /// Exactly `transaction_precision` decimals, padded with zeros when shorter.
+    ///
+    /// Expects a quantity already at the transaction step, the rest is truncated.
+    pub fn normalize_for_order(&self, quantity: &Quantity) -> Quantity {
+        let mut quantity = self.transaction_quantity_with_precision(&quantity.normalize());
+        quantity.rescale(self.transaction_precision());
+
+        quantity
+    }
+
+    /// Accurate the selling quantity according to `selling_quantity_rounding`.
+    pub fn selling_quantity_with_precision(&self, quantity: &Quantity) -> Quantity {
+        match self.selling_quantity_rounding {
+            QuantityRounding::FloorToStep => self.transaction_quantity_with_precision(quantity),
+            QuantityRounding::RoundToStep => {
+                self.round_to_step(quantity, RoundingStrategy::MidpointAwayFromZero)
+            }
+        }
+    }
+
+    /// Nearest multiple of `tick_size`, a midpoint rounds away from zero.
+    ///
+    /// Passes the price through without a tick size.
+    pub fn price_to_tick(&self, price: &Price) -> Price {
+        let tick_size = match self.tick_size {
+            Some(tick_size) if !tick_size.is_zero() => tick_size,
+            _ => return *price,
+        };
+
+        match price.checked_div(tick_size) {
+            Some(ticks) => {
+                ticks.round_dp_with_strategy(0, RoundingStrategy::MidpointAwayFromZero) * tick_size
+            }
+            None => *price,
+        }
+    }
+
+    fn round_to_step(&self, quantity: &Quantity, strategy: RoundingStrategy) -> Quantity {
+        let step = self.transaction_step();
+        match quantity.checked_div(step) {
+            Some(steps) => steps.round_dp_with_strategy(0, strategy) * step,
+            None => Decimal::ZERO,
+        }
+    }
+
+    /// Calculate earnings after upfront selling commission fees.
+    ///
+    /// Binance deducts the selling commission from the quote asset received,
+    /// so the fee is applied to the gross `amount` (price * quantity).
+    pub fn selling_amount_with_commission(&self, amount: &Amount) -> Result<Amount, ArithError> {
+        let commission = checked_mul(*amount, self.selling_commission)?;
+        let commission = self
+            .decimal_context
+            .round_dp(commission, self.amount_precision);
+        checked_sub(*amount, commission)
+    }
+
+    /// Calculate the amount spent when the buying commission is paid in the quote asset.
+    pub fn buying_amount_with_commission(&self, amount: &Amount) -> Result<Amount, ArithError> {
+        let commission = checked_mul(*amount, self.buying_commission)?;
+        let commission = self
+            .decimal_context
+            .round_dp(commission, self.amount_precision);
+        checked_add(*amount, commission)
     }
 
-    pub fn selling_income_amount(&self, price: &Price, quantity: &Quantity) -> Amount {
-        price * quantity
+    pub fn selling_income_amount(
+        &self,
+        price: &Price,
+        quantity: &Quantity,
+    ) -> Result<Amount, ArithError> {
+        checked_mul(*price, *quantity)
     }
 
-    pub fn buying_spent_amount(&self, price: &Price, quantity: &Quantity) -> Amount {
-        price * quantity
+    pub fn buying_spent_amount(
+        &self,
+        price: &Price,
+        quantity: &Quantity,
+    ) -> Result<Amount, ArithError> {
+        checked_mul(*price, *quantity)
     }
 
+    /// Minimum notional check of an order of `quantity` at `price`.
     pub fn is_reached_minimum_transaction_limit(&self, price: &Price, quantity: &Quantity) -> bool {
-        if price * quantity > self.minimum_transaction_amount {
+        self.is_reached_minimum_transaction_amount(&(price * quantity))
+    }
+
+    /// Minimum notional check against the quote amount directly, as with `quoteOrderQty` orders.
+    ///
+    /// Inclusive like `MIN_NOTIONAL`, an amount exactly at the minimum is allowed.
+    pub fn is_reached_minimum_transaction_amount(&self, amount: &Amount) -> bool {
+        if amount >= &self.minimum_transaction_amount {
             return true;
         }
 
         false
     }
 
+    /// Pre-flight check of a limit order at `price` against every configured filter,
+    /// returns the first violated.
+    pub fn validate_against_filters(
+        &self,
+        price: &Price,
+        quantity: &Quantity,
+    ) -> Result<(), SpotFilter> {
+        self.validate_filters(&OrderKind::Limit { price: *price }, Some(price), quantity)
+    }
+
+    /// Same as [`Spot::validate_against_filters`] for an order of `kind`, with the minimum
+    /// notional checked at `notional_price`, e.g. the average price for market orders, or
+    /// skipped when `None`. Only a limit order carries a price the tick size applies to.
+    pub fn validate_filters(
+        &self,
+        kind: &OrderKind,
+        notional_price: Option<&Price>,
+        quantity: &Quantity,
+    ) -> Result<(), SpotFilter> {
+        if let (OrderKind::Limit { price }, Some(tick_size)) = (kind, self.tick_size) {
+            if !tick_size.is_zero() && !(price % tick_size).is_zero() {
+                return Err(SpotFilter::PriceTick);
+            }
+        }
+
+        if let Some(minimum_quantity) = self.minimum_quantity {
+            if quantity < &minimum_quantity {
+                return Err(SpotFilter::MinimumQuantity);
+            }
+        }
+
+        if !(quantity % self.transaction_step()).is_zero() {
+            return Err(SpotFilter::LotStep);
+        }
+
+        if let Some(notional_price) = notional_price {
+            if !self.is_reached_minimum_transaction_limit(notional_price, quantity) {
+                return Err(SpotFilter::MinimumNotional);
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn buying_quantity_by_amount(&self, price: &Price, amount: &Amount) -> Quantity {
         self.transaction_quantity_with_precision(&(amount / price))
     }
+
+    /// Net profit of buying with `amount` at `buy_price` and selling everything sellable
+    /// at `sell_price`, after both commissions. Dust left below the transaction precision
+    /// is neither sold nor counted.
+    pub fn round_trip_net(
+        &self,
+        buy_price: &Price,
+        sell_price: &Price,
+        amount: &Amount,
+    ) -> Result<Amount, ArithError> {
+        let quantity = self.transaction_quantity_with_precision(&checked_div(*amount, *buy_price)?);
+        let spent = self.buying_spent_amount(buy_price, &quantity)?;
+
+        let held = self.buying_quantity_with_commission(&quantity)?;
+        let selling_quantity = self.selling_quantity_with_precision(&held);
+        let income = self.selling_income_amount(sell_price, &selling_quantity)?;
+        let income = self.selling_amount_with_commission(&income)?;
+
+        checked_sub(income, spent)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -168,6 +410,7 @@ impl SpotTransaction {
     }
 }
 
+#[cfg(feature = "client")]
 fn timestamp_millis() -> i64 {
     let now = Utc::now();
 
@@ -179,14 +422,20 @@ mod tests {
     use rust_decimal::prelude::FromPrimitive;
 
     use super::*;
+    use crate::tests_general::dec;
 
     fn btc_spot() -> Spot {
         Spot {
             symbol: "BTCUSDT".into(),
             transaction_quantity_precision: 5,
+            step_size: None,
             quantity_precision: 7, // BTC Precision
             amount_precision: 8,   // USDT Precision
             minimum_transaction_amount: Decimal::from(5),
+            selling_quantity_rounding: QuantityRounding::FloorToStep,
+            decimal_context: DecimalContext::default(),
+            tick_size: None,
+            minimum_quantity: None,
             buying_commission: Decimal::from_f64(0.001).unwrap(),
             selling_commission: Decimal::from_f64(0.001).unwrap(),
         }
@@ -196,9 +445,14 @@ mod tests {
         Spot {
             symbol: "ETHUSDT".into(),
             transaction_quantity_precision: 4,
+            step_size: None,
             quantity_precision: 7, // ETH Precision
             amount_precision: 8,   // USDT Precision
             minimum_transaction_amount: Decimal::from(5),
+            selling_quantity_rounding: QuantityRounding::FloorToStep,
+            decimal_context: DecimalContext::default(),
+            tick_size: None,
+            minimum_quantity: None,
             buying_commission: Decimal::from_f64(0.001).unwrap(),
             selling_commission: Decimal::from_f64(0.001).unwrap(),
         }
@@ -244,18 +498,65 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_spot_clone() {
+        let spot = btc_spot();
+        let cloned = spot.clone();
+        assert_eq!(spot, cloned);
+        assert_ne!(spot, eth_spot());
+    }
+
+    #[test]
+    fn test_spot_buying_serde() {
+        let buying = SpotBuying {
+            timestamp: 1_700_000_071_234,
+            ..buying_spot_one()
+        };
+
+        let json = serde_json::to_string(&buying).unwrap();
+        assert_eq!(
+            json,
+            r#"{"price":"100.23","quantity":"1.49","spent":"149.3427","quantity_after_commission":"1.48851","timestamp":1700000071234}"#
+        );
+
+        let decoded: SpotBuying = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, buying);
+        assert_eq!(decoded.timestamp, buying.timestamp);
+    }
+
+    #[test]
+    fn test_spot_selling_serde() {
+        let selling = SpotSelling {
+            timestamp: 1_700_000_071_234,
+            ..selling_spot_one()
+        };
+
+        let json = serde_json::to_string(&selling).unwrap();
+        assert_eq!(
+            json,
+            r#"{"price":"112.58","quantity":"1.48","income":"166.6184","income_after_commission":"166.4517816","timestamp":1700000071234}"#
+        );
+
+        let decoded: SpotSelling = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, selling);
+        assert_eq!(decoded.timestamp, selling.timestamp);
+    }
+
     #[test]
     fn test_buying_quantity_with_commission() {
-        let quantity =
-            btc_spot().buying_quantity_with_commission(&Decimal::from_f64(0.00985).unwrap());
+        let quantity = btc_spot()
+            .buying_quantity_with_commission(&Decimal::from_f64(0.00985).unwrap())
+            .unwrap();
         assert_eq!(quantity, Decimal::from_f64(0.0098402).unwrap());
 
-        let quantity =
-            btc_spot().buying_quantity_with_commission(&Decimal::from_f64(0.0008).unwrap());
+        let quantity = btc_spot()
+            .buying_quantity_with_commission(&Decimal::from_f64(0.0008).unwrap())
+            .unwrap();
         assert_eq!(quantity, Decimal::from_f64(0.0007992).unwrap());
 
-        let quantity =
-            eth_spot().buying_quantity_with_commission(&Decimal::from_f64(0.0025).unwrap());
+        let quantity = eth_spot()
+            .buying_quantity_with_commission(&Decimal::from_f64(0.0025).unwrap())
+            .unwrap();
         assert_eq!(quantity, Decimal::from_f64(0.0024975).unwrap());
     }
 
@@ -274,21 +575,355 @@ mod tests {
         assert_eq!(quantity, Decimal::from_f64(0.0023).unwrap());
     }
 
+    #[test]
+    fn test_normalize() {
+        let quantity = Decimal::from_str_exact("0.01156840").unwrap();
+        assert_eq!(normalize_for_display(&quantity).to_string(), "0.0115684");
+        assert_eq!(
+            btc_spot().normalize_for_order(&quantity).to_string(),
+            "0.01156"
+        );
+
+        let quantity = Decimal::from_str_exact("0.0100").unwrap();
+        assert_eq!(normalize_for_display(&quantity).to_string(), "0.01");
+        assert_eq!(
+            btc_spot().normalize_for_order(&quantity).to_string(),
+            "0.01000"
+        );
+
+        let amount = Decimal::from_str_exact("65.76500000").unwrap();
+        assert_eq!(normalize_for_display(&amount).to_string(), "65.765");
+        assert_eq!(
+            eth_spot().normalize_for_order(&Decimal::TWO).to_string(),
+            "2.0000"
+        );
+    }
+
+    #[test]
+    fn test_validate_against_filters() {
+        let spot = Spot {
+            tick_size: Some(dec("0.01")),
+            minimum_quantity: Some(dec("0.0001")),
+            ..btc_spot()
+        };
+        let (price, quantity) = (dec("43145.42"), dec("0.00116"));
+        assert_eq!(spot.validate_against_filters(&price, &quantity), Ok(()));
+
+        assert_eq!(
+            spot.validate_against_filters(&dec("43145.425"), &quantity),
+            Err(SpotFilter::PriceTick)
+        );
+        assert_eq!(
+            spot.validate_against_filters(&price, &dec("0.00005")),
+            Err(SpotFilter::MinimumQuantity)
+        );
+        assert_eq!(
+            spot.validate_against_filters(&price, &dec("0.001161")),
+            Err(SpotFilter::LotStep)
+        );
+        assert_eq!(
+            spot.validate_against_filters(&price, &dec("0.0001")),
+            Err(SpotFilter::MinimumNotional)
+        );
+
+        // Evaluated in the exchange order, the price filter comes first
+        assert_eq!(
+            spot.validate_against_filters(&dec("43145.425"), &dec("0.00005")),
+            Err(SpotFilter::PriceTick)
+        );
+
+        // Unset filters are not checked, trailing zeros are not extra decimals
+        let spot = btc_spot();
+        assert_eq!(
+            spot.validate_against_filters(&dec("43145.425"), &dec("0.0011600")),
+            Ok(())
+        );
+        let limit = OrderKind::Limit { price };
+        assert_eq!(spot.validate_filters(&limit, None, &dec("0.0001")), Ok(()));
+
+        // A market order has no price of its own to be on the tick
+        let spot = Spot {
+            tick_size: Some(dec("0.01")),
+            ..btc_spot()
+        };
+        assert_eq!(
+            spot.validate_filters(&OrderKind::Market, Some(&dec("43145.425")), &quantity),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_price_to_tick() {
+        let spot = Spot {
+            tick_size: Some(dec("0.01")),
+            ..btc_spot()
+        };
+        assert_eq!(spot.price_to_tick(&dec("43145.4249")), dec("43145.42"));
+        assert_eq!(spot.price_to_tick(&dec("43145.425")), dec("43145.43"));
+        assert_eq!(spot.price_to_tick(&dec("43145.42")), dec("43145.42"));
+
+        let spot = Spot {
+            tick_size: Some(dec("0.5")),
+            ..btc_spot()
+        };
+        assert_eq!(spot.price_to_tick(&dec("100.2")), dec("100.0"));
+        assert_eq!(spot.price_to_tick(&dec("100.25")), dec("100.5"));
+        assert_eq!(spot.price_to_tick(&dec("100.74")), dec("100.5"));
+        assert_eq!(spot.price_to_tick(&dec("100.75")), dec("101.0"));
+
+        // The rounded price passes the tick filter
+        let price = spot.price_to_tick(&dec("43145.3"));
+        let limit = OrderKind::Limit { price };
+        assert_eq!(spot.validate_filters(&limit, None, &dec("0.001")), Ok(()));
+
+        let spot = Spot {
+            tick_size: None,
+            ..btc_spot()
+        };
+        assert_eq!(spot.price_to_tick(&dec("43145.4249")), dec("43145.4249"));
+    }
+
+    #[test]
+    fn test_step_size() {
+        let spot = Spot {
+            step_size: Some(dec("0.0015")),
+            ..btc_spot()
+        };
+        assert_eq!(spot.transaction_step(), dec("0.0015"));
+        assert_eq!(spot.transaction_precision(), 4);
+        assert_eq!(btc_spot().transaction_step(), dec("0.00001"));
+        assert_eq!(btc_spot().transaction_precision(), 5);
+
+        // Floored to a multiple of the step, not to the fourth decimal
+        assert_eq!(
+            spot.transaction_quantity_with_precision(&dec("0.0049")),
+            dec("0.0045")
+        );
+        assert_eq!(
+            spot.buying_quantity_by_amount(&dec("40000"), &dec("100")),
+            dec("0.0015")
+        );
+        assert_eq!(
+            spot.normalize_for_order(&dec("0.003")).to_string(),
+            "0.0030"
+        );
+
+        let round = Spot {
+            selling_quantity_rounding: QuantityRounding::RoundToStep,
+            ..spot.clone()
+        };
+        assert_eq!(
+            spot.selling_quantity_with_precision(&dec("0.0054")),
+            dec("0.0045")
+        );
+        assert_eq!(
+            round.selling_quantity_with_precision(&dec("0.0054")),
+            dec("0.006")
+        );
+        assert_eq!(
+            round.selling_quantity_with_precision(&dec("0.00524")),
+            dec("0.0045")
+        );
+
+        // The lot step filter checks the multiple, four decimals are not enough
+        let price = dec("40000");
+        assert_eq!(spot.validate_against_filters(&price, &dec("0.003")), Ok(()));
+        assert_eq!(
+            spot.validate_against_filters(&price, &dec("0.0031")),
+            Err(SpotFilter::LotStep)
+        );
+
+        // A power of ten step floors like the decimal scale
+        let spot = Spot {
+            step_size: Some(dec("0.0001")),
+            ..btc_spot()
+        };
+        assert_eq!(
+            spot.buying_quantity_by_amount(&dec("40000"), &dec("100")),
+            dec("0.0025")
+        );
+        assert_eq!(
+            spot.selling_quantity_with_precision(&dec("0.01158")),
+            dec("0.0115")
+        );
+
+        // A step that is not a power of ten, e.g. a lot of quarter units
+        let spot = Spot {
+            step_size: Some(dec("0.25")),
+            ..btc_spot()
+        };
+        assert_eq!(spot.transaction_precision(), 2);
+        assert_eq!(
+            spot.buying_quantity_by_amount(&dec("100"), &dec("370")),
+            dec("3.5")
+        );
+        assert_eq!(
+            spot.selling_quantity_with_precision(&dec("1.99")),
+            dec("1.75")
+        );
+        assert_eq!(spot.quantity_to_step(&dec("0.2")), dec("0"));
+        assert_eq!(spot.quantity_to_step(&dec("2.74")), dec("2.5"));
+        assert_eq!(
+            spot.validate_against_filters(&dec("100"), &dec("1.25")),
+            Ok(())
+        );
+        assert_eq!(
+            spot.validate_against_filters(&dec("100"), &dec("1.3")),
+            Err(SpotFilter::LotStep)
+        );
+    }
+
+    #[test]
+    fn test_selling_quantity_with_precision() {
+        let floor = btc_spot();
+        let round = Spot {
+            selling_quantity_rounding: QuantityRounding::RoundToStep,
+            tick_size: None,
+            minimum_quantity: None,
+            ..btc_spot()
+        };
+
+        let quantity = Decimal::from_f64(0.003495).unwrap();
+        assert_eq!(
+            floor.selling_quantity_with_precision(&quantity),
+            Decimal::from_f64(0.00349).unwrap()
+        );
+        assert_eq!(
+            round.selling_quantity_with_precision(&quantity),
+            Decimal::from_f64(0.0035).unwrap()
+        );
+
+        let quantity = Decimal::from_f64(0.0034949).unwrap();
+        assert_eq!(
+            floor.selling_quantity_with_precision(&quantity),
+            Decimal::from_f64(0.00349).unwrap()
+        );
+        assert_eq!(
+            round.selling_quantity_with_precision(&quantity),
+            Decimal::from_f64(0.00349).unwrap()
+        );
+    }
+
     #[test]
     fn test_selling_amount_with_commission() {
-        let amount =
-            btc_spot().selling_amount_with_commission(&Decimal::from_f64(65.8308373).unwrap());
+        let amount = btc_spot()
+            .selling_amount_with_commission(&Decimal::from_f64(65.8308373).unwrap())
+            .unwrap();
         assert_eq!(amount, Decimal::from_f64(65.76500646).unwrap());
 
-        let amount =
-            btc_spot().selling_amount_with_commission(&Decimal::from_f64(16.4650161).unwrap());
+        let amount = btc_spot()
+            .selling_amount_with_commission(&Decimal::from_f64(16.4650161).unwrap())
+            .unwrap();
         assert_eq!(amount, Decimal::from_f64(16.44855108).unwrap());
 
-        let amount =
-            eth_spot().selling_amount_with_commission(&Decimal::from_f64(12.731936).unwrap());
+        let amount = eth_spot()
+            .selling_amount_with_commission(&Decimal::from_f64(12.731936).unwrap())
+            .unwrap();
         assert_eq!(amount, Decimal::from_f64(12.71920406).unwrap());
     }
 
+    #[test]
+    fn test_decimal_context() {
+        let truncate = Spot {
+            decimal_context: DecimalContext::new(8, Rounding::Truncate),
+            ..btc_spot()
+        };
+        let quantity = Decimal::from_f64(0.00985).unwrap();
+        let amount = Decimal::from_f64(65.8308373).unwrap();
+
+        // 0.00984015 and a commission of 0.0658308373 sit on or past the midpoint
+        assert_eq!(
+            btc_spot().buying_quantity_with_commission(&quantity),
+            Ok(Decimal::from_f64(0.0098402).unwrap())
+        );
+        assert_eq!(
+            truncate.buying_quantity_with_commission(&quantity),
+            Ok(Decimal::from_f64(0.0098401).unwrap())
+        );
+        assert_eq!(
+            btc_spot().selling_amount_with_commission(&amount),
+            Ok(Decimal::from_f64(65.76500646).unwrap())
+        );
+        assert_eq!(
+            truncate.selling_amount_with_commission(&amount),
+            Ok(Decimal::from_f64(65.76500647).unwrap())
+        );
+
+        // The context caps the scale of the symbol precision
+        let coarse = Spot {
+            decimal_context: DecimalContext::new(4, Rounding::HalfUp),
+            ..btc_spot()
+        };
+        assert_eq!(
+            coarse.buying_quantity_with_commission(&quantity),
+            Ok(Decimal::from_f64(0.0098).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_commission_matrix() {
+        let spot = btc_spot();
+        let prices = [5.5, 100.23, 2596.04, 43145.42, 72000.0];
+        let quantities = [0.0008, 0.00985, 0.01158, 0.5, 3.15487];
+        let fee = Decimal::ONE - spot.buying_commission;
+
+        for price in prices.iter() {
+            let price = Decimal::from_f64(*price).unwrap();
+            // Rounding the quantity moves its value by at most half a unit of precision
+            let tolerance = price * Decimal::new(1, spot.quantity_precision);
+
+            for quantity in quantities.iter() {
+                let quantity = Decimal::from_f64(*quantity).unwrap();
+                let gross_amount = spot.buying_spent_amount(&price, &quantity).unwrap();
+
+                let net_quantity = spot.buying_quantity_with_commission(&quantity).unwrap();
+                assert!((net_quantity * price - gross_amount * fee).abs() <= tolerance);
+
+                let net_amount = spot.selling_amount_with_commission(&gross_amount).unwrap();
+                let tolerance = Decimal::new(1, spot.amount_precision);
+                assert!((net_amount - gross_amount * fee).abs() <= tolerance);
+            }
+        }
+    }
+
+    #[test]
+    fn test_commission_overflow() {
+        let spot = Spot {
+            buying_commission: Decimal::from(-2),
+            ..btc_spot()
+        };
+        let error = spot.buying_spent_amount(&Decimal::MAX, &Decimal::TWO);
+        assert_eq!(error, Err(ArithError::Overflow));
+
+        let error = spot.buying_quantity_with_commission(&Decimal::MAX);
+        assert_eq!(error, Err(ArithError::Overflow));
+
+        let error = spot.buying_amount_with_commission(&Decimal::MAX);
+        assert_eq!(error, Err(ArithError::Underflow));
+    }
+
+    #[test]
+    fn test_round_trip_net() {
+        let spot = btc_spot();
+        let amount = Decimal::from(500);
+        let buy_price = Decimal::from_f64(43145.42).unwrap();
+
+        let net = spot.round_trip_net(&buy_price, &Decimal::from(45000), &amount);
+        assert_eq!(net, Ok(Decimal::from_f64(20.0558364).unwrap()));
+
+        let net = spot.round_trip_net(&buy_price, &Decimal::from(43000), &amount);
+        assert_eq!(net, Ok(Decimal::from_f64(-3.0410436).unwrap()));
+
+        // Commission makes selling at the buying price a loss
+        let net = spot
+            .round_trip_net(&buy_price, &buy_price, &amount)
+            .unwrap();
+        assert!(net.is_sign_negative());
+
+        let net = spot.round_trip_net(&Decimal::ZERO, &buy_price, &amount);
+        assert_eq!(net, Err(ArithError::DivideByZero));
+    }
+
     #[test]
     fn test_is_allow_transaction() {
         let allow = btc_spot().is_reached_minimum_transaction_limit(
@@ -303,6 +938,13 @@ mod tests {
         );
         assert_eq!(allow, true);
 
+        // Exactly the minimum notional of 5
+        let allow = btc_spot().is_reached_minimum_transaction_limit(
+            &Decimal::from_f64(2.5).unwrap(),
+            &Decimal::from_f64(2.0).unwrap(),
+        );
+        assert_eq!(allow, true);
+
         let allow = btc_spot().is_reached_minimum_transaction_limit(
             &Decimal::from_f64(30.5).unwrap(),
             &Decimal::from_f64(2.0).unwrap(),
@@ -316,6 +958,32 @@ mod tests {
         assert_eq!(allow, false);
     }
 
+    #[test]
+    fn test_is_reached_minimum_transaction_amount() {
+        let spot = btc_spot();
+        assert_eq!(
+            spot.is_reached_minimum_transaction_amount(&Decimal::from_f64(4.99).unwrap()),
+            false
+        );
+        assert_eq!(
+            spot.is_reached_minimum_transaction_amount(&Decimal::from_f64(5.0).unwrap()),
+            true
+        );
+        assert_eq!(
+            spot.is_reached_minimum_transaction_amount(&Decimal::from_f64(10.0).unwrap()),
+            true
+        );
+
+        // Both order styles share the same notional check
+        let price = Decimal::from_f64(43145.42).unwrap();
+        let amount = Decimal::from_f64(5.01).unwrap();
+        let quantity = Decimal::from_f64(0.00012).unwrap();
+        assert_eq!(
+            spot.is_reached_minimum_transaction_amount(&amount),
+            spot.is_reached_minimum_transaction_limit(&price, &quantity)
+        );
+    }
+
     #[test]
     fn test_buying_quantity_by_amount() {
         let quantity = btc_spot().buying_quantity_by_amount(
@@ -377,35 +1045,24 @@ mod tests {
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "client"))]
 mod tests_general {
-    use rust_decimal::{prelude::FromPrimitive, Decimal};
-
     use super::*;
 
-    pub(super) fn decimal(value: f64) -> Decimal {
-        Decimal::from_f64(value).unwrap()
-    }
-
-    pub(super) fn btc_spot() -> Spot {
-        Spot {
-            symbol: String::from("BTCUSDT"),
-            transaction_quantity_precision: 5,
-            quantity_precision: 7, // BTC Precision
-            amount_precision: 8,   // USDT Precision
-            minimum_transaction_amount: decimal(5.0),
-            buying_commission: decimal(0.001),
-            selling_commission: decimal(0.001),
-        }
-    }
+    pub(super) use crate::tests_general::{btc_spot, dec, decimal};
 
     pub(super) fn eth_spot() -> Spot {
         Spot {
             symbol: String::from("ETHUSDT"),
             transaction_quantity_precision: 4,
+            step_size: None,
             quantity_precision: 7, // ETH Precision
             amount_precision: 8,   // USDT Precision
             minimum_transaction_amount: decimal(5.0),
+            selling_quantity_rounding: QuantityRounding::FloorToStep,
+            decimal_context: DecimalContext::default(),
+            tick_size: None,
+            minimum_quantity: None,
             buying_commission: decimal(0.001),
             selling_commission: decimal(0.001),
         }
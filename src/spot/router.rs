@@ -0,0 +1,285 @@
+use std::{future::Future, pin::Pin};
+
+use binance::{
+    account::{Account, OrderRequest},
+    general::General,
+    rest_model::{DustTransfer, OrderSide, OrderType, TimeInForce, Transaction},
+    wallet::Wallet,
+};
+use chrono::Utc;
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use tracing::{debug, warn};
+
+use super::{
+    client::{FillSummary, SpotClient},
+    error::{DecimalField, SpotClientError},
+};
+use crate::{noun::*, strategy::TradeSide};
+
+pub use super::OrderKind;
+
+pub type RouterFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<Fill, SpotClientError>> + Send + Sync + 'a>>;
+
+pub type SyncFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<(), SpotClientError>> + Send + Sync + 'a>>;
+
+pub type DriftFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<Option<u64>, SpotClientError>> + Send + Sync + 'a>>;
+
+/// Largest receive window Binance accepts, in milliseconds
+const MAX_RECV_WINDOW: u64 = 60_000;
+
+/// How far ahead of the server time Binance accepts a timestamp, in milliseconds
+const AHEAD_TOLERANCE: i64 = 1_000;
+
+pub type DustFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<DustConversion, SpotClientError>> + Send + Sync + 'a>>;
+
+/// An order as decided by the client, before any venue specific encoding.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderIntent {
+    pub symbol: Symbol,
+    pub side: TradeSide,
+
+    /// Already at the transaction precision of the symbol
+    pub quantity: Quantity,
+    pub kind: OrderKind,
+
+    /// Milliseconds the order stays valid after its timestamp, the router default when `None`
+    pub recv_window: Option<u64>,
+}
+
+/// What the venue filled for an order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Fill {
+    pub summary: FillSummary,
+
+    /// Milliseconds since the epoch of the execution
+    pub timestamp: i64,
+}
+
+impl Fill {
+    pub fn from_transaction(
+        symbol: &Symbol,
+        transaction: &Transaction,
+    ) -> Result<Self, SpotClientError> {
+        Ok(Self {
+            summary: FillSummary::from_transaction(symbol, transaction)?,
+            timestamp: transaction.transact_time as i64,
+        })
+    }
+
+    /// Nothing filled, e.g. a response without fills.
+    pub fn is_empty(&self) -> bool {
+        self.summary.quantity.is_zero()
+    }
+}
+
+/// Dust swept into BNB by the exchange.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DustConversion {
+    /// Each converted asset with the BNB it turned into, service charge deducted
+    pub converted: Vec<(Symbol, Amount)>,
+
+    /// BNB received in total, service charge deducted
+    pub transferred: Amount,
+    pub service_charge: Amount,
+}
+
+impl DustConversion {
+    pub fn from_transfer(transfer: &DustTransfer) -> Result<Self, SpotClientError> {
+        let decimal = |asset: &str, value: f64| {
+            Decimal::from_f64(value).ok_or_else(|| SpotClientError::Decimal {
+                symbol: asset.to_string(),
+                field: DecimalField::Amount,
+                value: value.to_string(),
+            })
+        };
+
+        let mut converted = Vec::with_capacity(transfer.transfer_result.len());
+        for result in transfer.transfer_result.iter() {
+            let amount = decimal(&result.from_asset, result.transfered_amount)?;
+            converted.push((result.from_asset.clone(), amount));
+        }
+
+        Ok(Self {
+            converted,
+            transferred: decimal("BNB", transfer.total_transferred)?,
+            service_charge: decimal("BNB", transfer.total_service_charge)?,
+        })
+    }
+}
+
+/// Where [`SpotClient`] sends its production orders.
+pub trait OrderRouter: Send + Sync {
+    fn place(&self, intent: OrderIntent) -> RouterFuture<'_>;
+
+    /// Verify the credentials the orders are placed with, before a production client is armed.
+    fn verify(&self) -> SyncFuture<'_> {
+        Box::pin(async { Ok(()) })
+    }
+
+    /// Measure the drift from the server time after a `-1021` rejection, before the order
+    /// is retried. Returns the receive window covering the drift for the retry, `None` keeps
+    /// the default window.
+    fn check_clock_drift(&self) -> DriftFuture<'_> {
+        Box::pin(async { Ok(None) })
+    }
+
+    /// Convert the dust balances of `assets` to BNB.
+    fn convert_dust(&self, _assets: Vec<Symbol>) -> DustFuture<'_> {
+        Box::pin(async {
+            Err(SpotClientError::Trading(String::from(
+                "router does not convert dust",
+            )))
+        })
+    }
+}
+
+/// Market orders on Binance spot, the default router.
+#[derive(Clone)]
+pub struct BinanceRouter {
+    client: Account,
+}
+
+impl BinanceRouter {
+    pub fn new(client: Account) -> Self {
+        Self { client }
+    }
+
+    /// Receive window covering a local clock `drift` milliseconds ahead of the server time,
+    /// `None` when the clock is too far ahead to be covered.
+    pub fn recv_window_for_drift(recv_window: u64, drift: i64) -> Option<u64> {
+        if drift > AHEAD_TOLERANCE {
+            warn!(
+                "local clock is {}ms ahead of the server time, sync the clock",
+                drift
+            );
+            return None;
+        }
+
+        let behind = drift.min(0).unsigned_abs();
+        Some(recv_window.saturating_add(behind).min(MAX_RECV_WINDOW))
+    }
+
+    /// The Binance order placed for `intent`.
+    pub fn order_request(intent: &OrderIntent) -> Result<OrderRequest, SpotClientError> {
+        let to_f64 = |field: DecimalField, value: &Decimal| {
+            value.to_f64().ok_or_else(|| SpotClientError::Decimal {
+                symbol: intent.symbol.clone(),
+                field,
+                value: value.to_string(),
+            })
+        };
+        let quantity = to_f64(DecimalField::Quantity, &intent.quantity)?;
+        let side = match intent.side {
+            TradeSide::Buy => OrderSide::Buy,
+            TradeSide::Sell => OrderSide::Sell,
+        };
+        let (order_type, price, time_in_force) = match &intent.kind {
+            OrderKind::Market => (OrderType::Market, None, None),
+            OrderKind::Limit { price } => (
+                OrderType::Limit,
+                Some(to_f64(DecimalField::Price, price)?),
+                Some(TimeInForce::GTC),
+            ),
+        };
+
+        Ok(OrderRequest {
+            symbol: intent.symbol.clone(),
+            side,
+            order_type,
+            time_in_force,
+            quantity: Some(quantity),
+            price,
+            recv_window: intent.recv_window,
+            ..OrderRequest::default()
+        })
+    }
+}
+
+impl OrderRouter for BinanceRouter {
+    fn place(&self, intent: OrderIntent) -> RouterFuture<'_> {
+        Box::pin(async move {
+            let request = Self::order_request(&intent)?;
+            let order = match intent.side {
+                TradeSide::Buy => "buy",
+                TradeSide::Sell => "sell",
+            };
+
+            let transaction =
+                self.client
+                    .place_order(request)
+                    .await
+                    .map_err(|e| SpotClientError::Exchange {
+                        context: format!("failed to place {} order of {}", order, intent.symbol),
+                        source: Box::new(e),
+                    })?;
+
+            SpotClient::verify_transaction(&transaction)?;
+            Fill::from_transaction(&intent.symbol, &transaction)
+        })
+    }
+
+    // A signed account request fails on invalid credentials or a key without permission
+    fn verify(&self) -> SyncFuture<'_> {
+        Box::pin(async move {
+            self.client
+                .get_account()
+                .await
+                .map_err(|e| SpotClientError::Exchange {
+                    context: String::from("failed to verify credentials"),
+                    source: Box::new(e),
+                })?;
+
+            Ok(())
+        })
+    }
+
+    // The binance crate signs with the local clock, a clock behind the server is covered by a
+    // wider receive window. A clock ahead beyond the tolerance can not be covered.
+    fn check_clock_drift(&self) -> DriftFuture<'_> {
+        Box::pin(async move {
+            let general = General {
+                client: self.client.client.clone(),
+            };
+            let server_time =
+                general
+                    .get_server_time()
+                    .await
+                    .map_err(|e| SpotClientError::Exchange {
+                        context: String::from("failed to get server time"),
+                        source: Box::new(e),
+                    })?;
+
+            let drift = Utc::now().timestamp_millis() - server_time.server_time as i64;
+            match drift.unsigned_abs() > self.client.recv_window {
+                true => warn!("local clock drifts {}ms from the server time", drift),
+                false => debug!("local clock drifts {}ms from the server time", drift),
+            }
+
+            Ok(Self::recv_window_for_drift(self.client.recv_window, drift))
+        })
+    }
+
+    fn convert_dust(&self, assets: Vec<Symbol>) -> DustFuture<'_> {
+        Box::pin(async move {
+            let wallet = Wallet {
+                client: self.client.client.clone(),
+                recv_window: self.client.recv_window,
+            };
+            let context = format!("failed to convert dust of {}", assets.join(", "));
+            let transfer =
+                wallet
+                    .dust_transfer(assets)
+                    .await
+                    .map_err(|e| SpotClientError::Exchange {
+                        context,
+                        source: Box::new(e),
+                    })?;
+
+            DustConversion::from_transfer(&transfer)
+        })
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use crate::{extension::LockResultExt, noun::*};
+
+use super::TrapOutcome;
+
+/// Why a position did not trade on a tick.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SkipReason {
+    Paused,
+    OutsideTradingWindow,
+
+    /// Short and the price is outside the buying range, or held and outside the selling range
+    OutOfRange,
+
+    /// Within the buying range but the position is already held
+    AlreadyHeld,
+
+    /// Within the selling range but nothing is held to sell
+    NotHeld,
+
+    /// Within the selling range at or below the entry price plus the round-trip fee
+    BelowCost,
+
+    /// Within the buying range for fewer ticks than the activation requires
+    Activating {
+        touches: usize,
+        required: usize,
+    },
+
+    MaxOpenPositions,
+
+    /// Within the buying range since the first tick, waits for the price to leave it once
+    Disarmed,
+
+    /// A buy of the position from another tick is still awaiting the exchange
+    Buying,
+
+    /// The grid stop loss was reached, short positions stay out
+    StopLoss,
+
+    /// The grid take profit was reached, the grid no longer trades
+    TakeProfit,
+
+    /// The price fell the trailing stop below its peak, short positions stay out
+    TrailingStop,
+
+    /// The realized and unrealized loss reached the max drawdown, short positions stay out
+    MaxDrawdown,
+
+    /// Exactly at the top of the grid range, where only the configured levels sell
+    RangeHigh,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Decision {
+    Traded(TrapOutcome),
+    Skipped(SkipReason),
+
+    /// The trade failed and the error handler skipped it
+    Failed(String),
+}
+
+/// What one position decided on one tick.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecisionEntry {
+    pub timestamp: i64,
+
+    /// Unknown when the tick was skipped before fetching the price
+    pub price: Option<Price>,
+
+    /// Index of the position in the strategy
+    pub level: usize,
+
+    pub decision: Decision,
+}
+
+/// Audit trail of every decision, one entry per position per tick.
+///
+/// Keeps the latest `capacity` entries, the oldest are dropped first.
+#[derive(Debug)]
+pub struct DecisionLog {
+    entries: Mutex<VecDeque<DecisionEntry>>,
+    capacity: usize,
+}
+
+impl DecisionLog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+        }
+    }
+
+    pub fn record(&self, entry: DecisionEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut entries = self.entries.lock().ignore_poison();
+        if entries.len() == self.capacity {
+            entries.pop_front();
+        }
+
+        entries.push_back(entry);
+    }
+
+    /// Entries from the oldest to the latest.
+    pub fn entries(&self) -> Vec<DecisionEntry> {
+        self.entries
+            .lock()
+            .ignore_poison()
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().ignore_poison().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().ignore_poison().clear()
+    }
+}
+
+#[cfg(test)]
+mod tests_decision {
+    use super::super::tests_general::*;
+    use super::*;
+
+    fn skipped(level: usize) -> DecisionEntry {
+        DecisionEntry {
+            timestamp: 0,
+            price: Some(decimal(100.0)),
+            level,
+            decision: Decision::Skipped(SkipReason::OutOfRange),
+        }
+    }
+
+    #[test]
+    fn test_capacity() {
+        let log = DecisionLog::new(2);
+        log.record(skipped(0));
+        log.record(skipped(1));
+        log.record(skipped(2));
+
+        let levels: Vec<usize> = log.entries().iter().map(|e| e.level).collect();
+        assert_eq!(levels, vec![1, 2]);
+
+        let log = DecisionLog::new(0);
+        log.record(skipped(0));
+        assert_eq!(log.is_empty(), true);
+    }
+}
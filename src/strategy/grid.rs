@@ -1,243 +1,2191 @@
 use std::error::Error;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc::Sender, Arc, Mutex};
 
-use rust_decimal::prelude::FromPrimitive;
+use chrono::NaiveTime;
+
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "client")]
+use tokio::sync::watch;
+use tracing::{debug, instrument};
 
 use super::{
+    decision::{Decision, DecisionLog, SkipReason},
     limit::{Limit, LimitPosition},
-    AmountPoint, PinFutureResult, PricePoint, QuantityPoint, Range, Strategy,
+    round_trip::RoundTrip,
+    snapshot::{LevelSnapshot, StrategySnapshot},
+    AmountPoint, ErrorHandler, Interval, PinFutureResult, PricePoint, QuantityPoint, Range,
+    Strategy, StrategyEvent, TradeSide, TrapOutcome,
 };
-use crate::noun::*;
+use crate::{clock::Clock, extension::LockResultExt, noun::*, spot::Spot};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Grid {
     limit: Limit,
-    options: GridOptions,
+    options: Mutex<GridOptions>,
+
+    /// The take profit was reached and every level sold, the grid no longer trades
+    #[serde(default)]
+    is_done: AtomicBool,
+
+    /// Highest price seen since a level was last held, followed by the trailing stop
+    #[serde(default)]
+    peak: Mutex<Option<Price>>,
+
+    /// Held positions traded after the levels, e.g. carried over by a reload, never rebuilt
+    #[serde(default)]
+    extras: usize,
+
+    /// Source of live config updates, checked at the start of every trap
+    #[cfg(feature = "client")]
+    #[serde(skip)]
+    updates: Option<Mutex<watch::Receiver<StrategyConfigUpdate>>>,
 }
 
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct GridOptions {
     pub stop_loss: Option<Range>,
+
+    /// Within this range every held level sells and the grid is done, checked before the stop loss
+    #[serde(default)]
+    pub take_profit: Option<Range>,
+
+    /// Distance kept between the highest price seen and the top of the stop loss range.
+    /// The range moves up with new highs and never moves down.
+    #[serde(default)]
+    pub stop_loss_ratchet: Option<Price>,
+
+    /// Fraction below the peak price at which every held level sells, `0.1` trails 10% below.
+    /// The peak is followed from the first tick with a level held and reset once all are short.
+    #[serde(default)]
+    pub trailing_stop: Option<Decimal>,
+
+    /// Loss in the quote asset at which every held level sells, realized and unrealized
+    /// together, `50` sells once the grid is down 50 USDT. Independent of `stop_loss`.
+    #[serde(default)]
+    pub max_drawdown: Option<Amount>,
+
+    /// Maximum number of levels holding inventory at the same time
+    pub max_open_positions: Option<usize>,
+
+    /// Round-trip fee rate, when set a level never sells below its entry price plus this fee
+    pub sell_above_cost: Option<Commission>,
+
+    /// Consecutive ticks the price must stay within a buying range before the level buys
+    #[serde(default)]
+    pub activation_ticks: Option<usize>,
+
+    /// Levels whose buying range holds the starting price wait for the price to leave
+    /// the range once, rather than buying on the first tick
+    #[serde(default)]
+    pub skip_starting_levels: bool,
+
+    /// What a price exactly at the top of the range sells, see [`RangeHigh`]
+    #[serde(default)]
+    pub range_high: RangeHigh,
+
+    /// How the levels are spread across the range
+    #[serde(default)]
+    pub spacing: Spacing,
+
+    /// Rounding of the level investment and interval, truncates to 6 decimals when unset
+    #[serde(default)]
+    pub decimal_context: Option<DecimalContext>,
+}
+
+/// Behavior of the levels at a price exactly at the top of the grid range.
+///
+/// The buying ranges of the levels are half-open, the bottom of the range is only ever
+/// within the buying range of the lowest level. Every selling range ends at the top of
+/// the range and includes it, a price exactly at the high sells one level per tick
+/// unless configured otherwise rather than every held level at once.
+///
+/// No buying range of the levels reaches the top, such a tick only sells.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum RangeHigh {
+    /// Nothing trades
+    Hold,
+
+    /// Only the held level with the highest selling range sells
+    #[default]
+    SellTopLevel,
+
+    /// Every held level sells, as it would just below the top
+    SellAll,
+}
+
+/// How [`Grid`] spreads its levels across the range.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Spacing {
+    /// The same price difference between levels
+    #[default]
+    Arithmetic,
+
+    /// The same price ratio between levels, wider apart towards the top, requires a positive low
+    Geometric,
+}
+
+const GRID_DECIMAL_CONTEXT: DecimalContext = DecimalContext::new(6, Rounding::Truncate);
+
+impl GridOptions {
+    pub fn validate(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if let Some(stop_loss) = &self.stop_loss {
+            if stop_loss.is_zero_width() {
+                return Err(format!("stop loss range {} has zero width", stop_loss.low()).into());
+            }
+        }
+
+        if let Some(trailing_stop) = &self.trailing_stop {
+            if trailing_stop <= &Decimal::ZERO || trailing_stop >= &Decimal::ONE {
+                return Err(
+                    format!("trailing stop {} must be between 0 and 1", trailing_stop).into(),
+                );
+            }
+        }
+
+        if let Some(max_drawdown) = &self.max_drawdown {
+            if max_drawdown <= &Decimal::ZERO {
+                return Err(format!("max drawdown {} must be positive", max_drawdown).into());
+            }
+        }
+
+        if let Some(take_profit) = &self.take_profit {
+            if take_profit.is_zero_width() {
+                let low = take_profit.low();
+                return Err(format!("take profit range {} has zero width", low).into());
+            }
+        }
+
+        if let Some(distance) = &self.stop_loss_ratchet {
+            if self.stop_loss.is_none() {
+                return Err("stop loss ratchet requires a stop loss range".into());
+            }
+
+            if distance <= &Decimal::ZERO {
+                return Err(format!("stop loss ratchet {} must be positive", distance).into());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Changes applied to a running grid, a `None` field keeps the current value.
+///
+/// Only settings that can be swapped without touching the levels are hot
+/// reloadable. `copies` and `range` reshape the levels, an update carrying
+/// either of them is rejected as a whole and the grid must be rebuilt.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StrategyConfigUpdate {
+    pub stop_loss: Option<Option<Range>>,
+    pub paused: Option<bool>,
+    pub max_open_positions: Option<Option<usize>>,
+
+    pub copies: Option<usize>,
+    pub range: Option<Range>,
+}
+
+/// What [`Grid::warm_reload`] did with the held levels.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReloadReport {
+    /// Held levels moved onto a new level, as `(old index, new index)`
+    pub placed: Vec<(usize, usize)>,
+
+    /// Held levels no new level was free for, still trading at their original ranges
+    pub unplaced: Vec<LevelSnapshot>,
 }
 
 impl Grid {
+    /// Same as [`Grid::try_new`], panics when the levels cannot be computed.
     pub fn new(
         investment: Amount,
         range: Range,
         copies: usize,
         options: Option<GridOptions>,
     ) -> Self {
-        let limit = Limit::with_positions(Self::split(investment, range, copies));
+        Self::try_new(investment, range, copies, options).expect("invalid grid")
+    }
 
-        Self {
-            limit,
-            options: options.unwrap_or_default(),
+    pub fn try_new(
+        investment: Amount,
+        range: Range,
+        copies: usize,
+        options: Option<GridOptions>,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let options = options.unwrap_or_default();
+        options.validate()?;
+
+        let context = options.decimal_context.unwrap_or(GRID_DECIMAL_CONTEXT);
+        let mut positions = match options.spacing {
+            Spacing::Arithmetic => Self::split(investment, range, copies, &context)?,
+            Spacing::Geometric => Self::split_geometric(investment, range, copies, &context)?,
+        };
+        if let Some(fee) = options.sell_above_cost {
+            positions = positions
+                .into_iter()
+                .map(|e| e.with_sell_above_cost(fee))
+                .collect();
         }
-    }
 
-    fn split(investment: Amount, range: Range, copies: usize) -> Vec<LimitPosition> {
-        let mut result = Vec::with_capacity(copies);
-        let investment = investment / Decimal::from(copies - 1);
-        let interval = (range.high() - range.low()) / Decimal::from(copies);
+        if let Some(ticks) = options.activation_ticks {
+            positions = positions
+                .into_iter()
+                .map(|e| e.with_activation_ticks(ticks))
+                .collect();
+        }
 
-        let investment = investment.trunc_with_scale(6);
-        let interval = interval.trunc_with_scale(6);
+        if options.skip_starting_levels {
+            positions = positions
+                .into_iter()
+                .map(LimitPosition::with_arm_after_exit)
+                .collect();
+        }
 
-        for i in 0..copies - 1 {
-            let buying = range.low() + interval * Decimal::from(i);
-            let selling = range.low() + interval * Decimal::from(i + 2);
-            result.push(LimitPosition::new(
-                investment,
-                Range(buying, buying + (interval / Decimal::TWO)),
-                Range(selling - (interval / Decimal::TWO), range.high().clone()),
-                None,
-            ))
+        let mut limit = Limit::with_positions(positions);
+        if let Some(max_open_positions) = options.max_open_positions {
+            limit = limit.with_max_open_positions(max_open_positions);
         }
 
-        result
+        Ok(Self {
+            limit,
+            options: Mutex::new(options),
+            is_done: AtomicBool::new(false),
+            peak: Mutex::new(None),
+            extras: 0,
+            #[cfg(feature = "client")]
+            updates: None,
+        })
     }
 
-    pub fn predictive_lowest_profit_price(&self) -> Vec<Price> {
-        let positions = self.limit.positions();
-        let mut result = Vec::with_capacity(positions.len() + 1);
+    /// Apply every update sent through `updates` at the start of the next trap.
+    #[cfg(feature = "client")]
+    pub fn with_config_updates(mut self, updates: watch::Receiver<StrategyConfigUpdate>) -> Self {
+        self.updates = Some(Mutex::new(updates));
+        self
+    }
 
-        for i in positions.iter() {
-            let buying_price = i.buying.1 * Decimal::from_f64(0.9999).unwrap();
-            let selling_price = i.selling.0 * Decimal::from_f64(1.0001).unwrap();
-            result.push(buying_price.trunc_with_scale(8));
-            result.push(selling_price.trunc_with_scale(8));
+    pub fn apply_update(
+        &self,
+        update: &StrategyConfigUpdate,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if update.copies.is_some() {
+            return Err("changing copies requires rebuilding the grid".into());
         }
 
-        result
-    }
+        if update.range.is_some() {
+            return Err("changing range requires rebuilding the grid".into());
+        }
 
-    pub fn is_reached_stop_loss(&self, price: &Price) -> bool {
-        if let Some(range) = &self.options.stop_loss {
-            return range.is_within_inclusive(price);
+        if let Some(stop_loss) = &update.stop_loss {
+            let options = GridOptions {
+                stop_loss: stop_loss.clone(),
+                ..GridOptions::default()
+            };
+            options.validate()?;
         }
 
-        false
-    }
+        let mut options = self.options.lock().ignore_poison();
+        if let Some(stop_loss) = &update.stop_loss {
+            options.stop_loss = stop_loss.clone();
+        }
 
-    pub fn is_all_short(&self) -> bool {
-        self.limit.is_all_short()
+        if let Some(max_open_positions) = update.max_open_positions {
+            options.max_open_positions = max_open_positions;
+            self.limit.set_max_open_positions(max_open_positions);
+        }
+
+        match update.paused {
+            Some(true) => self.pause(),
+            Some(false) => self.resume(),
+            None => {}
+        }
+
+        Ok(())
     }
-}
 
-impl Strategy for Grid {
-    async fn trap<P, B, S>(
-        &self,
-        price: &P,
-        buy: &B,
-        sell: &S,
-    ) -> Result<(), Box<dyn Error + Send + Sync>>
-    where
-        P: Fn() -> PinFutureResult<PricePoint>,
-        B: Fn(Price, Amount) -> PinFutureResult<QuantityPoint>,
-        S: Fn(Price, Quantity) -> PinFutureResult<AmountPoint>,
-    {
-        let price_point = price().await?;
-        let price = price_point.value().clone();
+    /// Apply `update` to a running grid without losing any fill.
+    ///
+    /// An update leaving the levels untouched is applied as by [`Grid::apply_update`].
+    /// A new `range` or `copies` rebuilds the levels over the same total investment, every
+    /// held level moves onto the free new level closest to its entry price, counts move along.
+    /// Held levels without a free new level keep trading unchanged after the new levels, their
+    /// investment is left out of the new levels.
+    pub fn warm_reload(
+        self,
+        update: &StrategyConfigUpdate,
+    ) -> Result<(Self, ReloadReport), Box<dyn Error + Send + Sync>> {
+        let settings = StrategyConfigUpdate {
+            copies: None,
+            range: None,
+            ..update.clone()
+        };
+        if update.copies.is_none() && update.range.is_none() {
+            self.apply_update(&settings)?;
+            return Ok((self, ReloadReport::default()));
+        }
 
-        if self.is_reached_stop_loss(&price) {
-            for position in self.limit.positions().iter() {
-                if !position.is_short() {
-                    position.sell(sell, price).await?;
+        let positions = self.limit.positions();
+        let range = match &update.range {
+            Some(range) => range.clone(),
+            None => self.range().ok_or("grid has no levels to reload")?,
+        };
+        let copies = update.copies.unwrap_or(positions.len() - self.extras + 1);
+        let investment: Amount = positions.iter().map(|e| e.investment).sum();
+
+        let options = self.options.lock().ignore_poison().clone();
+        let build =
+            |investment: Amount| -> Result<Vec<LimitPosition>, Box<dyn Error + Send + Sync>> {
+                Ok(
+                    Self::try_new(investment, range.clone(), copies, Some(options.clone()))?
+                        .limit
+                        .into_positions(),
+                )
+            };
+
+        // The ranges of the levels do not depend on the investment, the placement is decided
+        // first so that the unplaced investment can be left out of the levels
+        let levels = build(investment)?;
+        let mut report = ReloadReport::default();
+        let mut is_free = vec![true; levels.len()];
+        let mut placements = Vec::new();
+        let mut unplaced = Vec::new();
+        for (index, position) in positions.iter().enumerate() {
+            let is_held = !position.is_short();
+            let price = match *position.entry_price.lock().ignore_poison() {
+                Some(price) => price,
+                None => position.buying.midpoint(),
+            };
+
+            let nearest = levels
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| !is_held || is_free[*i])
+                .min_by_key(|(_, e)| (e.buying.midpoint() - price).abs());
+
+            match (is_held, nearest) {
+                (true, Some((i, _))) => {
+                    is_free[i] = false;
+                    report.placed.push((index, i));
+                    placements.push((position, i, true));
+                }
+                (true, None) => {
+                    report.unplaced.push(LevelSnapshot::from_position(position));
+                    unplaced.push(position.fork());
                 }
+                (false, Some((i, _))) => placements.push((position, i, false)),
+                (false, None) => {}
             }
-
-            return Ok(());
         }
 
-        let price = &Self::spawn_price(price_point);
+        let levels = match unplaced.is_empty() {
+            true => levels,
+            false => {
+                let held: Amount = unplaced.iter().map(|e| e.investment).sum();
+                build(checked_sub(investment, held)?)?
+            }
+        };
+        for (position, i, is_held) in placements {
+            match is_held {
+                true => levels[i].take_over(position),
+                false => levels[i].add_counts(position),
+            }
+        }
 
-        self.limit.trap(price, buy, sell).await?;
+        let Self {
+            limit,
+            is_done,
+            peak,
+            #[cfg(feature = "client")]
+            updates,
+            ..
+        } = self;
+        let extras = unplaced.len();
+        let grid = Self {
+            limit: limit
+                .with_same_settings(levels)
+                .with_extra_positions(unplaced),
+            options: Mutex::new(options),
+            is_done,
+            peak,
+            extras,
+            #[cfg(feature = "client")]
+            updates,
+        };
+        grid.apply_update(&settings)?;
 
-        Ok(())
+        Ok((grid, report))
     }
-}
 
-#[cfg(test)]
-mod tests_grid {
-    use std::sync::atomic::Ordering;
+    #[cfg(feature = "client")]
+    fn receive_config_update(&self) {
+        let Some(updates) = &self.updates else {
+            return;
+        };
 
-    use super::super::tests_general::*;
-    use super::*;
+        let mut updates = updates.lock().ignore_poison();
+        if !updates.has_changed().unwrap_or(false) {
+            return;
+        }
 
-    impl PartialEq for LimitPosition {
-        fn eq(&self, other: &Self) -> bool {
-            self.investment == other.investment
-                && self.buying == other.buying
-                && self.selling == other.selling
-                && *self.position.lock().ignore_poison() == *other.position.lock().ignore_poison()
+        let update = updates.borrow_and_update().clone();
+        drop(updates);
+
+        if let Err(e) = self.apply_update(&update) {
+            tracing::warn!("reject config update: {}", e);
         }
     }
 
-    #[test]
-    fn test_split_limit_position() {
-        let positions = Grid::split(decimal(100.0), Range(decimal(50.0), decimal(90.0)), 4);
-        let target = vec![
-            LimitPosition::new(
-                decimal(33.333333),
-                Range(decimal(50.0), decimal(55.0)),
-                Range(decimal(65.0), decimal(90.0)),
-                None,
-            ),
-            LimitPosition::new(
-                decimal(33.333333),
-                Range(decimal(60.0), decimal(65.0)),
-                Range(decimal(75.0), decimal(90.0)),
-                None,
-            ),
-            LimitPosition::new(
-                decimal(33.333333),
-                Range(decimal(70.0), decimal(75.0)),
-                Range(decimal(85.0), decimal(90.0)),
-                None,
-            ),
-        ];
-        assert_eq!(positions, target);
+    /// Keep `reserve` out of `balance`, only the investable remainder is
+    /// distributed across the grid levels.
+    pub fn with_reserve(
+        balance: Amount,
+        reserve: Amount,
+        range: Range,
+        copies: usize,
+        options: Option<GridOptions>,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        if reserve.is_sign_negative() {
+            return Err("reserve must not be negative".into());
+        }
 
-        let positions = Grid::split(decimal(100.0), Range(decimal(50.0), decimal(90.0)), 3);
-        let target = vec![
-            LimitPosition::new(
-                decimal(50.0),
-                Range(decimal(50.0), decimal(56.66666650)),
-                Range(decimal(69.99999950), decimal(90.0)),
-                None,
-            ),
-            LimitPosition::new(
-                decimal(50.0),
-                Range(decimal(63.333333), decimal(69.99999950)),
-                Range(decimal(83.33333250), decimal(90.0)),
-                None,
-            ),
-        ];
-        assert_eq!(positions, target);
+        if reserve >= balance {
+            return Err("reserve must be less than balance".into());
+        }
+
+        Self::try_new(balance - reserve, range, copies, options)
     }
 
-    #[test]
-    fn test_predictive_lowest_profit_price() {
-        let grid = Grid::new(
-            decimal(50.0),
-            Range(decimal(30.75), decimal(175.35)),
-            6,
-            None,
-        );
+    /// Build a grid for `spot`, every level must be able to trade at least the minimum notional.
+    pub fn with_spot(
+        spot: &Spot,
+        investment: Amount,
+        range: Range,
+        copies: usize,
+        options: Option<GridOptions>,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let grid = Self::try_new(investment, range, copies, options)?;
+        let minimum = &spot.minimum_transaction_amount;
 
-        let target = vec![
-            decimal(42.795720),
-            decimal(66.906690),
-            decimal(66.893310),
-            decimal(91.009100),
-            decimal(90.990900),
-            decimal(115.11151),
-            decimal(115.08849),
-            decimal(139.21392),
-            decimal(139.18608),
-            decimal(163.31633),
-        ];
+        let smallest = grid.limit.positions().iter().min_by_key(|i| i.investment);
+        if let Some(position) = smallest {
+            if &position.investment < minimum {
+                let levels = checked_div(investment, *minimum)?.floor();
+                let message = match levels.is_zero() {
+                    true => format!(
+                        "investment {} is below minimum notional {} of {}",
+                        investment,
+                        minimum,
+                        spot.symbol()
+                    ),
+                    false => format!(
+                        "per level investment {} of {} copies is below minimum notional {} of {}, use at most {} copies",
+                        position.investment,
+                        copies,
+                        minimum,
+                        spot.symbol(),
+                        levels + Decimal::ONE
+                    ),
+                };
 
-        assert_eq!(grid.predictive_lowest_profit_price(), target);
+                return Err(message.into());
+            }
+        }
+
+        // Truncated to the transaction precision the quantity can trade below the investment,
+        // the most at the top of the buying range
+        for (level, position) in grid.limit.positions().iter().enumerate() {
+            let price = position.buying.high();
+            let quantity = spot.buying_quantity_by_amount(price, &position.investment);
+            if !spot.is_reached_minimum_transaction_limit(price, &quantity) {
+                return Err(format!(
+                    "level {} buys {} at {} below minimum notional {} of {}",
+                    level,
+                    quantity,
+                    price,
+                    minimum,
+                    spot.symbol()
+                )
+                .into());
+            }
+        }
+
+        Ok(grid)
     }
 
-    #[tokio::test]
-    #[traced_test]
-    async fn test_stop_loss() {
-        let trading = simple_trading();
-        let grid: Grid = Grid::new(
-            decimal(50.0),
-            Range(decimal(100.0), decimal(175.35)),
-            4,
-            Some(GridOptions {
-                stop_loss: Some(Range(decimal(80.0), decimal(90.0))),
-            }),
-        );
+    /// Build a grid for `spot` with as many levels as `investment` funds at `level_notional`.
+    ///
+    /// The whole investment is split, so every level invests at least `level_notional`.
+    pub fn with_level_notional(
+        spot: &Spot,
+        investment: Amount,
+        level_notional: Amount,
+        range: Range,
+        options: Option<GridOptions>,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let minimum = &spot.minimum_transaction_amount;
+        if &level_notional < minimum {
+            return Err(format!(
+                "level notional {} is below minimum notional {} of {}",
+                level_notional,
+                minimum,
+                spot.symbol()
+            )
+            .into());
+        }
 
-        assert_eq!(grid.is_reached_stop_loss(&decimal(75.0)), false);
-        assert_eq!(grid.is_reached_stop_loss(&decimal(80.0)), true);
-        assert_eq!(grid.is_reached_stop_loss(&decimal(85.0)), true);
-        assert_eq!(grid.is_reached_stop_loss(&decimal(90.0)), true);
-        assert_eq!(grid.is_reached_stop_loss(&decimal(95.0)), false);
+        let levels = checked_div(investment, level_notional)?.floor();
+        let levels = match levels.to_usize() {
+            Some(levels) if levels > 0 => levels,
+            _ => {
+                return Err(format!(
+                    "investment {} cannot fund a level of notional {}",
+                    investment, level_notional
+                )
+                .into())
+            }
+        };
 
-        let prices = vec![100.0, 110.0, 125.0, 100.0, 95.0, 85.0];
-        let price = simple_prices(prices.clone());
+        Self::with_spot(spot, investment, range, levels + 1, options)
+    }
 
-        for _ in prices.iter() {
-            grid.trap(&price, &trading.buy, &trading.sell)
-                .await
-                .unwrap();
+    fn split(
+        investment: Amount,
+        range: Range,
+        copies: usize,
+        context: &DecimalContext,
+    ) -> Result<Vec<LimitPosition>, ArithError> {
+        // `Range::split` steps within the length, so only the length itself can overflow
+        checked_sub(*range.high(), *range.low())?;
+
+        let mut boundaries: Vec<Price> = range
+            .split(copies)
+            .iter()
+            .map(|e| context.round(*e.low()))
+            .collect();
+        boundaries.push(*range.high());
+
+        Self::split_at(investment, &range, boundaries, context)
+    }
+
+    fn split_geometric(
+        investment: Amount,
+        range: Range,
+        copies: usize,
+        context: &DecimalContext,
+    ) -> Result<Vec<LimitPosition>, Box<dyn Error + Send + Sync>> {
+        if range.low() <= &Decimal::ZERO {
+            return Err(format!(
+                "geometric spacing requires a positive low, not {}",
+                range.low()
+            )
+            .into());
         }
 
-        assert_eq!(trading.buying().count.load(Ordering::Relaxed), 2);
-        assert_eq!(trading.selling().count.load(Ordering::Relaxed), 2);
+        let low = range
+            .low()
+            .to_f64()
+            .ok_or("range low is not representable")?;
+        let high = range
+            .high()
+            .to_f64()
+            .ok_or("range high is not representable")?;
+        let ratio = (high / low).powf(1.0 / copies as f64);
 
-        assert_eq!(
-            trading.buying().prices,
-            vec![decimal(100.0), decimal(125.0)]
+        let mut boundaries = vec![*range.low()];
+        for i in 1..=copies {
+            let boundary = Decimal::from_f64(low * ratio.powi(i as i32))
+                .ok_or("geometric level is not representable")?;
+            boundaries.push(context.round(boundary).min(*range.high()));
+        }
+
+        Ok(Self::split_at(investment, &range, boundaries, context)?)
+    }
+
+    // Level `i` buys in the lower half of `[boundaries[i], boundaries[i + 1])` and sells
+    // from the upper half of the next interval up to the top of the range.
+    //
+    // The even share is truncated at the context scale whatever its rounding, so the
+    // remainder never goes negative. It goes to the highest level, so the levels invest
+    // exactly `investment` while the lowest level keeps the smallest share.
+    fn split_at(
+        investment: Amount,
+        range: &Range,
+        boundaries: Vec<Price>,
+        context: &DecimalContext,
+    ) -> Result<Vec<LimitPosition>, ArithError> {
+        let levels = boundaries.len().saturating_sub(2);
+        let share = Rounding::Truncate.apply(
+            checked_div(investment, Decimal::from(levels))?,
+            context.scale,
         );
-        assert_eq!(trading.selling().prices, vec![decimal(85.0), decimal(85.0)]);
+        let remainder = checked_sub(investment, checked_mul(share, Decimal::from(levels))?)?;
 
-        assert_eq!(grid.is_all_short(), true);
+        let mut result = Vec::with_capacity(levels);
+        for i in 0..levels {
+            let (buying, next, selling) = (boundaries[i], boundaries[i + 1], boundaries[i + 2]);
+            let investment = match i + 1 == levels {
+                true => checked_add(share, remainder)?,
+                false => share,
+            };
+            result.push(
+                LimitPosition::new(
+                    investment,
+                    Range(buying, buying + (next - buying) / Decimal::TWO),
+                    Range(selling - (selling - next) / Decimal::TWO, *range.high()),
+                    None,
+                )
+                .with_interval(Interval::HalfOpen),
+            )
+        }
+
+        Ok(result)
+    }
+
+    pub fn predictive_lowest_profit_price(&self) -> Vec<Price> {
+        let positions = self.limit.positions();
+        let mut result = Vec::with_capacity(positions.len() + 1);
+        let rounding = self.decimal_context().rounding;
+
+        for i in positions.iter() {
+            let buying_price = i.buying.1 * Decimal::from_f64(0.9999).unwrap();
+            let selling_price = i.selling.0 * Decimal::from_f64(1.0001).unwrap();
+            result.push(rounding.apply(buying_price, 8));
+            result.push(rounding.apply(selling_price, 8));
+        }
+
+        result
+    }
+
+    /// Draw the levels top-to-bottom with the current price marked, for terminal inspection.
+    pub fn render_ladder(&self, current_price: &Price) -> String {
+        let mut levels: Vec<(usize, &LimitPosition)> =
+            self.limit.positions().iter().enumerate().collect();
+        levels.sort_by(|a, b| b.1.buying.low().cmp(a.1.buying.low()));
+
+        let marker = format!("--> {}\n", current_price.normalize());
+        let mut is_marked = false;
+        let mut result = String::new();
+
+        for (index, position) in levels.into_iter() {
+            if !is_marked && position.buying.low() <= current_price {
+                result.push_str(&marker);
+                is_marked = true;
+            }
+
+            let state = if position.is_short() {
+                "empty"
+            } else {
+                "holding"
+            };
+            result.push_str(&format!(
+                "[{}] buy {} - {} | sell {} - {} | {}\n",
+                index,
+                position.buying.low().normalize(),
+                position.buying.high().normalize(),
+                position.selling.low().normalize(),
+                position.selling.high().normalize(),
+                state
+            ));
+        }
+
+        if !is_marked {
+            result.push_str(&marker);
+        }
+
+        result
+    }
+
+    /// Raise the stop loss range so that its top stays `stop_loss_ratchet` below `price`.
+    pub fn ratchet_stop_loss(&self, price: &Price) {
+        let mut options = self.options.lock().ignore_poison();
+        let (Some(distance), Some(stop_loss)) = (options.stop_loss_ratchet, &options.stop_loss)
+        else {
+            return;
+        };
+
+        let high = price - distance;
+        if &high > stop_loss.high() {
+            let range = Range(high - stop_loss.length(), high);
+            debug!("raise stop loss to {:?}", range);
+            options.stop_loss = Some(range);
+        }
+    }
+
+    pub fn stop_loss(&self) -> Option<Range> {
+        self.options.lock().ignore_poison().stop_loss.clone()
+    }
+
+    pub fn is_reached_stop_loss(&self, price: &Price) -> bool {
+        if let Some(range) = &self.options.lock().ignore_poison().stop_loss {
+            return range.is_within_inclusive(price);
+        }
+
+        false
+    }
+
+    /// Follow the highest `price` while a level is held, forget it once all levels are short.
+    fn track_peak(&self, price: &Price) {
+        let mut peak = self.peak.lock().ignore_poison();
+        *peak = match self.is_all_short() {
+            true => None,
+            false => Some(peak.map_or(*price, |e| e.max(*price))),
+        };
+    }
+
+    pub fn peak(&self) -> Option<Price> {
+        *self.peak.lock().ignore_poison()
+    }
+
+    pub fn is_reached_trailing_stop(&self, price: &Price) -> bool {
+        let trailing_stop = self.options.lock().ignore_poison().trailing_stop;
+        match (trailing_stop, self.peak()) {
+            (Some(trailing_stop), Some(peak)) => price < &(peak * (Decimal::ONE - trailing_stop)),
+            _ => false,
+        }
+    }
+
+    /// Realized profit of the round trips plus the unrealized profit of the held levels at `price`.
+    pub fn pnl(&self, price: &Price) -> Amount {
+        let realized: Amount = self.round_trips().iter().map(|e| e.profit).sum();
+        let held_cost: Amount = self
+            .positions()
+            .iter()
+            .filter(|e| !e.is_short())
+            .filter_map(LimitPosition::cost)
+            .sum();
+
+        realized + self.limit.unrealized_value(price) - held_cost
+    }
+
+    pub fn is_reached_max_drawdown(&self, price: &Price) -> bool {
+        match self.options.lock().ignore_poison().max_drawdown {
+            Some(max_drawdown) => self.pnl(price) <= -max_drawdown,
+            None => false,
+        }
+    }
+
+    pub fn take_profit(&self) -> Option<Range> {
+        self.options.lock().ignore_poison().take_profit.clone()
+    }
+
+    pub fn is_reached_take_profit(&self, price: &Price) -> bool {
+        if let Some(range) = &self.options.lock().ignore_poison().take_profit {
+            return range.is_within_inclusive(price);
+        }
+
+        false
+    }
+
+    /// The take profit was reached, the grid sold every level and no longer trades.
+    pub fn is_done(&self) -> bool {
+        self.is_done.load(Ordering::SeqCst)
+    }
+
+    pub fn is_all_short(&self) -> bool {
+        self.limit.is_all_short()
+    }
+
+    pub fn pause(&self) {
+        self.limit.pause()
+    }
+
+    pub fn resume(&self) {
+        self.limit.resume()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.limit.is_paused()
+    }
+
+    /// Independent copy for running the same grid against another price scenario.
+    ///
+    /// The fork does not follow the config updates of the original.
+    pub fn fork(&self) -> Self {
+        Self {
+            limit: self.limit.fork(),
+            options: Mutex::new(self.options.lock().ignore_poison().clone()),
+            is_done: AtomicBool::new(self.is_done()),
+            peak: Mutex::new(self.peak()),
+            extras: self.extras,
+            #[cfg(feature = "client")]
+            updates: None,
+        }
+    }
+
+    pub fn with_symbol(mut self, symbol: Symbol) -> Self {
+        self.limit = self.limit.with_symbol(symbol);
+        self
+    }
+
+    pub fn with_trading_window(mut self, trading_window: Vec<(NaiveTime, NaiveTime)>) -> Self {
+        self.limit = self.limit.with_trading_window(trading_window);
+        self
+    }
+
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.limit = self.limit.with_clock(clock);
+        self
+    }
+
+    pub fn with_on_error(mut self, on_error: ErrorHandler) -> Self {
+        self.limit = self.limit.with_on_error(on_error);
+        self
+    }
+
+    pub fn with_decision_log(mut self, decision_log: Arc<DecisionLog>) -> Self {
+        self.limit = self.limit.with_decision_log(decision_log);
+        self
+    }
+
+    pub fn with_events(mut self, events: Sender<StrategyEvent>) -> Self {
+        self.limit = self.limit.with_events(events);
+        self
+    }
+
+    pub fn symbol(&self) -> Option<&Symbol> {
+        self.limit.symbol()
+    }
+
+    /// From the bottom of the lowest level to the top of its selling range, `None` without levels.
+    pub fn range(&self) -> Option<Range> {
+        let first = self.limit.positions().first()?;
+        Some(Range(*first.buying.low(), *first.selling.high()))
+    }
+
+    pub fn positions(&self) -> &Vec<LimitPosition> {
+        self.limit.positions()
+    }
+
+    /// New levels of `investment` over `range`, trading `held` after them.
+    ///
+    /// The settings and state of `self` carry over, e.g. paused, done and the peak. The counts
+    /// and round trips of every empty position move onto the new level closest to it, as by
+    /// [`Grid::warm_reload`]. Like [`Grid::fork`] the new grid does not follow the config
+    /// updates of `self`.
+    pub(crate) fn rebuild(
+        &self,
+        investment: Amount,
+        range: Range,
+        copies: usize,
+        held: Vec<LimitPosition>,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let options = self.options.lock().ignore_poison().clone();
+        let levels = Self::try_new(investment, range, copies, Some(options.clone()))?
+            .limit
+            .into_positions();
+        for position in self.positions().iter().filter(|e| e.is_short()) {
+            let price = position.buying.midpoint();
+            let nearest = levels
+                .iter()
+                .min_by_key(|e| (e.buying.midpoint() - price).abs());
+            if let Some(level) = nearest {
+                level.add_counts(position);
+            }
+        }
+
+        Ok(Self {
+            extras: held.len(),
+            limit: self
+                .limit
+                .with_same_settings(levels)
+                .with_extra_positions(held),
+            options: Mutex::new(options),
+            is_done: AtomicBool::new(self.is_done()),
+            peak: Mutex::new(self.peak()),
+            #[cfg(feature = "client")]
+            updates: None,
+        })
+    }
+
+    pub fn decimal_context(&self) -> DecimalContext {
+        self.options
+            .lock()
+            .ignore_poison()
+            .decimal_context
+            .unwrap_or(GRID_DECIMAL_CONTEXT)
+    }
+
+    pub fn snapshot(&self) -> StrategySnapshot {
+        self.limit.snapshot()
+    }
+
+    pub fn round_trips(&self) -> Vec<RoundTrip> {
+        self.limit.round_trips()
+    }
+
+    /// Mean time between the buy and the sell of a level, a long time means idle capital.
+    pub fn average_holding_time_millis(&self) -> Option<i64> {
+        self.limit.average_holding_time_millis()
+    }
+
+    /// Fraction of the investment held in positions at `price`, the rest waits in buying ranges.
+    ///
+    /// A low utilization while the price ranges may mean the range is off center.
+    /// Exceeds one when the held levels gained more than the idle investment.
+    pub fn capital_utilization(&self, price: &Price) -> Decimal {
+        let investment = self.limit.total_investment();
+        if investment.is_zero() {
+            return Decimal::ZERO;
+        }
+
+        self.limit.unrealized_value(price) / investment
+    }
+}
+
+impl Grid {
+    #[instrument(skip_all, fields(symbol = self.symbol().map(String::as_str)))]
+    pub async fn trap_with_outcome<P, B, S>(
+        &self,
+        price: &P,
+        buy: &B,
+        sell: &S,
+    ) -> Result<TrapOutcome, Box<dyn Error + Send + Sync>>
+    where
+        P: Fn() -> PinFutureResult<PricePoint>,
+        B: Fn(Price, Amount) -> PinFutureResult<QuantityPoint>,
+        S: Fn(Price, Quantity) -> PinFutureResult<AmountPoint>,
+    {
+        #[cfg(feature = "client")]
+        self.receive_config_update();
+
+        if self.is_done() {
+            debug!("take profit reached, grid is done");
+            self.limit.record_skipped(None, SkipReason::TakeProfit);
+            return Ok(TrapOutcome::NoAction);
+        }
+
+        if self.is_paused() {
+            debug!("strategy is paused, skip trap");
+            self.limit.record_skipped(None, SkipReason::Paused);
+            return Ok(TrapOutcome::NoAction);
+        }
+
+        // The exits below protect the capital, they apply outside the trading window too
+        let price_point = price().await?;
+        let price = *price_point.value();
+
+        if self.is_reached_take_profit(&price) {
+            debug!("take profit reached at {}, sell every level", price);
+            let outcome = self.liquidate(sell, price, SkipReason::TakeProfit).await?;
+            self.is_done.store(true, Ordering::SeqCst);
+            return Ok(outcome);
+        }
+
+        self.ratchet_stop_loss(&price);
+        if self.is_reached_stop_loss(&price) {
+            return self.liquidate(sell, price, SkipReason::StopLoss).await;
+        }
+
+        if self.is_reached_max_drawdown(&price) {
+            debug!(
+                "drawdown at {} reached the max drawdown, sell every level",
+                price
+            );
+            return self.liquidate(sell, price, SkipReason::MaxDrawdown).await;
+        }
+
+        self.track_peak(&price);
+        if self.is_reached_trailing_stop(&price) {
+            debug!(
+                "price {} trails the peak by the trailing stop, sell every level",
+                price
+            );
+            return self.liquidate(sell, price, SkipReason::TrailingStop).await;
+        }
+
+        if !self.limit.is_within_trading_window() {
+            debug!("outside trading window, skip trap");
+            self.limit
+                .record_skipped(Some(price), SkipReason::OutsideTradingWindow);
+            return Ok(TrapOutcome::NoAction);
+        }
+
+        let range_high = self.options.lock().ignore_poison().range_high.clone();
+        if self.is_at_range_high(&price) {
+            debug!("price {} at the top of the range, {:?}", price, range_high);
+            return self.sell_at_range_high(sell, price, range_high).await;
+        }
+
+        let price = &Self::spawn_price(price_point);
+
+        self.limit.trap_with_outcome(price, buy, sell).await
+    }
+
+    // Every selling range ends at the top of the grid range
+    fn is_at_range_high(&self, price: &Price) -> bool {
+        let high = self
+            .limit
+            .positions()
+            .iter()
+            .map(|e| *e.selling.high())
+            .max();
+
+        high.as_ref() == Some(price)
+    }
+
+    // Takes the place of the limit trap for the tick, no level is evaluated for buying as
+    // none of their buying ranges reaches the top, a carried over position neither buys
+    async fn sell_at_range_high<S>(
+        &self,
+        sell: &S,
+        price: Price,
+        range_high: RangeHigh,
+    ) -> Result<TrapOutcome, Box<dyn Error + Send + Sync>>
+    where
+        S: Fn(Price, Quantity) -> PinFutureResult<AmountPoint>,
+    {
+        let positions = self.limit.positions();
+        let is_selling = |e: &LimitPosition| {
+            !e.is_short() && e.selling.high() == &price && e.is_above_cost(&price)
+        };
+        let top = positions
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| is_selling(e))
+            .max_by_key(|(_, e)| *e.selling.low())
+            .map(|(level, _)| level);
+
+        let mut outcomes = Vec::new();
+        for (level, position) in positions.iter().enumerate() {
+            let is_sold = match range_high {
+                RangeHigh::Hold => false,
+                RangeHigh::SellTopLevel => top == Some(level),
+                RangeHigh::SellAll => is_selling(position),
+            };
+
+            let decision = match is_sold {
+                false => Decision::Skipped(SkipReason::RangeHigh),
+                true => {
+                    let cost = position.cost();
+                    let outcome = TrapOutcome::Sold(position.sell(sell, price).await?);
+                    self.limit.emit_outcome(level, price, cost, &outcome);
+                    outcomes.push(outcome.clone());
+                    Decision::Traded(outcome)
+                }
+            };
+            self.limit.record_decision(level, Some(price), decision);
+        }
+
+        Ok(TrapOutcome::from_outcomes(outcomes))
+    }
+
+    // Sell every held level at `price`, short levels are skipped for `reason`
+    async fn liquidate<S>(
+        &self,
+        sell: &S,
+        price: Price,
+        reason: SkipReason,
+    ) -> Result<TrapOutcome, Box<dyn Error + Send + Sync>>
+    where
+        S: Fn(Price, Quantity) -> PinFutureResult<AmountPoint>,
+    {
+        let mut outcomes = Vec::new();
+        for (level, position) in self.limit.positions().iter().enumerate() {
+            let decision = match position.is_short() {
+                true => Decision::Skipped(reason.clone()),
+                false => {
+                    let cost = position.cost();
+                    let outcome = TrapOutcome::Sold(position.sell(sell, price).await?);
+                    self.limit.emit_outcome(level, price, cost, &outcome);
+                    outcomes.push(outcome.clone());
+                    Decision::Traded(outcome)
+                }
+            };
+            self.limit.record_decision(level, Some(price), decision);
+        }
+
+        Ok(TrapOutcome::from_outcomes(outcomes))
+    }
+}
+
+impl Strategy for Grid {
+    async fn trap<P, B, S>(
+        &self,
+        price: &P,
+        buy: &B,
+        sell: &S,
+    ) -> Result<(), Box<dyn Error + Send + Sync>>
+    where
+        P: Fn() -> PinFutureResult<PricePoint>,
+        B: Fn(Price, Amount) -> PinFutureResult<QuantityPoint>,
+        S: Fn(Price, Quantity) -> PinFutureResult<AmountPoint>,
+    {
+        self.trap_with_outcome(price, buy, sell).await?;
+
+        Ok(())
+    }
+
+    fn next_actionable_prices(&self) -> Vec<(Price, TradeSide)> {
+        self.limit.next_actionable_prices()
+    }
+
+    fn describe(&self) -> String {
+        let range = match self.range() {
+            Some(range) => format!("{} to {}", range.low(), range.high()),
+            None => String::from("no range"),
+        };
+        let stop_loss = match self.stop_loss() {
+            Some(range) => format!("stop loss {} to {}", range.low(), range.high()),
+            None => String::from("no stop loss"),
+        };
+
+        format!(
+            "grid on {} from {} with {} levels, investment {}, {}, {} open",
+            self.symbol().map_or("an unknown symbol", |e| e.as_str()),
+            range,
+            self.positions().len(),
+            self.limit.total_investment(),
+            stop_loss,
+            self.limit.open_positions(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests_grid {
+    use std::collections::VecDeque;
+    use std::sync::atomic::Ordering;
+
+    use super::super::tests_general::*;
+    use super::*;
+    use crate::tests_general::btc_spot;
+
+    impl PartialEq for LimitPosition {
+        fn eq(&self, other: &Self) -> bool {
+            self.investment == other.investment
+                && self.buying == other.buying
+                && self.selling == other.selling
+                && *self.position.lock().ignore_poison() == *other.position.lock().ignore_poison()
+        }
+    }
+
+    #[test]
+    fn test_split_limit_position() {
+        let positions = Grid::split(
+            decimal(100.0),
+            Range(decimal(50.0), decimal(90.0)),
+            4,
+            &GRID_DECIMAL_CONTEXT,
+        )
+        .unwrap();
+        let target = vec![
+            LimitPosition::new(
+                decimal(33.333333),
+                Range(decimal(50.0), decimal(55.0)),
+                Range(decimal(65.0), decimal(90.0)),
+                None,
+            ),
+            LimitPosition::new(
+                decimal(33.333333),
+                Range(decimal(60.0), decimal(65.0)),
+                Range(decimal(75.0), decimal(90.0)),
+                None,
+            ),
+            // The truncation remainder goes to the highest level
+            LimitPosition::new(
+                decimal(33.333334),
+                Range(decimal(70.0), decimal(75.0)),
+                Range(decimal(85.0), decimal(90.0)),
+                None,
+            ),
+        ];
+        assert_eq!(positions, target);
+        let total: Amount = positions.iter().map(|e| e.investment).sum();
+        assert_eq!(total, decimal(100.0));
+
+        let positions = Grid::split(
+            decimal(100.0),
+            Range(decimal(50.0), decimal(90.0)),
+            3,
+            &GRID_DECIMAL_CONTEXT,
+        )
+        .unwrap();
+        let target = vec![
+            LimitPosition::new(
+                decimal(50.0),
+                Range(decimal(50.0), decimal(56.66666650)),
+                Range(decimal(69.99999950), decimal(90.0)),
+                None,
+            ),
+            LimitPosition::new(
+                decimal(50.0),
+                Range(decimal(63.333333), decimal(69.99999950)),
+                // The last boundary is the top of the range, not three rounded intervals
+                Range(decimal(83.333333), decimal(90.0)),
+                None,
+            ),
+        ];
+        assert_eq!(positions, target);
+    }
+
+    #[test]
+    fn test_split_geometric() {
+        let options = GridOptions {
+            spacing: Spacing::Geometric,
+            ..GridOptions::default()
+        };
+        let grid = Grid::new(decimal(100.0), range(50.0, 90.0), 4, Some(options.clone()));
+        let positions = grid.limit.positions();
+
+        // Boundaries 50, 57.914609, 67.082039, 77.700601 and 90, about 15.8% apart
+        assert_eq!(positions[0].buying, range(50.0, 53.9573045));
+        assert_eq!(positions[0].selling, range(62.498324, 90.0));
+        assert_eq!(positions[1].buying, range(57.914609, 62.498324));
+        assert_eq!(positions[2].buying, range(67.082039, 72.39132));
+        assert_eq!(positions[2].selling, range(83.8503005, 90.0));
+
+        let error = Grid::try_new(decimal(100.0), range(0.0, 90.0), 4, Some(options));
+        assert_eq!(error.is_err(), true);
+    }
+
+    #[test]
+    fn test_try_new() {
+        let range = Range(decimal(50.0), decimal(90.0));
+        let error = Grid::try_new(decimal(100.0), range.clone(), 1, None).unwrap_err();
+        assert_eq!(error.to_string(), "decimal divide by zero");
+
+        let error = Grid::try_new(decimal(100.0), range.clone(), 0, None).unwrap_err();
+        assert_eq!(error.to_string(), "decimal divide by zero");
+
+        let range = Range(Decimal::MIN, Decimal::MAX);
+        let error = Grid::try_new(decimal(100.0), range, 4, None).unwrap_err();
+        assert_eq!(error.to_string(), "decimal overflow");
+    }
+
+    #[test]
+    fn test_decimal_context() {
+        let range = Range(decimal(50.0), decimal(90.0));
+        let grid = Grid::new(decimal(200.0), range.clone(), 4, None);
+        assert_eq!(grid.decimal_context(), GRID_DECIMAL_CONTEXT);
+        assert_eq!(grid.limit.positions()[0].investment, decimal(66.666666));
+
+        let options = GridOptions {
+            decimal_context: Some(DecimalContext::new(6, Rounding::HalfUp)),
+            ..GridOptions::default()
+        };
+        let grid = Grid::new(decimal(200.0), range.clone(), 4, Some(options));
+        let investments: Vec<_> = grid.positions().iter().map(|i| i.investment).collect();
+        assert_eq!(
+            investments,
+            [decimal(66.666666), decimal(66.666666), decimal(66.666668)]
+        );
+        assert_eq!(investments.iter().sum::<Decimal>(), decimal(200.0));
+
+        let options = GridOptions {
+            decimal_context: Some(DecimalContext::new(2, Rounding::HalfUp)),
+            ..GridOptions::default()
+        };
+        let grid = Grid::new(decimal(200.0), range, 4, Some(options));
+        assert_eq!(grid.limit.positions()[0].investment, decimal(66.66));
+        assert_eq!(grid.limit.positions()[2].investment, decimal(66.68));
+        assert_eq!(
+            grid.limit.positions()[0].buying,
+            Range(decimal(50.0), decimal(55.0))
+        );
+    }
+
+    #[test]
+    fn test_with_level_notional() {
+        let spot = btc_spot();
+        let range = Range(decimal(100.0), decimal(200.0));
+
+        // Ten levels of 10, one more copy than levels
+        let grid =
+            Grid::with_level_notional(&spot, decimal(100.0), decimal(10.0), range.clone(), None)
+                .unwrap();
+        assert_eq!(grid.positions().len(), 10);
+        assert_eq!(grid.positions()[0].investment, decimal(10.0));
+
+        // The remainder is spread over the levels
+        let grid =
+            Grid::with_level_notional(&spot, decimal(105.0), decimal(10.0), range.clone(), None)
+                .unwrap();
+        assert_eq!(grid.positions().len(), 10);
+        assert_eq!(grid.positions()[0].investment, decimal(10.5));
+
+        let error =
+            Grid::with_level_notional(&spot, decimal(100.0), decimal(4.0), range.clone(), None)
+                .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "level notional 4 is below minimum notional 5 of BTCUSDT"
+        );
+
+        let error =
+            Grid::with_level_notional(&spot, decimal(8.0), decimal(10.0), range, None).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "investment 8 cannot fund a level of notional 10"
+        );
+    }
+
+    #[test]
+    fn test_with_spot() {
+        let spot = btc_spot();
+        let range = Range(decimal(50.0), decimal(90.0));
+
+        let error = Grid::with_spot(&spot, decimal(50.0), range.clone(), 20, None).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "per level investment 2.631578 of 20 copies is below minimum notional 5 of BTCUSDT, use at most 11 copies"
+        );
+
+        let grid = Grid::with_spot(&spot, decimal(50.0), range.clone(), 10, None).unwrap();
+        assert_eq!(grid.limit.positions()[0].investment, decimal(5.555555));
+
+        // Levels of exactly the minimum, but 0.09649 is all 5 buys at the top of the first
+        let error = Grid::with_spot(&spot, decimal(50.0), range.clone(), 11, None).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "level 0 buys 0.09649 at 51.81818150 below minimum notional 5 of BTCUSDT"
+        );
+
+        let error = Grid::with_spot(&spot, decimal(4.0), range, 2, None).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "investment 4 is below minimum notional 5 of BTCUSDT"
+        );
+    }
+
+    #[test]
+    fn test_zero_width_stop_loss() {
+        let options = GridOptions {
+            stop_loss: Some(Range(decimal(80.0), decimal(80.0))),
+            ..GridOptions::default()
+        };
+        let grid_range = Range(decimal(100.0), decimal(175.35));
+        let error = Grid::try_new(decimal(50.0), grid_range.clone(), 4, Some(options)).unwrap_err();
+        assert_eq!(error.to_string(), "stop loss range 80 has zero width");
+
+        let grid = Grid::new(decimal(50.0), grid_range, 4, None);
+        let update = StrategyConfigUpdate {
+            stop_loss: Some(Some(Range(decimal(80.0), decimal(80.0)))),
+            ..StrategyConfigUpdate::default()
+        };
+        assert!(grid.apply_update(&update).is_err());
+        assert_eq!(grid.is_reached_stop_loss(&decimal(80.0)), false);
+
+        // Generated levels always have width
+        for position in grid.limit.positions().iter() {
+            position.validate().unwrap();
+        }
+
+        let position =
+            LimitPosition::new(decimal(10.0), range(50.0, 50.0), range(60.0, 70.0), None);
+        let error = position.validate().unwrap_err();
+        assert_eq!(error.to_string(), "buying range 50 has zero width");
+    }
+
+    #[test]
+    fn test_predictive_lowest_profit_price() {
+        let grid = Grid::new(
+            decimal(50.0),
+            Range(decimal(30.75), decimal(175.35)),
+            6,
+            None,
+        );
+
+        let target = vec![
+            decimal(42.795720),
+            decimal(66.906690),
+            decimal(66.893310),
+            decimal(91.009100),
+            decimal(90.990900),
+            decimal(115.11151),
+            decimal(115.08849),
+            decimal(139.21392),
+            decimal(139.18608),
+            decimal(163.31633),
+        ];
+
+        assert_eq!(grid.predictive_lowest_profit_price(), target);
+    }
+
+    #[test]
+    fn test_with_reserve() {
+        let grid = Grid::with_reserve(
+            decimal(1000.0),
+            decimal(200.0),
+            Range(decimal(50.0), decimal(90.0)),
+            4,
+            None,
+        )
+        .unwrap();
+
+        let investment: Decimal = grid.limit.positions().iter().map(|e| e.investment).sum();
+        let rounding = decimal(0.000001) * Decimal::from(grid.limit.positions().len());
+        assert!((decimal(800.0) - investment).abs() <= rounding);
+
+        let range = Range(decimal(50.0), decimal(90.0));
+        assert!(
+            Grid::with_reserve(decimal(100.0), decimal(100.0), range.clone(), 4, None).is_err()
+        );
+        assert!(Grid::with_reserve(decimal(100.0), decimal(-1.0), range, 4, None).is_err());
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_fork() {
+        let trading = simple_trading();
+        let grid = Grid::new(decimal(100.0), Range(decimal(50.0), decimal(90.0)), 4, None)
+            .with_symbol(String::from("BTCUSDT"));
+
+        let price = simple_prices(vec![62.0, 52.0]);
+        grid.trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+
+        let fork = grid.fork();
+        assert_eq!(fork.limit.positions(), grid.limit.positions());
+        assert_eq!(fork.symbol(), grid.symbol());
+
+        fork.trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        fork.pause();
+
+        let counts = |grid: &Grid| -> Vec<usize> {
+            let positions = grid.limit.positions();
+            positions.iter().map(|e| e.buying_count()).collect()
+        };
+        assert_eq!(counts(&grid), vec![0, 1, 0]);
+        assert_eq!(counts(&fork), vec![1, 1, 0]);
+        assert_eq!(grid.limit.positions()[0].is_short(), true);
+        assert_eq!(grid.is_paused(), false);
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_render_ladder() {
+        let trading = simple_trading();
+        let grid = Grid::new(decimal(100.0), Range(decimal(50.0), decimal(90.0)), 4, None);
+
+        let price = simple_prices(vec![62.0]);
+        grid.trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+
+        let ladder = grid.render_ladder(&decimal(62.0));
+        let target = "[2] buy 70 - 75 | sell 85 - 90 | empty\n\
+                      --> 62\n\
+                      [1] buy 60 - 65 | sell 75 - 90 | holding\n\
+                      [0] buy 50 - 55 | sell 65 - 90 | empty\n";
+        assert_eq!(ladder, target);
+
+        let ladder = grid.render_ladder(&decimal(40.0));
+        assert!(ladder.ends_with("--> 40\n"));
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_next_actionable_prices() {
+        let grid = Grid::new(decimal(100.0), Range(decimal(50.0), decimal(90.0)), 4, None);
+        let trading = simple_trading();
+        let price = simple_prices(vec![61.0]);
+
+        // Levels buy within [50, 55), [60, 65), [70, 75) and sell from 65, 75, 85
+        let all_buy = vec![
+            (decimal(55.0), TradeSide::Buy),
+            (decimal(65.0), TradeSide::Buy),
+            (decimal(75.0), TradeSide::Buy),
+        ];
+        assert_eq!(grid.next_actionable_prices(), all_buy);
+
+        grid.trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        let partially_filled = vec![
+            (decimal(55.0), TradeSide::Buy),
+            (decimal(75.0), TradeSide::Sell),
+            (decimal(75.0), TradeSide::Buy),
+        ];
+        assert_eq!(grid.next_actionable_prices(), partially_filled);
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_activation_ticks() {
+        let options = GridOptions {
+            activation_ticks: Some(2),
+            ..GridOptions::default()
+        };
+        let grid = Grid::new(
+            decimal(100.0),
+            Range(decimal(50.0), decimal(90.0)),
+            4,
+            Some(options),
+        );
+        let trading = simple_trading();
+        let price = simple_prices(vec![52.0, 57.0, 52.0, 53.0]);
+        let buying_count = || grid.limit.positions()[0].buying_count();
+
+        // A single tick touch followed by a move away does not fill
+        grid.trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert_eq!(buying_count(), 0);
+        grid.trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert_eq!(buying_count(), 0);
+
+        // Two ticks in a row within the buying range do
+        grid.trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert_eq!(buying_count(), 0);
+        grid.trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert_eq!(buying_count(), 1);
+        assert_eq!(trading.buying().prices, vec![decimal(53.0)]);
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_stop_loss() {
+        let trading = simple_trading();
+        let grid: Grid = Grid::new(
+            decimal(50.0),
+            Range(decimal(100.0), decimal(175.35)),
+            4,
+            Some(GridOptions {
+                stop_loss: Some(Range(decimal(80.0), decimal(90.0))),
+                ..GridOptions::default()
+            }),
+        );
+
+        assert_eq!(grid.is_reached_stop_loss(&decimal(75.0)), false);
+        assert_eq!(grid.is_reached_stop_loss(&decimal(80.0)), true);
+        assert_eq!(grid.is_reached_stop_loss(&decimal(85.0)), true);
+        assert_eq!(grid.is_reached_stop_loss(&decimal(90.0)), true);
+        assert_eq!(grid.is_reached_stop_loss(&decimal(95.0)), false);
+
+        let prices = vec![100.0, 110.0, 125.0, 100.0, 95.0, 85.0];
+        let price = simple_prices(prices.clone());
+
+        for _ in prices.iter() {
+            grid.trap(&price, &trading.buy, &trading.sell)
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(trading.buying().count.load(Ordering::Relaxed), 2);
+        assert_eq!(trading.selling().count.load(Ordering::Relaxed), 2);
+
+        assert_eq!(
+            trading.buying().prices,
+            vec![decimal(100.0), decimal(125.0)]
+        );
+        assert_eq!(trading.selling().prices, vec![decimal(85.0), decimal(85.0)]);
+
+        assert_eq!(grid.is_all_short(), true);
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_stop_loss_outside_trading_window() {
+        use chrono::{NaiveTime, TimeZone, Utc};
+
+        use crate::clock::MockClock;
+
+        let time = |h: u32| NaiveTime::from_hms_opt(h, 0, 0).unwrap();
+        let clock = Arc::new(MockClock::new(
+            Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap(),
+        ));
+        let trading = simple_trading();
+        let grid = Grid::new(
+            decimal(50.0),
+            Range(decimal(100.0), decimal(175.35)),
+            4,
+            Some(GridOptions {
+                stop_loss: Some(Range(decimal(80.0), decimal(90.0))),
+                ..GridOptions::default()
+            }),
+        )
+        .with_trading_window(vec![(time(8), time(12))])
+        .with_clock(clock.clone());
+
+        let price = simple_prices(vec![100.0, 100.0, 85.0]);
+        grid.trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert_eq!(trading.buying().count.load(Ordering::Relaxed), 1);
+
+        // Outside the window nothing trades but the stop loss still sells
+        clock.set(Utc.with_ymd_and_hms(2024, 3, 1, 13, 0, 0).unwrap());
+        grid.trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert!(logs_contain("outside trading window, skip trap"));
+        grid.trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert_eq!(trading.selling().prices, vec![decimal(85.0)]);
+        assert_eq!(grid.is_all_short(), true);
+    }
+
+    #[tokio::test]
+    async fn test_range_high() {
+        // Buying ranges [50, 55), [60, 65), [70, 75) and selling up to 90
+        let trap = |range_high: RangeHigh, prices: Vec<f64>| async move {
+            let options = GridOptions {
+                range_high,
+                ..GridOptions::default()
+            };
+            let grid = Grid::new(decimal(100.0), range(50.0, 90.0), 4, Some(options));
+            let trading = simple_trading();
+            let price = simple_prices(prices.clone());
+            for _ in prices.iter() {
+                grid.trap(&price, &trading.buy, &trading.sell)
+                    .await
+                    .unwrap();
+            }
+
+            trading
+        };
+
+        // Exactly at the low only the lowest level buys
+        let trading = trap(RangeHigh::Hold, vec![50.0]).await;
+        assert_eq!(trading.buying().count.load(Ordering::Relaxed), 1);
+
+        // Exactly at the high nothing sells when held
+        let trading = trap(RangeHigh::Hold, vec![50.0, 60.0, 90.0]).await;
+        assert_eq!(trading.buying().count.load(Ordering::Relaxed), 2);
+        assert_eq!(trading.selling().count.load(Ordering::Relaxed), 0);
+
+        // One level per tick, from the top, by default
+        assert_eq!(RangeHigh::default(), RangeHigh::SellTopLevel);
+        let trading = trap(RangeHigh::SellTopLevel, vec![50.0, 60.0, 90.0]).await;
+        assert_eq!(trading.selling().count.load(Ordering::Relaxed), 1);
+        assert_eq!(
+            trading.selling().quantitys,
+            vec![trading.buying().quantitys[1]]
+        );
+
+        let trading = trap(RangeHigh::SellAll, vec![50.0, 60.0, 90.0]).await;
+        assert_eq!(trading.selling().count.load(Ordering::Relaxed), 2);
+        assert_eq!(trading.buying().count.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn test_trailing_stop() {
+        let trading = simple_trading();
+        let options = GridOptions {
+            trailing_stop: Some(decimal(0.1)),
+            ..GridOptions::default()
+        };
+        let grid = Grid::new(decimal(50.0), range(100.0, 400.0), 4, Some(options));
+
+        // Nothing held at 240, the fall to 100 buys instead of stopping
+        let price = simple_prices(vec![240.0, 100.0, 150.0, 170.0, 160.0, 152.0, 152.0]);
+        for _ in 0..2 {
+            grid.trap(&price, &trading.buy, &trading.sell)
+                .await
+                .unwrap();
+        }
+        assert_eq!(trading.buying().prices, vec![decimal(100.0)]);
+        assert_eq!(grid.peak(), None);
+
+        // The peak follows the rise, 160 is still above 170 * 0.9
+        for _ in 0..3 {
+            grid.trap(&price, &trading.buy, &trading.sell)
+                .await
+                .unwrap();
+        }
+        assert_eq!(grid.peak(), Some(decimal(170.0)));
+        assert_eq!(trading.selling().prices.is_empty(), true);
+
+        grid.trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert_eq!(trading.selling().prices, vec![decimal(152.0)]);
+        assert_eq!(grid.is_all_short(), true);
+
+        grid.trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert_eq!(grid.peak(), None);
+
+        let options = GridOptions {
+            trailing_stop: Some(decimal(1.0)),
+            ..GridOptions::default()
+        };
+        assert!(Grid::try_new(decimal(50.0), range(100.0, 400.0), 4, Some(options)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_average_holding_time() {
+        let grid = Grid::new(decimal(100.0), range(100.0, 200.0), 5, None);
+        assert_eq!(grid.average_holding_time_millis(), None);
+
+        // Two cycles of the lowest level, held for one minute then for three
+        let timestamps = Arc::new(Mutex::new(VecDeque::from([
+            1_700_000_000_000,
+            1_700_000_060_000,
+            1_700_000_100_000,
+            1_700_000_280_000,
+        ])));
+        let next = move || timestamps.lock().unwrap().pop_front().unwrap();
+        let buy_next = next.clone();
+        let buy = move |price: Price, amount: Amount| -> PinFutureResult<QuantityPoint> {
+            let point = QuantityPoint::with_timestamp(amount / price, buy_next());
+            Box::pin(async move { Ok(point) })
+        };
+        let sell = move |price: Price, quantity: Quantity| -> PinFutureResult<AmountPoint> {
+            let point = AmountPoint::with_timestamp(price * quantity, next());
+            Box::pin(async move { Ok(point) })
+        };
+        let price = simple_prices(vec![105.0, 135.0, 105.0, 135.0]);
+
+        for _ in 0..4 {
+            grid.trap(&price, &buy, &sell).await.unwrap();
+        }
+
+        assert_eq!(grid.round_trips().len(), 2);
+        assert_eq!(grid.average_holding_time_millis(), Some(120_000));
+    }
+
+    #[tokio::test]
+    async fn test_max_drawdown() {
+        let trading = simple_trading();
+        let options = GridOptions {
+            max_drawdown: Some(decimal(15.0)),
+            ..GridOptions::default()
+        };
+        let grid = Grid::new(decimal(100.0), range(100.0, 200.0), 5, Some(options));
+
+        // Three levels of 25 bought on the way down, 9.51 down at 125
+        let price = simple_prices(vec![165.0, 145.0, 125.0, 105.0, 105.0]);
+        for _ in 0..3 {
+            grid.trap(&price, &trading.buy, &trading.sell)
+                .await
+                .unwrap();
+        }
+        assert_eq!(trading.buying().count.load(Ordering::Relaxed), 3);
+        assert_eq!(grid.is_reached_max_drawdown(&decimal(125.0)), false);
+        assert_eq!(grid.pnl(&decimal(105.0)) < decimal(-15.0), true);
+
+        // 19.99 down at 105, every level sells before the lowest one buys
+        grid.trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert_eq!(trading.selling().prices, vec![decimal(105.0); 3]);
+        assert_eq!(trading.buying().count.load(Ordering::Relaxed), 3);
+        assert_eq!(grid.is_all_short(), true);
+
+        // The realized loss keeps the grid out
+        grid.trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert_eq!(trading.buying().count.load(Ordering::Relaxed), 3);
+
+        let options = GridOptions {
+            max_drawdown: Some(decimal(0.0)),
+            ..GridOptions::default()
+        };
+        assert!(Grid::try_new(decimal(100.0), range(100.0, 200.0), 5, Some(options)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_take_profit() {
+        let trading = simple_trading();
+        let decision_log = Arc::new(DecisionLog::new(16));
+        let grid = Grid::new(
+            decimal(50.0),
+            Range(decimal(100.0), decimal(175.35)),
+            4,
+            Some(GridOptions {
+                stop_loss: Some(range(150.0, 160.0)),
+                take_profit: Some(range(150.0, 170.0)),
+                ..GridOptions::default()
+            }),
+        )
+        .with_decision_log(decision_log.clone());
+        assert_eq!(grid.is_reached_take_profit(&decimal(149.0)), false);
+        assert_eq!(grid.is_reached_take_profit(&decimal(170.0)), true);
+
+        let price = simple_prices(vec![100.0, 125.0, 155.0, 100.0]);
+        for _ in 0..2 {
+            grid.trap(&price, &trading.buy, &trading.sell)
+                .await
+                .unwrap();
+        }
+        assert_eq!(grid.is_done(), false);
+
+        // Within both bands, the take profit wins and ends the grid
+        decision_log.clear();
+        grid.trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert_eq!(
+            trading.selling().prices,
+            vec![decimal(155.0), decimal(155.0)]
+        );
+        assert_eq!(grid.is_all_short(), true);
+        assert_eq!(grid.is_done(), true);
+        assert_eq!(
+            decision_log.entries()[2].decision,
+            Decision::Skipped(SkipReason::TakeProfit)
+        );
+
+        grid.trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert_eq!(trading.buying().prices.len(), 2);
+
+        // Neither band configured
+        let grid = Grid::new(
+            decimal(50.0),
+            Range(decimal(100.0), decimal(175.35)),
+            4,
+            None,
+        );
+        assert_eq!(grid.take_profit(), None);
+        assert_eq!(grid.is_reached_take_profit(&decimal(155.0)), false);
+        assert_eq!(grid.is_reached_stop_loss(&decimal(155.0)), false);
+
+        let trading = simple_trading();
+        let price = simple_prices(vec![100.0, 155.0]);
+        for _ in 0..2 {
+            grid.trap(&price, &trading.buy, &trading.sell)
+                .await
+                .unwrap();
+        }
+        assert_eq!(trading.selling().prices, vec![decimal(155.0)]);
+        assert_eq!(grid.is_done(), false);
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_stop_loss_ratchet() {
+        let grid = Grid::new(
+            decimal(100.0),
+            Range(decimal(100.0), decimal(200.0)),
+            4,
+            Some(GridOptions {
+                stop_loss: Some(Range(decimal(80.0), decimal(90.0))),
+                stop_loss_ratchet: Some(decimal(20.0)),
+                ..GridOptions::default()
+            }),
+        );
+        let trading = simple_trading();
+        let price = simple_prices(vec![105.0, 130.0, 134.0, 120.0, 112.0]);
+
+        // 105 buys the first level, 85 is below the stop loss so it stays
+        grid.trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert_eq!(grid.stop_loss(), Some(Range(decimal(80.0), decimal(90.0))));
+
+        // New highs raise the stop loss, keeping its width
+        grid.trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert_eq!(
+            grid.stop_loss(),
+            Some(Range(decimal(100.0), decimal(110.0)))
+        );
+        grid.trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert_eq!(
+            grid.stop_loss(),
+            Some(Range(decimal(104.0), decimal(114.0)))
+        );
+
+        // A pullback never lowers it
+        grid.trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert_eq!(
+            grid.stop_loss(),
+            Some(Range(decimal(104.0), decimal(114.0)))
+        );
+        assert_eq!(trading.buying().count.load(Ordering::SeqCst), 2);
+
+        // 112 is within the raised stop loss, far above the original one
+        let outcome = grid
+            .trap_with_outcome(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert!(matches!(outcome, TrapOutcome::MultipleActions(ref v) if v.len() == 2));
+        assert_eq!(grid.is_all_short(), true);
+    }
+
+    #[test]
+    fn test_stop_loss_ratchet_validate() {
+        let options = GridOptions {
+            stop_loss_ratchet: Some(decimal(20.0)),
+            ..GridOptions::default()
+        };
+        assert!(options.validate().is_err());
+
+        let options = GridOptions {
+            stop_loss: Some(Range(decimal(80.0), decimal(90.0))),
+            stop_loss_ratchet: Some(decimal(0.0)),
+            ..GridOptions::default()
+        };
+        assert!(options.validate().is_err());
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_trap_with_outcome() {
+        let trading = simple_trading();
+        let grid: Grid = Grid::new(
+            decimal(50.0),
+            Range(decimal(100.0), decimal(175.35)),
+            4,
+            Some(GridOptions {
+                stop_loss: Some(Range(decimal(80.0), decimal(90.0))),
+                ..GridOptions::default()
+            }),
+        );
+
+        let price = simple_prices(vec![100.0, 125.0, 85.0, 85.0]);
+
+        let outcome = grid
+            .trap_with_outcome(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert!(matches!(outcome, TrapOutcome::Bought(_)));
+
+        grid.trap_with_outcome(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+
+        let outcome = grid
+            .trap_with_outcome(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert!(matches!(&outcome, TrapOutcome::MultipleActions(v) if v.len() == 2));
+
+        let outcome = grid
+            .trap_with_outcome(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert_eq!(outcome, TrapOutcome::NoAction);
+    }
+
+    #[cfg(feature = "client")]
+    #[tokio::test]
+    #[traced_test]
+    async fn test_config_updates() {
+        let trading = simple_trading();
+        let (sender, receiver) = watch::channel(StrategyConfigUpdate::default());
+        let grid = Grid::new(
+            decimal(50.0),
+            Range(decimal(100.0), decimal(175.35)),
+            4,
+            None,
+        )
+        .with_config_updates(receiver);
+
+        let price = simple_prices(vec![100.0, 85.0, 85.0]);
+        grid.trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+
+        // No stop loss yet, the held level waits for its selling range
+        grid.trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert_eq!(trading.selling().count.load(Ordering::Relaxed), 0);
+
+        sender
+            .send(StrategyConfigUpdate {
+                stop_loss: Some(Some(Range(decimal(80.0), decimal(90.0)))),
+                ..StrategyConfigUpdate::default()
+            })
+            .unwrap();
+        grid.trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert_eq!(trading.selling().prices, vec![decimal(85.0)]);
+        assert_eq!(grid.is_all_short(), true);
+
+        sender
+            .send(StrategyConfigUpdate {
+                copies: Some(8),
+                paused: Some(true),
+                ..StrategyConfigUpdate::default()
+            })
+            .unwrap();
+        grid.receive_config_update();
+        assert_eq!(grid.is_paused(), false);
+        assert!(logs_contain("changing copies requires rebuilding the grid"));
+
+        grid.apply_update(&StrategyConfigUpdate {
+            paused: Some(true),
+            max_open_positions: Some(Some(1)),
+            ..StrategyConfigUpdate::default()
+        })
+        .unwrap();
+        assert_eq!(grid.is_paused(), true);
+        assert_eq!(grid.limit.is_reached_max_open_positions(), false);
+    }
+
+    #[tokio::test]
+    async fn test_warm_reload() {
+        let trading = simple_trading();
+        let grid = Grid::new(decimal(100.0), range(100.0, 200.0), 5, None);
+
+        // Buys on the levels [160, 170) and [120, 130)
+        let price = simple_prices(vec![165.0, 125.0]);
+        for _ in 0..2 {
+            grid.trap(&price, &trading.buy, &trading.sell)
+                .await
+                .unwrap();
+        }
+        let holding = grid.snapshot().holding;
+        assert_eq!(trading.buying().prices.len(), 2);
+
+        let (grid, report) = grid
+            .warm_reload(&StrategyConfigUpdate {
+                range: Some(range(80.0, 220.0)),
+                copies: Some(8),
+                paused: Some(true),
+                ..StrategyConfigUpdate::default()
+            })
+            .unwrap();
+        assert_eq!(grid.is_paused(), true);
+        assert_eq!(grid.positions().len(), 7);
+        assert_eq!(grid.positions()[0].buying, range(80.0, 88.75));
+
+        // Nearest to the entry prices, the levels [115, 123.75) and [167.5, 176.25)
+        assert_eq!(report.placed, vec![(1, 2), (3, 5)]);
+        assert_eq!(report.unplaced.is_empty(), true);
+        assert_eq!(grid.snapshot().holding, holding);
+        assert_eq!(
+            *grid.positions()[2].entry_price.lock().unwrap(),
+            Some(decimal(125.0))
+        );
+        assert_eq!(
+            *grid.positions()[5].entry_price.lock().unwrap(),
+            Some(decimal(165.0))
+        );
+        let bought: usize = grid.positions().iter().map(|e| e.buying_count()).sum();
+        assert_eq!(bought, 2);
+
+        // A single new level, the other held level keeps its range
+        let (grid, report) = grid
+            .warm_reload(&StrategyConfigUpdate {
+                copies: Some(2),
+                ..StrategyConfigUpdate::default()
+            })
+            .unwrap();
+        assert_eq!(report.placed, vec![(2, 0)]);
+        assert_eq!(report.unplaced.len(), 1);
+        assert_eq!(report.unplaced[0].buying, range(167.5, 176.25));
+        assert_eq!(grid.positions().len(), 2);
+        assert_eq!(grid.snapshot().holding, holding);
+        let investment =
+            |grid: &Grid| -> Amount { grid.positions().iter().map(|e| e.investment).sum() };
+        assert_eq!(investment(&grid), decimal(100.0));
+
+        // The unplaced level is no level of its own, one level is rebuilt next to it
+        let (grid, report) = grid
+            .warm_reload(&StrategyConfigUpdate {
+                range: Some(range(90.0, 210.0)),
+                ..StrategyConfigUpdate::default()
+            })
+            .unwrap();
+        assert_eq!(report.placed, vec![(0, 0)]);
+        assert_eq!(report.unplaced.len(), 1);
+        assert_eq!(grid.positions().len(), 2);
+        assert_eq!(grid.positions()[0].buying.low(), &decimal(90.0));
+        assert_eq!(grid.snapshot().holding, holding);
+        assert_eq!(investment(&grid), decimal(100.0));
+    }
+
+    #[tokio::test]
+    async fn test_capital_utilization() {
+        let trading = simple_trading();
+        let grid = Grid::new(decimal(100.0), range(100.0, 200.0), 5, None);
+        assert_eq!(grid.capital_utilization(&decimal(165.0)), Decimal::ZERO);
+
+        // One of four levels of 25 bought at 165
+        let price = simple_prices(vec![165.0, 125.0]);
+        grid.trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        let one = grid.capital_utilization(&decimal(165.0));
+        assert!(one > decimal(0.24) && one <= decimal(0.25), "{}", one);
+
+        grid.trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        let two = grid.capital_utilization(&decimal(125.0));
+        assert!(two > one, "{} not above {}", two, one);
+    }
+
+    #[tokio::test]
+    async fn test_skip_starting_levels() {
+        let trading = simple_trading();
+        let options = GridOptions {
+            skip_starting_levels: true,
+            ..GridOptions::default()
+        };
+        let grid = Grid::new(decimal(100.0), range(100.0, 200.0), 5, Some(options));
+        let price = simple_prices(vec![125.0, 105.0, 135.0, 125.0]);
+
+        // Started within [120, 130), the level waits
+        grid.trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert_eq!(trading.buying().prices.is_empty(), true);
+
+        // Other levels were armed from the start
+        grid.trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert_eq!(trading.buying().prices, vec![decimal(105.0)]);
+
+        // Left the range and came back
+        for _ in 0..2 {
+            grid.trap(&price, &trading.buy, &trading.sell)
+                .await
+                .unwrap();
+        }
+        assert_eq!(
+            trading.buying().prices,
+            vec![decimal(105.0), decimal(125.0)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_describe() {
+        let trading = simple_trading();
+        let options = GridOptions {
+            stop_loss: Some(range(80.0, 90.0)),
+            ..Default::default()
+        };
+        let grid = Grid::new(decimal(100.0), range(100.0, 200.0), 5, Some(options))
+            .with_symbol(String::from("BTCUSDT"));
+
+        let description = grid.describe();
+        assert_eq!(description.contains("BTCUSDT"), true);
+        assert_eq!(
+            description.contains("from 100 to 200"),
+            true,
+            "{}",
+            description
+        );
+        assert_eq!(description.contains("4 levels"), true, "{}", description);
+        assert_eq!(
+            description.contains("stop loss 80 to 90"),
+            true,
+            "{}",
+            description
+        );
+        assert_eq!(description.contains("0 open"), true, "{}", description);
+
+        let price = simple_prices(vec![165.0]);
+        grid.trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert_eq!(grid.describe().contains("1 open"), true);
     }
 }
@@ -0,0 +1,136 @@
+use std::collections::VecDeque;
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::noun::*;
+
+// ===== Exponential Moving Average =====
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Ema {
+    period: usize,
+    value: Option<Price>,
+}
+
+impl Ema {
+    /// `period` must be greater than zero.
+    pub fn new(period: usize) -> Self {
+        assert!(period > 0, "ema period must be greater than zero");
+
+        Self {
+            period,
+            value: None,
+        }
+    }
+
+    /// The first price seeds the average, every later price is weighted by `2 / (period + 1)`.
+    pub fn update(&mut self, price: impl Into<Price>) {
+        let price = price.into();
+        let alpha = Decimal::TWO / Decimal::from(self.period + 1);
+
+        self.value = match self.value {
+            Some(value) => Some(value + alpha * (price - value)),
+            None => Some(price),
+        };
+    }
+
+    pub fn period(&self) -> usize {
+        self.period
+    }
+
+    /// `None` until the first price.
+    pub fn value(&self) -> Option<Price> {
+        self.value
+    }
+}
+
+// ===== Simple Moving Average =====
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sma {
+    period: usize,
+    window: VecDeque<Price>,
+    sum: Price,
+}
+
+impl Sma {
+    /// `period` must be greater than zero.
+    pub fn new(period: usize) -> Self {
+        assert!(period > 0, "sma period must be greater than zero");
+
+        Self {
+            period,
+            window: VecDeque::with_capacity(period),
+            sum: Decimal::ZERO,
+        }
+    }
+
+    pub fn update(&mut self, price: impl Into<Price>) {
+        let price = price.into();
+        if self.window.len() == self.period {
+            if let Some(oldest) = self.window.pop_front() {
+                self.sum -= oldest;
+            }
+        }
+
+        self.window.push_back(price);
+        self.sum += price;
+    }
+
+    pub fn period(&self) -> usize {
+        self.period
+    }
+
+    /// `None` until `period` prices have been seen.
+    pub fn value(&self) -> Option<Price> {
+        if self.window.len() < self.period {
+            return None;
+        }
+
+        Some(self.sum / Decimal::from(self.period))
+    }
+}
+
+#[cfg(test)]
+mod tests_indicator {
+    use super::super::PricePoint;
+    use super::*;
+
+    #[test]
+    fn test_ema() {
+        let mut ema = Ema::new(3);
+        assert_eq!(ema.value(), None);
+
+        let mut values = Vec::new();
+        for price in 1..=6 {
+            ema.update(Decimal::from(price));
+            values.push(ema.value().unwrap());
+        }
+
+        let target: Vec<Decimal> = ["1", "1.5", "2.25", "3.125", "4.0625", "5.03125"]
+            .iter()
+            .map(|e| e.parse().unwrap())
+            .collect();
+        assert_eq!(values, target);
+    }
+
+    #[test]
+    fn test_sma() {
+        let mut sma = Sma::new(3);
+
+        let mut values = Vec::new();
+        for price in [10, 20, 30, 40, 35, 60] {
+            sma.update(PricePoint::new(Decimal::from(price)));
+            values.push(sma.value());
+        }
+
+        let target = vec![
+            None,
+            None,
+            Some(Decimal::from(20)),
+            Some(Decimal::from(30)),
+            Some(Decimal::from(35)),
+            Some(Decimal::from(45)),
+        ];
+        assert_eq!(values, target);
+    }
+}
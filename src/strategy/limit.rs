@@ -1,21 +1,31 @@
 use std::error::Error;
-use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize};
+use std::sync::{mpsc::Sender, Arc, Mutex};
 
+use chrono::NaiveTime;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
-use tracing::instrument;
+use tracing::{debug, instrument, warn};
 
+use crate::clock::{Clock, SystemClock};
 use crate::extension::LockResultExt;
+use crate::treasurer::Prosperity;
 
+use super::decision::{Decision, DecisionEntry, DecisionLog, SkipReason};
+use super::round_trip::{self, RoundTrip};
+use super::snapshot::StrategySnapshot;
 use super::{
-    Amount, AmountPoint, PinFutureResult, Price, PricePoint, Quantity, QuantityPoint, Range,
-    Strategy,
+    Amount, AmountPoint, Commission, ErrorDirective, ErrorHandler, Interval, PinFutureResult,
+    Price, PricePoint, Quantity, QuantityPoint, Range, Strategy, StrategyEvent, Symbol, TradeSide,
+    TrapOutcome,
 };
 
 pub type Position = Option<Quantity>;
 
+/// Round trips kept by a position, the oldest are dropped first.
+pub const MAX_ROUND_TRIPS: usize = 1000;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LimitPosition {
     pub buying: Range,
@@ -23,8 +33,73 @@ pub struct LimitPosition {
     pub investment: Amount,
     pub position: Mutex<Position>,
 
+    /// How the buying range treats its boundaries, the selling range always includes both
+    /// so that a level selling up to the top of a grid still sells there
+    #[serde(default)]
+    pub interval: Interval,
+
+    /// Average entry price of the held quantity, unknown for an initial position
+    #[serde(default)]
+    pub entry_price: Mutex<Option<Price>>,
+
+    /// Round-trip fee rate, when set the position only sells above its entry price plus this fee
+    #[serde(default)]
+    pub sell_above_cost: Option<Commission>,
+
+    /// Consecutive ticks the price must stay within the buying range before buying
+    #[serde(default)]
+    pub activation_ticks: Option<usize>,
+
+    /// A position starting with the price within its buying range waits for the price to
+    /// leave the range once before buying, instead of buying on the first tick
+    #[serde(default)]
+    pub arm_after_exit: bool,
+
+    /// Fraction of the treasurer available balance to invest, resolved against the balance
+    /// available at the start of the tick. `investment` is used when the `Limit` has no
+    /// treasurer.
+    #[serde(default)]
+    pub investment_fraction: Option<Decimal>,
+
+    /// Amount spent on the held quantity, unknown for an initial position
+    #[serde(default)]
+    pub cost: Mutex<Option<Amount>>,
+
+    /// Milliseconds of the fill of the held quantity, unknown for an initial position
+    #[serde(default)]
+    pub entry_timestamp: Mutex<Option<i64>>,
+
+    /// Part of the investment a partial fill left unbought, topped up on a later tick
+    /// within the buying range
+    #[serde(default)]
+    pub unfilled: Mutex<Option<Amount>>,
+
     buying_count: AtomicUsize,
     selling_count: AtomicUsize,
+
+    /// Sells following a buy of this position, each one a completed round trip
+    #[serde(default)]
+    cycle_count: AtomicUsize,
+
+    /// The latest [`MAX_ROUND_TRIPS`] completed round trips, the oldest first
+    #[serde(default)]
+    round_trips: Mutex<Vec<RoundTrip>>,
+
+    /// Consecutive ticks the price has been within the buying range
+    #[serde(skip)]
+    touches: AtomicUsize,
+
+    /// A buy is awaiting the exchange, set under the position lock
+    #[serde(skip)]
+    is_buying: AtomicBool,
+
+    /// The first tick was seen, only that tick can disarm the position
+    #[serde(skip)]
+    is_started: AtomicBool,
+
+    /// Started within the buying range and the price has not left it yet
+    #[serde(skip)]
+    is_disarmed: AtomicBool,
 }
 
 impl LimitPosition {
@@ -36,9 +111,103 @@ impl LimitPosition {
             selling,
             selling_count: AtomicUsize::default(),
             position: Mutex::new(position),
+            interval: Interval::default(),
+            entry_price: Mutex::new(None),
+            cost: Mutex::new(None),
+            entry_timestamp: Mutex::new(None),
+            cycle_count: AtomicUsize::default(),
+            round_trips: Mutex::new(Vec::new()),
+            unfilled: Mutex::new(None),
+            sell_above_cost: None,
+            activation_ticks: None,
+            arm_after_exit: false,
+            investment_fraction: None,
+            touches: AtomicUsize::default(),
+            is_buying: AtomicBool::default(),
+            is_started: AtomicBool::default(),
+            is_disarmed: AtomicBool::default(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if self.buying.is_zero_width() {
+            return Err(format!("buying range {} has zero width", self.buying.low()).into());
+        }
+
+        if self.selling.is_zero_width() {
+            return Err(format!("selling range {} has zero width", self.selling.low()).into());
+        }
+
+        Ok(())
+    }
+
+    pub fn with_sell_above_cost(mut self, round_trip_fee: Commission) -> Self {
+        self.sell_above_cost = Some(round_trip_fee);
+        self
+    }
+
+    /// Whether selling at `price` does not realize a loss against the entry price.
+    pub fn is_above_cost(&self, price: &Price) -> bool {
+        let fee = match self.sell_above_cost {
+            Some(fee) => fee,
+            None => return true,
+        };
+
+        match *self.entry_price.lock().ignore_poison() {
+            Some(entry_price) => price > &(entry_price * (Decimal::ONE + fee)),
+            None => true,
         }
     }
 
+    pub fn with_investment_fraction(mut self, fraction: Decimal) -> Self {
+        self.investment_fraction = Some(fraction);
+        self
+    }
+
+    /// Only buy once the price stayed within the buying range for `ticks` ticks in a row.
+    pub fn with_activation_ticks(mut self, ticks: usize) -> Self {
+        self.activation_ticks = Some(ticks);
+        self
+    }
+
+    // Counts the current tick, a tick outside the buying range starts over
+    fn touch(&self, is_within: bool) -> usize {
+        match is_within {
+            true => self.touches.fetch_add(1, Ordering::Relaxed) + 1,
+            false => {
+                self.touches.store(0, Ordering::Relaxed);
+                0
+            }
+        }
+    }
+
+    pub fn with_arm_after_exit(mut self) -> Self {
+        self.arm_after_exit = true;
+        self
+    }
+
+    // The first tick disarms a position starting within its buying range, leaving the range arms it
+    fn disarm(&self, is_within: bool) -> bool {
+        if !self.arm_after_exit {
+            return false;
+        }
+
+        if !self.is_started.swap(true, Ordering::SeqCst) && is_within {
+            self.is_disarmed.store(true, Ordering::SeqCst);
+        }
+
+        if !is_within {
+            self.is_disarmed.store(false, Ordering::SeqCst);
+        }
+
+        self.is_disarmed.load(Ordering::SeqCst)
+    }
+
+    pub fn with_interval(mut self, interval: Interval) -> Self {
+        self.interval = interval;
+        self
+    }
+
     pub fn selling_count(&self) -> usize {
         self.selling_count.load(Ordering::Relaxed)
     }
@@ -46,6 +215,45 @@ impl LimitPosition {
     pub fn buying_count(&self) -> usize {
         self.buying_count.load(Ordering::Relaxed)
     }
+
+    pub fn cycle_count(&self) -> usize {
+        self.cycle_count.load(Ordering::Relaxed)
+    }
+
+    pub fn cost(&self) -> Option<Amount> {
+        *self.cost.lock().ignore_poison()
+    }
+
+    pub fn round_trips(&self) -> Vec<RoundTrip> {
+        self.round_trips.lock().ignore_poison().clone()
+    }
+
+    /// Deep copy into fresh locks and counters, trading on the copy never touches `self`.
+    pub fn fork(&self) -> Self {
+        Self {
+            investment: self.investment,
+            buying: self.buying.clone(),
+            buying_count: AtomicUsize::new(self.buying_count()),
+            selling: self.selling.clone(),
+            selling_count: AtomicUsize::new(self.selling_count()),
+            position: Mutex::new(*self.position.lock().ignore_poison()),
+            interval: self.interval.clone(),
+            entry_price: Mutex::new(*self.entry_price.lock().ignore_poison()),
+            cost: Mutex::new(*self.cost.lock().ignore_poison()),
+            entry_timestamp: Mutex::new(*self.entry_timestamp.lock().ignore_poison()),
+            cycle_count: AtomicUsize::new(self.cycle_count()),
+            round_trips: Mutex::new(self.round_trips()),
+            unfilled: Mutex::new(*self.unfilled.lock().ignore_poison()),
+            sell_above_cost: self.sell_above_cost,
+            activation_ticks: self.activation_ticks,
+            arm_after_exit: self.arm_after_exit,
+            investment_fraction: self.investment_fraction,
+            touches: AtomicUsize::new(self.touches.load(Ordering::Relaxed)),
+            is_buying: AtomicBool::default(),
+            is_started: AtomicBool::new(self.is_started.load(Ordering::SeqCst)),
+            is_disarmed: AtomicBool::new(self.is_disarmed.load(Ordering::SeqCst)),
+        }
+    }
 }
 
 // ===== Limit Position Trading =====
@@ -71,31 +279,131 @@ impl LimitPosition {
         }
     }
 
+    pub fn is_buying(&self) -> bool {
+        self.is_buying.load(Ordering::SeqCst)
+    }
+
+    // Claimed until the returned claim is dropped, also when the buy awaiting it is
+    fn claim_buying(&self) -> Result<BuyingClaim<'_>, Box<dyn Error + Send + Sync>> {
+        match self.is_buying.swap(true, Ordering::SeqCst) {
+            true => Err("a buy of the current position is already in flight".into()),
+            false => Ok(BuyingClaim { position: self }),
+        }
+    }
+
+    /// The position is claimed under the lock before awaiting `f` and only recorded
+    /// afterwards, so a concurrent tick can never spend on the same position twice.
     pub(crate) async fn buy<B>(
         &self,
         f: B,
         price: Price,
+        investment: Amount,
     ) -> Result<QuantityPoint, Box<dyn Error + Send + Sync>>
     where
         B: Fn(Price, Amount) -> PinFutureResult<QuantityPoint>,
     {
-        let result = {
+        let claim = {
+            let position = self.position.lock().ignore_poison();
+            if Self::position_quantity(&position).is_some() {
+                return Err("current position is already held".into());
+            }
+
+            self.claim_buying()?
+        };
+
+        let result = f(price, investment).await;
+        let quantity_point = {
             let mut position = self.position.lock().ignore_poison();
+            drop(claim);
+
+            let quantity_point = result?;
+            let unfilled = Self::unfilled_of(&quantity_point);
+            let cost = match quantity_point.spent() {
+                Some(spent) => *spent,
+                None => investment - unfilled.unwrap_or(Decimal::ZERO),
+            };
+            *position = Some(*quantity_point.value());
+            *self.entry_price.lock().ignore_poison() = Some(price);
+            *self.cost.lock().ignore_poison() = Some(cost);
+            *self.entry_timestamp.lock().ignore_poison() = Some(quantity_point.timestamp());
+            *self.unfilled.lock().ignore_poison() = unfilled;
+
+            quantity_point
+        };
 
-            match Self::position_quantity(&*position) {
-                Some(_quantity) => return Err("current position is already held".into()),
-                None => {
-                    let quantity_point = f(price, self.investment).await?;
-                    *position = Some(quantity_point.value().clone());
+        self.fetch_add_buying_count(1);
 
-                    quantity_point
-                }
+        Ok(quantity_point)
+    }
+
+    /// Held, but a partial fill left part of the investment unbought.
+    pub fn is_partially_filled(&self) -> bool {
+        !self.is_short() && self.unfilled.lock().ignore_poison().is_some()
+    }
+
+    /// Buy the part of the investment a partial fill left unbought, the filled quantity
+    /// is added to the held one and the entry price averaged over both.
+    pub(crate) async fn top_up<B>(
+        &self,
+        f: B,
+        price: Price,
+    ) -> Result<QuantityPoint, Box<dyn Error + Send + Sync>>
+    where
+        B: Fn(Price, Amount) -> PinFutureResult<QuantityPoint>,
+    {
+        let (unfilled, claim) = {
+            let position = self.position.lock().ignore_poison();
+            if Self::position_quantity(&position).is_none() {
+                return Err("no position quantity currently held".into());
             }
+
+            let unfilled = match *self.unfilled.lock().ignore_poison() {
+                Some(unfilled) => unfilled,
+                None => return Err("current position is already filled".into()),
+            };
+
+            (unfilled, self.claim_buying()?)
+        };
+
+        let result = f(price, unfilled).await;
+        let quantity_point = {
+            let mut position = self.position.lock().ignore_poison();
+            drop(claim);
+
+            let quantity_point = result?;
+            let held = position.unwrap_or_default();
+            let quantity = held + quantity_point.value();
+            let remaining = Self::unfilled_of(&quantity_point);
+
+            let mut entry_price = self.entry_price.lock().ignore_poison();
+            if let (Some(entry), false) = (*entry_price, quantity.is_zero()) {
+                *entry_price = Some((entry * held + price * quantity_point.value()) / quantity);
+            }
+
+            let mut cost = self.cost.lock().ignore_poison();
+            if let Some(cost) = cost.as_mut() {
+                *cost += match quantity_point.spent() {
+                    Some(spent) => *spent,
+                    None => unfilled - remaining.unwrap_or(Decimal::ZERO),
+                };
+            }
+
+            *position = Some(quantity);
+            *self.unfilled.lock().ignore_poison() = remaining;
+
+            quantity_point
         };
 
         self.fetch_add_buying_count(1);
 
-        Ok(result)
+        Ok(quantity_point)
+    }
+
+    fn unfilled_of(quantity_point: &QuantityPoint) -> Option<Amount> {
+        match quantity_point.unfilled() > &Decimal::ZERO {
+            true => Some(*quantity_point.unfilled()),
+            false => None,
+        }
     }
 
     pub(crate) async fn sell<S>(
@@ -113,7 +421,24 @@ impl LimitPosition {
                 None => return Err("no position quantity currently held".into()),
                 Some(quantity) => {
                     let amount_point = f(price, quantity.clone()).await?;
+                    let entry_price = self.entry_price.lock().ignore_poison().take();
+                    let entry_timestamp = self.entry_timestamp.lock().ignore_poison().take();
+                    if let Some(cost) = self.cost.lock().ignore_poison().take() {
+                        self.cycle_count.fetch_add(1, Ordering::Relaxed);
+
+                        if let (Some(entry_price), Some(entry_timestamp)) =
+                            (entry_price, entry_timestamp)
+                        {
+                            let round_trip = RoundTrip::new(
+                                (entry_timestamp, entry_price, cost),
+                                (amount_point.timestamp(), price, *amount_point.value()),
+                                *quantity,
+                            );
+                            self.record_round_trips([round_trip]);
+                        }
+                    }
                     *position = None;
+                    *self.unfilled.lock().ignore_poison() = None;
 
                     amount_point
                 }
@@ -125,6 +450,34 @@ impl LimitPosition {
         Ok(result)
     }
 
+    /// Hold the inventory of `other` and add its counts to these, `other` is left untouched.
+    pub(crate) fn take_over(&self, other: &LimitPosition) {
+        *self.position.lock().ignore_poison() = *other.position.lock().ignore_poison();
+        *self.entry_price.lock().ignore_poison() = *other.entry_price.lock().ignore_poison();
+        *self.cost.lock().ignore_poison() = other.cost();
+        *self.entry_timestamp.lock().ignore_poison() =
+            *other.entry_timestamp.lock().ignore_poison();
+        *self.unfilled.lock().ignore_poison() = *other.unfilled.lock().ignore_poison();
+        self.add_counts(other);
+    }
+
+    /// Add the counts and round trips of `other` to these.
+    pub(crate) fn add_counts(&self, other: &LimitPosition) {
+        self.fetch_add_buying_count(other.buying_count());
+        self.fetch_add_selling_count(other.selling_count());
+        self.cycle_count
+            .fetch_add(other.cycle_count(), Ordering::Relaxed);
+        self.record_round_trips(other.round_trips());
+    }
+
+    fn record_round_trips(&self, round_trips: impl IntoIterator<Item = RoundTrip>) {
+        let mut kept = self.round_trips.lock().ignore_poison();
+        kept.extend(round_trips);
+
+        let excess = kept.len().saturating_sub(MAX_ROUND_TRIPS);
+        kept.drain(..excess);
+    }
+
     fn fetch_add_buying_count(&self, val: usize) {
         self.buying_count.fetch_add(val, Ordering::Relaxed);
     }
@@ -134,64 +487,838 @@ impl LimitPosition {
     }
 }
 
-impl Strategy for LimitPosition {
+impl LimitPosition {
+    /// Each tick only acts on the current price: selling is checked before buying,
+    /// so a position bought on a previous tick is sold as soon as the price gaps into
+    /// the selling range. A short position whose price gaps straight into the selling
+    /// range is left untouched. A position sold on a tick buys again on the same tick
+    /// when the price is also within its buying range, e.g. with overlapping ranges.
     #[instrument(skip_all)]
-    async fn trap<P, B, S>(
+    pub async fn trap_with_outcome<P, B, S>(
         &self,
         price: &P,
         buy: &B,
         sell: &S,
-    ) -> Result<(), Box<dyn Error + Send + Sync>>
+    ) -> Result<TrapOutcome, Box<dyn Error + Send + Sync>>
     where
         P: Fn() -> PinFutureResult<PricePoint>,
         B: Fn(Price, Amount) -> PinFutureResult<QuantityPoint>,
         S: Fn(Price, Quantity) -> PinFutureResult<AmountPoint>,
     {
-        let price = price().await?.value().clone();
+        match self
+            .trap_with_decision(price, buy, sell, self.investment, &|| Some(()))
+            .await
+            .map_err(|e| e.error)?
+        {
+            Decision::Traded(outcome) => Ok(outcome),
+            _ => Ok(TrapOutcome::NoAction),
+        }
+    }
+
+    /// Same as [`LimitPosition::trap_with_outcome`] buying with `investment`,
+    /// with the reason when nothing traded.
+    ///
+    /// A buy first takes a slot from `reserve`, held until the buy completes, and is
+    /// skipped when none is left.
+    pub(crate) async fn trap_with_decision<P, B, S, R, G>(
+        &self,
+        price: &P,
+        buy: &B,
+        sell: &S,
+        investment: Amount,
+        reserve: &R,
+    ) -> Result<Decision, TradeFailure>
+    where
+        P: Fn() -> PinFutureResult<PricePoint>,
+        B: Fn(Price, Amount) -> PinFutureResult<QuantityPoint>,
+        S: Fn(Price, Quantity) -> PinFutureResult<AmountPoint>,
+        R: Fn() -> Option<G>,
+    {
+        let mut outcomes = Vec::with_capacity(2);
+        let fail = |side, outcomes: Vec<TrapOutcome>| {
+            move |error| TradeFailure {
+                side,
+                completed: TrapOutcome::from_outcomes(outcomes),
+                error,
+            }
+        };
+
+        let side = match self.is_short() {
+            true => TradeSide::Buy,
+            false => TradeSide::Sell,
+        };
+        let price = *price().await.map_err(fail(side, Vec::new()))?.value();
+        let mut reason = None;
 
         if self.selling.is_within_inclusive(&price) {
             if !self.is_short() {
-                self.sell(sell, price).await?;
+                if self.is_above_cost(&price) {
+                    let amount_point = self
+                        .sell(sell, price)
+                        .await
+                        .map_err(fail(TradeSide::Sell, Vec::new()))?;
+                    outcomes.push(TrapOutcome::Sold(amount_point));
+                } else {
+                    debug!("selling price {} below average cost, hold position", price);
+                    reason = Some(SkipReason::BelowCost);
+                }
+            } else {
+                reason = Some(SkipReason::NotHeld);
+            }
+        }
+
+        let is_within = self.buying.is_within(&price, &self.interval);
+        let is_disarmed = self.disarm(is_within);
+        let touches = self.touch(is_within && !is_disarmed);
+        if touches > 0 && self.is_buying() {
+            reason = Some(SkipReason::Buying);
+        } else if is_disarmed && self.is_short() {
+            reason = Some(SkipReason::Disarmed);
+        } else if touches > 0 && self.is_short() {
+            let activation_ticks = self.activation_ticks.unwrap_or(1);
+            if touches >= activation_ticks {
+                match reserve() {
+                    Some(_slot) => {
+                        let quantity_point = match self.buy(buy, price, investment).await {
+                            Ok(quantity_point) => quantity_point,
+                            Err(e) => return Err(fail(TradeSide::Buy, outcomes)(e)),
+                        };
+                        outcomes.push(TrapOutcome::Bought(quantity_point));
+                        self.touch(false);
+                    }
+                    None => reason = Some(SkipReason::MaxOpenPositions),
+                }
+            } else {
+                debug!(
+                    "buying range touched {} of {} ticks, wait",
+                    touches, activation_ticks
+                );
+                reason = Some(SkipReason::Activating {
+                    touches,
+                    required: activation_ticks,
+                });
+            }
+        } else if touches > 0 && self.is_partially_filled() {
+            let quantity_point = match self.top_up(buy, price).await {
+                Ok(quantity_point) => quantity_point,
+                Err(e) => return Err(fail(TradeSide::Buy, outcomes)(e)),
+            };
+            outcomes.push(TrapOutcome::Bought(quantity_point));
+        } else if touches > 0 && outcomes.is_empty() {
+            reason = reason.or(Some(SkipReason::AlreadyHeld));
+        }
+
+        match outcomes.is_empty() {
+            true => Ok(Decision::Skipped(reason.unwrap_or(SkipReason::OutOfRange))),
+            false => Ok(Decision::Traded(TrapOutcome::from_outcomes(outcomes))),
+        }
+    }
+}
+
+impl Strategy for LimitPosition {
+    async fn trap<P, B, S>(
+        &self,
+        price: &P,
+        buy: &B,
+        sell: &S,
+    ) -> Result<(), Box<dyn Error + Send + Sync>>
+    where
+        P: Fn() -> PinFutureResult<PricePoint>,
+        B: Fn(Price, Amount) -> PinFutureResult<QuantityPoint>,
+        S: Fn(Price, Quantity) -> PinFutureResult<AmountPoint>,
+    {
+        self.trap_with_outcome(price, buy, sell).await?;
+
+        Ok(())
+    }
+
+    fn next_actionable_prices(&self) -> Vec<(Price, TradeSide)> {
+        match self.is_short() {
+            true => vec![(*self.buying.high(), TradeSide::Buy)],
+            false => {
+                let mut price = *self.selling.low();
+                if let (Some(fee), Some(entry_price)) = (
+                    self.sell_above_cost,
+                    *self.entry_price.lock().ignore_poison(),
+                ) {
+                    price = price.max(entry_price * (Decimal::ONE + fee));
+                }
+
+                vec![(price, TradeSide::Sell)]
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Limit {
+    positions: Vec<LimitPosition>,
+
+    /// Trading pair label attached to the tracing spans
+    symbol: Option<Symbol>,
+
+    #[serde(default)]
+    paused: AtomicBool,
+
+    /// Maximum number of positions holding inventory at the same time
+    #[serde(default)]
+    max_open_positions: Mutex<Option<usize>>,
+
+    /// Slots taken by buys still awaiting the exchange, counted against the cap
+    #[serde(skip)]
+    pending_open_positions: AtomicUsize,
+
+    /// Decides what happens after a failed trade, aborts the tick when unset
+    #[serde(skip)]
+    on_error: Option<ErrorHandler>,
+
+    #[serde(default)]
+    evaluation_order: EvaluationOrder,
+
+    /// UTC `[start, end)` times of day when trading is allowed, a window may span midnight
+    #[serde(default, with = "trading_window")]
+    trading_window: Option<Vec<(NaiveTime, NaiveTime)>>,
+
+    #[serde(skip, default = "system_clock")]
+    clock: Arc<dyn Clock>,
+
+    /// Balance the investment fractions of the positions are resolved against
+    #[serde(skip)]
+    treasurer: Option<Arc<Prosperity>>,
+
+    /// Opt-in audit trail of every decision of every position
+    #[serde(skip)]
+    decision_log: Option<Arc<DecisionLog>>,
+
+    /// Receives the trades and completed cycles of every position
+    #[serde(skip)]
+    events: Option<Sender<StrategyEvent>>,
+}
+
+fn system_clock() -> Arc<dyn Clock> {
+    Arc::new(SystemClock)
+}
+
+// Times of day as `HH:MM:SS`, chrono is built without its serde support
+mod trading_window {
+    use chrono::NaiveTime;
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    type Windows = Option<Vec<(NaiveTime, NaiveTime)>>;
+
+    pub(super) fn serialize<S>(windows: &Windows, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let windows: Option<Vec<(String, String)>> = windows.as_ref().map(|windows| {
+            windows
+                .iter()
+                .map(|(start, end)| (start.to_string(), end.to_string()))
+                .collect()
+        });
+
+        windows.serialize(serializer)
+    }
+
+    pub(super) fn deserialize<'de, D>(deserializer: D) -> Result<Windows, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let windows: Option<Vec<(String, String)>> = Option::deserialize(deserializer)?;
+        windows
+            .map(|windows| {
+                windows
+                    .iter()
+                    .map(|(start, end)| Ok((parse(start)?, parse(end)?)))
+                    .collect()
+            })
+            .transpose()
+    }
+
+    fn parse<E: de::Error>(value: &str) -> Result<NaiveTime, E> {
+        value
+            .parse()
+            .map_err(|_| E::custom(format!("invalid time of day {}", value)))
+    }
+}
+
+/// Order in which `Limit` traps its positions within a tick.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub enum EvaluationOrder {
+    /// The order the positions were given in
+    #[default]
+    Insertion,
+
+    /// Closest first by the distance between the price and the midpoint of the range
+    /// the position acts on next, buying when short and selling when held.
+    /// Ties keep the insertion order.
+    Proximity,
+}
+
+impl Limit {
+    pub fn with_positions(positions: Vec<LimitPosition>) -> Self {
+        Self {
+            positions,
+            symbol: None,
+            paused: AtomicBool::new(false),
+            max_open_positions: Mutex::new(None),
+            pending_open_positions: AtomicUsize::default(),
+            on_error: None,
+            evaluation_order: EvaluationOrder::default(),
+            trading_window: None,
+            clock: system_clock(),
+            treasurer: None,
+            decision_log: None,
+            events: None,
+        }
+    }
+
+    /// Same as [`Limit::with_positions`], rejects invalid positions and investment
+    /// fractions adding up to more than the whole balance.
+    pub fn try_with_positions(
+        positions: Vec<LimitPosition>,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let mut fractions = Decimal::ZERO;
+        for position in positions.iter() {
+            position.validate()?;
+
+            if let Some(fraction) = position.investment_fraction {
+                if fraction <= Decimal::ZERO {
+                    return Err(format!("investment fraction {} must be positive", fraction).into());
+                }
+
+                fractions += fraction;
             }
         }
 
-        if self.buying.is_within_inclusive(&price) {
-            if self.is_short() {
-                self.buy(buy, price).await?;
-            }
-        }
+        if fractions > Decimal::ONE {
+            return Err(format!("investment fractions add up to {}, above 1", fractions).into());
+        }
+
+        Ok(Self::with_positions(positions))
+    }
+
+    pub fn with_treasurer(mut self, treasurer: Arc<Prosperity>) -> Self {
+        self.treasurer = Some(treasurer);
+        self
+    }
+
+    pub fn with_decision_log(mut self, decision_log: Arc<DecisionLog>) -> Self {
+        self.decision_log = Some(decision_log);
+        self
+    }
+
+    pub fn with_events(mut self, events: Sender<StrategyEvent>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    pub fn decision_log(&self) -> Option<&Arc<DecisionLog>> {
+        self.decision_log.as_ref()
+    }
+
+    /// Record `decision` for the position at `level`, a no-op without a decision log.
+    pub(crate) fn record_decision(&self, level: usize, price: Option<Price>, decision: Decision) {
+        if let Some(decision_log) = &self.decision_log {
+            decision_log.record(DecisionEntry {
+                timestamp: self.clock.now().timestamp_millis(),
+                price,
+                level,
+                decision,
+            });
+        }
+    }
+
+    /// Send the events of `outcome` of the position at `level`, `cost` is what the
+    /// position held before trading.
+    pub(crate) fn emit_outcome(
+        &self,
+        level: usize,
+        price: Price,
+        cost: Option<Amount>,
+        outcome: &TrapOutcome,
+    ) {
+        let events = match &self.events {
+            Some(events) => events,
+            None => return,
+        };
+
+        let send = |event: StrategyEvent| {
+            if events.send(event).is_err() {
+                debug!("strategy event receiver dropped");
+            }
+        };
+
+        match outcome {
+            TrapOutcome::NoAction => {}
+            TrapOutcome::Bought(quantity) => send(StrategyEvent::Bought {
+                level,
+                price,
+                quantity: *quantity.value(),
+            }),
+            TrapOutcome::Sold(amount) => {
+                send(StrategyEvent::Sold {
+                    level,
+                    price,
+                    amount: *amount.value(),
+                });
+                if let Some(cost) = cost {
+                    send(StrategyEvent::CycleCompleted {
+                        level,
+                        profit: amount.value() - cost,
+                    });
+                }
+            }
+            TrapOutcome::MultipleActions(outcomes) => {
+                for outcome in outcomes.iter() {
+                    self.emit_outcome(level, price, cost, outcome);
+                }
+            }
+        }
+    }
+
+    /// Record the same skip for every position.
+    pub(crate) fn record_skipped(&self, price: Option<Price>, reason: SkipReason) {
+        if self.decision_log.is_some() {
+            for level in 0..self.positions.len() {
+                self.record_decision(level, price, Decision::Skipped(reason.clone()));
+            }
+        }
+    }
+
+    /// Only trade within the given UTC times of day, every other tick is a no-op.
+    pub fn with_trading_window(mut self, trading_window: Vec<(NaiveTime, NaiveTime)>) -> Self {
+        self.trading_window = Some(trading_window);
+        self
+    }
+
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn is_within_trading_window(&self) -> bool {
+        let windows = match &self.trading_window {
+            Some(windows) => windows,
+            None => return true,
+        };
+
+        let now = self.clock.now().time();
+        windows.iter().any(|(start, end)| match start <= end {
+            true => start <= &now && &now < end,
+            false => start <= &now || &now < end,
+        })
+    }
+
+    pub fn with_evaluation_order(mut self, evaluation_order: EvaluationOrder) -> Self {
+        self.evaluation_order = evaluation_order;
+        self
+    }
+
+    pub fn with_on_error(mut self, on_error: ErrorHandler) -> Self {
+        self.on_error = Some(on_error);
+        self
+    }
+
+    pub fn with_max_open_positions(self, max_open_positions: usize) -> Self {
+        self.set_max_open_positions(Some(max_open_positions));
+        self
+    }
+
+    /// Change the cap while running, `None` removes it.
+    pub fn set_max_open_positions(&self, max_open_positions: Option<usize>) {
+        *self.max_open_positions.lock().ignore_poison() = max_open_positions;
+    }
+
+    pub fn open_positions(&self) -> usize {
+        self.positions.iter().filter(|e| !e.is_short()).count()
+    }
+
+    /// Take a slot under the cap for a position about to buy, `None` when the cap is reached.
+    ///
+    /// Checked and taken under the cap lock, the slot is held until the guard is dropped
+    /// so that buys awaiting the exchange at the same time never exceed the cap together.
+    fn reserve_open_position(&self) -> Option<OpenPositionSlot<'_>> {
+        let max_open_positions = self.max_open_positions.lock().ignore_poison();
+        if let Some(max) = *max_open_positions {
+            let pending = self.pending_open_positions.load(Ordering::SeqCst);
+            if self.open_positions() + pending >= max {
+                return None;
+            }
+        }
+
+        self.pending_open_positions.fetch_add(1, Ordering::SeqCst);
+
+        Some(OpenPositionSlot { limit: self })
+    }
+
+    pub fn is_reached_max_open_positions(&self) -> bool {
+        match *self.max_open_positions.lock().ignore_poison() {
+            Some(max) => self.open_positions() >= max,
+            None => false,
+        }
+    }
+
+    /// Make `trap` a no-op until resumed, held positions are untouched.
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst)
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    pub fn with_symbol(mut self, symbol: Symbol) -> Self {
+        self.symbol = Some(symbol);
+        self
+    }
+
+    pub fn symbol(&self) -> Option<&Symbol> {
+        self.symbol.as_ref()
+    }
+
+    pub fn positions(&self) -> &Vec<LimitPosition> {
+        &self.positions
+    }
+
+    pub(crate) fn into_positions(self) -> Vec<LimitPosition> {
+        self.positions
+    }
+
+    pub(crate) fn with_extra_positions(mut self, positions: Vec<LimitPosition>) -> Self {
+        self.positions.extend(positions);
+        self
+    }
+
+    pub fn snapshot(&self) -> StrategySnapshot {
+        StrategySnapshot::from_limit(self)
+    }
+
+    /// Round trips of every position, in the order they were sold.
+    pub fn round_trips(&self) -> Vec<RoundTrip> {
+        let mut round_trips: Vec<RoundTrip> = self
+            .positions
+            .iter()
+            .flat_map(LimitPosition::round_trips)
+            .collect();
+        round_trips.sort_by_key(|e| e.sell_timestamp);
+
+        round_trips
+    }
+
+    /// Mean time a position was held over the round trips, `None` before the first sell.
+    pub fn average_holding_time_millis(&self) -> Option<i64> {
+        round_trip::average_holding_time_millis(&self.round_trips())
+    }
+
+    pub fn total_investment(&self) -> Amount {
+        self.positions.iter().map(|e| e.investment).sum()
+    }
+
+    /// Market value at `price` of the quantity held across all positions.
+    pub fn unrealized_value(&self, price: &Price) -> Amount {
+        let holding: Quantity = self
+            .positions
+            .iter()
+            .filter_map(|e| *e.position.lock().ignore_poison())
+            .sum();
+
+        holding * price
+    }
+
+    /// Deep copy of the configuration and current positions, sharing no state with `self`.
+    pub fn fork(&self) -> Self {
+        self.with_same_settings(self.positions.iter().map(LimitPosition::fork).collect())
+    }
+
+    /// Trade `positions` with the settings of `self`, sharing its treasurer, log and events.
+    pub(crate) fn with_same_settings(&self, positions: Vec<LimitPosition>) -> Self {
+        Self {
+            positions,
+            symbol: self.symbol.clone(),
+            paused: AtomicBool::new(self.is_paused()),
+            max_open_positions: Mutex::new(*self.max_open_positions.lock().ignore_poison()),
+            pending_open_positions: AtomicUsize::default(),
+            on_error: self.on_error.clone(),
+            evaluation_order: self.evaluation_order,
+            trading_window: self.trading_window.clone(),
+            clock: self.clock.clone(),
+            treasurer: self.treasurer.clone(),
+            decision_log: self.decision_log.clone(),
+            events: self.events.clone(),
+        }
+    }
+
+    /// Positions in the order they are trapped at `price`.
+    pub fn positions_in_evaluation_order(&self, price: &Price) -> Vec<&LimitPosition> {
+        let mut positions: Vec<&LimitPosition> = self.positions.iter().collect();
+        if self.evaluation_order == EvaluationOrder::Proximity {
+            positions.sort_by_cached_key(|e| {
+                let range = match e.is_short() {
+                    true => &e.buying,
+                    false => &e.selling,
+                };
+
+                (range.midpoint() - price).abs()
+            });
+        }
+
+        positions
+    }
+
+    /// Guard against ranges that trade twice on one price, to call before trapping.
+    ///
+    /// Reports every two positions whose buying ranges overlap and every position whose
+    /// buying range overlaps its own selling range, bounds included.
+    pub fn validate(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        for position in self.positions.iter() {
+            position.validate()?;
+        }
+
+        let mut overlaps = Vec::new();
+        for (i, position) in self.positions.iter().enumerate() {
+            if position.buying.overlaps(&position.selling) {
+                overlaps.push(format!(
+                    "position {} buying range {:?} overlaps its selling range {:?}",
+                    i, position.buying, position.selling
+                ));
+            }
+
+            for (j, other) in self.positions.iter().enumerate().skip(i + 1) {
+                if position.buying.overlaps(&other.buying) {
+                    overlaps.push(format!(
+                        "buying ranges of positions {} and {} overlap",
+                        i, j
+                    ));
+                }
+            }
+        }
+
+        match overlaps.is_empty() {
+            true => Ok(()),
+            false => Err(overlaps.join(", ").into()),
+        }
+    }
+
+    /// Parts of `over` no buying range covers, where the price can sit without any level acting.
+    pub fn coverage_gaps(&self, over: &Range) -> Vec<Range> {
+        let mut covered: Vec<Range> = self
+            .positions
+            .iter()
+            .filter_map(|e| e.buying.intersection(over))
+            .collect();
+        covered.sort_by(|a, b| a.low().cmp(b.low()));
+
+        let mut gaps = Vec::new();
+        let mut cursor = *over.low();
+        for range in covered.iter() {
+            if range.low() > &cursor {
+                gaps.push(Range(cursor, *range.low()));
+            }
+
+            cursor = cursor.max(*range.high());
+        }
+
+        if &cursor < over.high() {
+            gaps.push(Range(cursor, *over.high()));
+        }
+
+        gaps
+    }
+
+    pub fn is_all_short(&self) -> bool {
+        for position in self.positions.iter() {
+            if !position.is_short() {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+impl Limit {
+    #[instrument(skip_all, fields(symbol = self.symbol.as_deref()))]
+    pub async fn trap_with_outcome<P, B, S>(
+        &self,
+        price: &P,
+        buy: &B,
+        sell: &S,
+    ) -> Result<TrapOutcome, Box<dyn Error + Send + Sync>>
+    where
+        P: Fn() -> PinFutureResult<PricePoint>,
+        B: Fn(Price, Amount) -> PinFutureResult<QuantityPoint>,
+        S: Fn(Price, Quantity) -> PinFutureResult<AmountPoint>,
+    {
+        if self.is_paused() {
+            debug!("strategy is paused, skip trap");
+            self.record_skipped(None, SkipReason::Paused);
+            return Ok(TrapOutcome::NoAction);
+        }
+
+        if !self.is_within_trading_window() {
+            debug!("outside trading window, skip trap");
+            self.record_skipped(None, SkipReason::OutsideTradingWindow);
+            return Ok(TrapOutcome::NoAction);
+        }
+
+        let price_point = price().await?;
+        let current_price = *price_point.value();
+        let price = Self::spawn_price(price_point);
+        let allocation_base = self.allocation_base().await;
+        let mut outcomes = Vec::new();
+
+        for position in self.positions_in_evaluation_order(&current_price) {
+            let level = self.level_of(position);
+            let record = |decision| self.record_decision(level, Some(current_price), decision);
+
+            let investment =
+                Self::resolve_investment(position, allocation_base).unwrap_or(position.investment);
+
+            let mut retries = 0;
+            loop {
+                let cost = position.cost();
+                let TradeFailure {
+                    side,
+                    completed,
+                    error,
+                } = match position
+                    .trap_with_decision(&price, buy, sell, investment, &|| {
+                        self.reserve_open_position()
+                    })
+                    .await
+                {
+                    Ok(decision) => {
+                        if let Decision::Traded(outcome) = &decision {
+                            self.emit_outcome(level, current_price, cost, outcome);
+                            outcomes.push(outcome.clone());
+                        }
+                        record(decision);
+                        break;
+                    }
+                    Err(failure) => failure,
+                };
+
+                // A sell completed before the failed buy of the same tick is kept
+                if completed != TrapOutcome::NoAction {
+                    self.emit_outcome(level, current_price, cost, &completed);
+                    outcomes.push(completed.clone());
+                    record(Decision::Traded(completed));
+                }
+
+                let (directive, delay) = match &self.on_error {
+                    Some(on_error) => (
+                        on_error.handle(side, &current_price, error.as_ref()),
+                        on_error.retry_delay(retries),
+                    ),
+                    None => (ErrorDirective::Abort, None),
+                };
+
+                match (directive, delay) {
+                    (ErrorDirective::Abort, _) => {
+                        record(Decision::Failed(error.to_string()));
+                        return Err(error);
+                    }
+                    (ErrorDirective::Skip, _) => {
+                        warn!("skip {:?} at {}: {}", side, current_price, error);
+                        record(Decision::Failed(error.to_string()));
+                        break;
+                    }
+                    (ErrorDirective::Retry, None) => {
+                        warn!(
+                            "skip {:?} at {} after {} retries: {}",
+                            side, current_price, retries, error
+                        );
+                        record(Decision::Failed(error.to_string()));
+                        break;
+                    }
+                    (ErrorDirective::Retry, Some(delay)) => {
+                        debug!("retry {:?} at {} in {:?}", side, current_price, delay);
+                        retries += 1;
+
+                        // Only reached with the client feature, see `ErrorHandler::retry_delay`
+                        #[cfg(feature = "client")]
+                        tokio::time::sleep(delay).await;
+                    }
+                }
+            }
+        }
+
+        Ok(TrapOutcome::from_outcomes(outcomes))
+    }
+}
+
+/// A trade of a position that failed, with what the same tick traded before it.
+pub(crate) struct TradeFailure {
+    side: TradeSide,
+
+    /// e.g. the sell of a position whose buy then failed on the same tick
+    completed: TrapOutcome,
+    error: Box<dyn Error + Send + Sync>,
+}
 
-        Ok(())
+/// The buy in flight of a position, released on drop.
+struct BuyingClaim<'a> {
+    position: &'a LimitPosition,
+}
+
+impl Drop for BuyingClaim<'_> {
+    fn drop(&mut self) {
+        self.position.is_buying.store(false, Ordering::SeqCst);
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Limit {
-    positions: Vec<LimitPosition>,
+/// A slot under the open positions cap, given back on drop.
+struct OpenPositionSlot<'a> {
+    limit: &'a Limit,
 }
 
-impl Limit {
-    pub fn with_positions(positions: Vec<LimitPosition>) -> Self {
-        Self { positions }
+impl Drop for OpenPositionSlot<'_> {
+    fn drop(&mut self) {
+        self.limit
+            .pending_open_positions
+            .fetch_sub(1, Ordering::SeqCst);
     }
+}
 
-    pub fn positions(&self) -> &Vec<LimitPosition> {
-        &self.positions
+impl Limit {
+    fn level_of(&self, position: &LimitPosition) -> usize {
+        self.positions
+            .iter()
+            .position(|e| std::ptr::eq(e, position))
+            .unwrap_or_default()
     }
 
-    pub fn is_all_short(&self) -> bool {
-        for position in self.positions.iter() {
-            if !position.is_short() {
-                return false;
+    // Read once per tick, the buys of a tick must not shrink the share of the positions
+    // evaluated after them
+    async fn allocation_base(&self) -> Option<Amount> {
+        match &self.treasurer {
+            Some(treasurer)
+                if self
+                    .positions
+                    .iter()
+                    .any(|e| e.investment_fraction.is_some()) =>
+            {
+                Some(treasurer.available().await)
             }
+            _ => None,
         }
+    }
 
-        true
+    // Only a short position buys, a held one never needs the balance
+    fn resolve_investment(position: &LimitPosition, base: Option<Amount>) -> Option<Amount> {
+        match (position.investment_fraction, base) {
+            (Some(fraction), Some(base)) if position.is_short() => Some(fraction * base),
+            _ => None,
+        }
     }
 }
 
 impl Strategy for Limit {
-    #[instrument(skip_all)]
     async fn trap<P, B, S>(
         &self,
         price: &P,
@@ -203,18 +1330,38 @@ impl Strategy for Limit {
         B: Fn(Price, Amount) -> PinFutureResult<QuantityPoint>,
         S: Fn(Price, Quantity) -> PinFutureResult<AmountPoint>,
     {
-        let price = Self::spawn_price(price().await?);
-
-        for position in self.positions.iter() {
-            position.trap(&price, buy, sell).await?;
-        }
+        self.trap_with_outcome(price, buy, sell).await?;
 
         Ok(())
     }
+
+    fn next_actionable_prices(&self) -> Vec<(Price, TradeSide)> {
+        self.positions
+            .iter()
+            .flat_map(LimitPosition::next_actionable_prices)
+            .collect()
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "limit on {} with {} positions, investment {}, {} open",
+            self.symbol().map_or("an unknown symbol", |e| e.as_str()),
+            self.positions.len(),
+            self.total_investment(),
+            self.open_positions(),
+        )
+    }
 }
 
 #[cfg(test)]
 mod tests_limit_trap {
+    use std::{sync::Arc, time::Duration};
+
+    use chrono::{NaiveDate, TimeZone, Utc};
+
+    use crate::clock::MockClock;
+
+    use super::super::round_trip;
     use super::super::tests_general::*;
     use super::*;
 
@@ -405,6 +1552,895 @@ mod tests_limit_trap {
         }
     }
 
+    #[tokio::test]
+    #[traced_test]
+    async fn test_trap_gap_up() {
+        let trading = simple_trading();
+        let limit = single_none_position_limit();
+
+        let prices = vec![250.0, 50.0, 250.0];
+        let price = simple_prices(prices.clone());
+
+        // Gap straight into the selling range while short, nothing happens
+        limit
+            .trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert_eq!(trading.buying().count.load(Ordering::SeqCst), 0);
+        assert_eq!(trading.selling().count.load(Ordering::SeqCst), 0);
+
+        // Bought in the buying range, then a single tick gaps into the selling range
+        for _ in 1..prices.len() {
+            limit
+                .trap(&price, &trading.buy, &trading.sell)
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(trading.buying().prices, vec![decimal(50.0)]);
+        assert_eq!(trading.selling().prices, vec![decimal(250.0)]);
+        assert_eq!(limit.positions[0].buying_count(), 1);
+        assert_eq!(limit.positions[0].selling_count(), 1);
+        assert_eq!(limit.is_all_short(), true);
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_trap_symbol() {
+        let trading = simple_trading();
+        let limit = single_none_position_limit().with_symbol(String::from("BTCUSDT"));
+        assert_eq!(limit.symbol(), Some(&String::from("BTCUSDT")));
+
+        let price = simple_prices(vec![50.0]);
+        limit
+            .trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+
+        assert!(logs_contain("symbol=\"BTCUSDT\""));
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_trap_shared_boundary() {
+        let trading = simple_trading();
+        let limit = Limit::with_positions(vec![
+            LimitPosition::new(decimal(10.0), range(50.0, 60.0), range(100.0, 110.0), None)
+                .with_interval(Interval::HalfOpen),
+            LimitPosition::new(decimal(20.0), range(60.0, 70.0), range(110.0, 120.0), None)
+                .with_interval(Interval::HalfOpen),
+        ]);
+
+        let price = simple_prices(vec![60.0, 120.0]);
+        limit
+            .trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+
+        assert_eq!(trading.buying().amounts, vec![decimal(20.0)]);
+        assert_eq!(limit.positions[0].buying_count(), 0);
+        assert_eq!(limit.positions[1].buying_count(), 1);
+
+        // Only the buying range is half-open, the top of the selling range still sells
+        limit
+            .trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert_eq!(trading.selling().prices, vec![decimal(120.0)]);
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_trap_paused() {
+        let trading = simple_trading();
+        let limit = single_some_position_limit();
+
+        limit.pause();
+        assert_eq!(limit.is_paused(), true);
+
+        let price = simple_prices(vec![210.0, 90.5]);
+        for _ in 0..3 {
+            limit
+                .trap(&price, &trading.buy, &trading.sell)
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(trading.selling().count.load(Ordering::SeqCst), 0);
+        assert_eq!(trading.buying().count.load(Ordering::SeqCst), 0);
+        assert_eq!(limit.is_all_short(), false);
+        assert!(logs_contain("strategy is paused, skip trap"));
+
+        limit.resume();
+        for _ in 0..2 {
+            limit
+                .trap(&price, &trading.buy, &trading.sell)
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(trading.selling().prices, vec![decimal(210.0)]);
+        assert_eq!(trading.buying().prices, vec![decimal(90.5)]);
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_trap_max_open_positions() {
+        let trading = simple_trading();
+        let positions = (0..5)
+            .map(|i| {
+                let low = 90.0 - 10.0 * i as f64;
+                LimitPosition::new(
+                    decimal(10.0),
+                    range(low, low + 9.0),
+                    range(200.0, 300.0),
+                    None,
+                )
+            })
+            .collect();
+        let limit = Limit::with_positions(positions).with_max_open_positions(3);
+
+        let prices = vec![95.0, 85.0, 75.0, 65.0, 55.0, 250.0, 55.0];
+        let price = simple_prices(prices.clone());
+        for _ in 0..5 {
+            limit
+                .trap(&price, &trading.buy, &trading.sell)
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(
+            trading.buying().prices,
+            vec![decimal(95.0), decimal(85.0), decimal(75.0)]
+        );
+        assert_eq!(limit.open_positions(), 3);
+        assert_eq!(limit.is_reached_max_open_positions(), true);
+
+        // Selling is still allowed and frees the slots
+        for _ in 5..prices.len() {
+            limit
+                .trap(&price, &trading.buy, &trading.sell)
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(trading.selling().count.load(Ordering::SeqCst), 3);
+        assert_eq!(trading.buying().count.load(Ordering::SeqCst), 4);
+        assert_eq!(limit.open_positions(), 1);
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_trap_max_open_positions_concurrent() {
+        let positions = (0..3)
+            .map(|_| {
+                LimitPosition::new(decimal(10.0), range(90.0, 110.0), range(200.0, 300.0), None)
+            })
+            .collect();
+        let limit = Limit::with_positions(positions).with_max_open_positions(2);
+        let spent = Arc::new(Mutex::new(Vec::new()));
+        let buy = |price: Price, amount: Amount| -> PinFutureResult<QuantityPoint> {
+            let spent = spent.clone();
+            Box::pin(async move {
+                spent.lock().unwrap().push(amount);
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                Ok(QuantityPoint::new(amount / price))
+            })
+        };
+        let trading = simple_trading();
+        let price = simple_prices(vec![100.0; 4]);
+
+        // Every tick finds short positions while the others are still awaiting their buy
+        let results = tokio::join!(
+            limit.trap(&price, &buy, &trading.sell),
+            limit.trap(&price, &buy, &trading.sell),
+            limit.trap(&price, &buy, &trading.sell),
+            limit.trap(&price, &buy, &trading.sell),
+        );
+        assert!(results.0.is_ok() && results.1.is_ok() && results.2.is_ok() && results.3.is_ok());
+
+        // Never above the cap, and every buy that spent is recorded on a position
+        assert_eq!(spent.lock().unwrap().len(), 2);
+        assert_eq!(limit.open_positions(), 2);
+        let bought: usize = limit.positions.iter().map(|e| e.buying_count()).sum();
+        assert_eq!(bought, 2);
+        assert_eq!(limit.pending_open_positions.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_trap_max_open_positions_after_sell() {
+        let trading = simple_trading();
+        let positions = (0..2)
+            .map(|_| {
+                LimitPosition::new(decimal(10.0), range(90.0, 110.0), range(100.0, 120.0), None)
+            })
+            .collect();
+        let limit = Limit::with_positions(positions).with_max_open_positions(2);
+        let price = simple_prices(vec![95.0, 105.0]);
+
+        limit
+            .trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert_eq!(limit.open_positions(), 2);
+
+        // Both sell and buy again on the same tick, only one buy fits under the lowered cap
+        limit.set_max_open_positions(Some(1));
+        limit
+            .trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert_eq!(trading.selling().count.load(Ordering::SeqCst), 2);
+        assert_eq!(trading.buying().count.load(Ordering::SeqCst), 3);
+        assert_eq!(limit.open_positions(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_buy_cancelled() {
+        let position =
+            LimitPosition::new(decimal(10.0), range(90.0, 110.0), range(200.0, 300.0), None);
+        let pending = |_: Price, _: Amount| -> PinFutureResult<QuantityPoint> {
+            Box::pin(std::future::pending())
+        };
+
+        // The tick awaiting the buy is dropped, e.g. by a timeout
+        let buying = position.buy(pending, decimal(100.0), decimal(10.0));
+        let timeout = tokio::time::timeout(Duration::from_millis(1), buying).await;
+        assert!(timeout.is_err());
+        assert_eq!(position.is_buying(), false);
+
+        let trading = simple_trading();
+        position
+            .buy(&trading.buy, decimal(100.0), decimal(10.0))
+            .await
+            .unwrap();
+        assert_eq!(position.is_short(), false);
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_trap_investment_fraction() {
+        let position = |buying: Range, fraction: f64| {
+            LimitPosition::new(decimal(50.0), buying, range(200.0, 300.0), None)
+                .with_investment_fraction(decimal(fraction))
+        };
+        let treasurer = Arc::new(Prosperity::new(Some(decimal(1000.0))));
+        let limit = Limit::try_with_positions(vec![
+            position(range(90.0, 110.0), 0.2),
+            position(range(95.0, 105.0), 0.3),
+            LimitPosition::new(decimal(50.0), range(80.0, 120.0), range(200.0, 300.0), None),
+        ])
+        .unwrap()
+        .with_treasurer(treasurer.clone());
+        let trading = simple_trading();
+        let price = simple_prices(vec![100.0]);
+
+        limit
+            .trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        let amounts = trading.buying().amounts.clone();
+        assert_eq!(amounts, vec![decimal(200.0), decimal(300.0), decimal(50.0)]);
+
+        // The buys of a tick debit the treasurer, the fractions still share one balance
+        let treasurer = Arc::new(Prosperity::new(Some(decimal(1000.0))));
+        let limit = Limit::try_with_positions(vec![
+            position(range(90.0, 110.0), 0.2),
+            position(range(95.0, 105.0), 0.3),
+        ])
+        .unwrap()
+        .with_treasurer(treasurer.clone());
+        let amounts = Arc::new(Mutex::new(Vec::new()));
+        let buy = |price: Price, amount: Amount| -> PinFutureResult<QuantityPoint> {
+            let (treasurer, amounts) = (treasurer.clone(), amounts.clone());
+            Box::pin(async move {
+                treasurer.transfer_out(amount, Decimal::ZERO).await;
+                amounts.lock().unwrap().push(amount);
+                Ok(QuantityPoint::new(amount / price))
+            })
+        };
+        limit
+            .trap(&simple_prices(vec![100.0]), &buy, &trading.sell)
+            .await
+            .unwrap();
+        assert_eq!(
+            *amounts.lock().unwrap(),
+            vec![decimal(200.0), decimal(300.0)]
+        );
+        assert_eq!(treasurer.balance().await, decimal(500.0));
+
+        let positions = vec![
+            position(range(90.0, 110.0), 0.6),
+            position(range(95.0, 105.0), 0.5),
+        ];
+        let error = Limit::try_with_positions(positions).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "investment fractions add up to 1.1, above 1"
+        );
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_trap_on_error() {
+        let failures = Arc::new(AtomicUsize::new(0));
+        let buy_failures = failures.clone();
+        let buy = move |price: Price, amount: Amount| -> PinFutureResult<QuantityPoint> {
+            let is_failed =
+                amount == decimal(10.0) && buy_failures.fetch_add(1, Ordering::SeqCst) < 2;
+            Box::pin(async move {
+                match is_failed {
+                    true => Err("insufficient balance".into()),
+                    false => Ok(QuantityPoint::new(amount / price)),
+                }
+            })
+        };
+        let sell = simple_trading().sell;
+        let price = simple_prices(vec![80.0, 80.0, 80.0]);
+        let limit = || {
+            Limit::with_positions(
+                [10.0, 20.0, 30.0]
+                    .iter()
+                    .map(|e| {
+                        LimitPosition::new(
+                            decimal(*e),
+                            range(75.0, 85.0),
+                            range(100.0, 110.0),
+                            None,
+                        )
+                    })
+                    .collect(),
+            )
+        };
+
+        let error = limit().trap(&price, &buy, &sell).await.unwrap_err();
+        assert_eq!(error.to_string(), "insufficient balance");
+
+        let on_error = ErrorHandler::new(|side, price, _| {
+            assert_eq!(side, TradeSide::Buy);
+            assert_eq!(price, &decimal(80.0));
+            ErrorDirective::Skip
+        });
+        let skipping = limit().with_on_error(on_error);
+        skipping.trap(&price, &buy, &sell).await.unwrap();
+        let counts: Vec<usize> = skipping
+            .positions
+            .iter()
+            .map(|e| e.buying_count())
+            .collect();
+        assert_eq!(counts, vec![0, 1, 1]);
+        assert!(logs_contain("skip Buy at 80: insufficient balance"));
+
+        let retry = || {
+            ErrorHandler::new(|_, _, _| ErrorDirective::Retry)
+                .with_retries(3, Duration::from_millis(1))
+        };
+        failures.store(0, Ordering::SeqCst);
+        let retrying = limit().with_on_error(retry());
+        retrying.trap(&price, &buy, &sell).await.unwrap();
+        let counts: Vec<usize> = retrying
+            .positions
+            .iter()
+            .map(|e| e.buying_count())
+            .collect();
+
+        #[cfg(feature = "client")]
+        {
+            assert_eq!(counts, vec![1, 1, 1]);
+            assert_eq!(failures.load(Ordering::SeqCst), 3);
+
+            // Skipped once the single retry fails as well
+            failures.store(0, Ordering::SeqCst);
+            let retrying = limit().with_on_error(retry().with_retries(1, Duration::from_millis(1)));
+            let price = simple_prices(vec![80.0]);
+            retrying.trap(&price, &buy, &sell).await.unwrap();
+            assert_eq!(retrying.positions[0].buying_count(), 0);
+            assert_eq!(failures.load(Ordering::SeqCst), 2);
+            assert!(logs_contain(
+                "skip Buy at 80 after 1 retries: insufficient balance"
+            ));
+        }
+
+        // Without the client runtime there is no backoff, the position is skipped right away
+        #[cfg(not(feature = "client"))]
+        {
+            assert_eq!(counts, vec![0, 1, 1]);
+            assert_eq!(failures.load(Ordering::SeqCst), 1);
+            assert!(logs_contain(
+                "skip Buy at 80 after 0 retries: insufficient balance"
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_trap_on_error_after_sell() {
+        // Held, both ranges hold 80 so the sell is followed by a failing buy
+        let limit = Limit::with_positions(vec![LimitPosition::new(
+            decimal(10.0),
+            range(75.0, 85.0),
+            range(78.0, 90.0),
+            Some(decimal(0.1)),
+        )]);
+        let sides = Arc::new(Mutex::new(Vec::new()));
+        let on_error = {
+            let sides = sides.clone();
+            ErrorHandler::new(move |side, _, _| {
+                sides.lock().unwrap().push(side);
+                ErrorDirective::Skip
+            })
+        };
+        let limit = limit.with_on_error(on_error);
+        let buy = |_: Price, _: Amount| -> PinFutureResult<QuantityPoint> {
+            Box::pin(async { Err("insufficient balance".into()) })
+        };
+        let sell = simple_trading().sell;
+
+        let outcome = limit
+            .trap_with_outcome(&simple_prices(vec![80.0]), &buy, &sell)
+            .await
+            .unwrap();
+        assert_eq!(matches!(outcome, TrapOutcome::Sold(_)), true);
+        assert_eq!(*sides.lock().unwrap(), vec![TradeSide::Buy]);
+        assert_eq!(limit.positions[0].selling_count(), 1);
+        assert_eq!(limit.positions[0].is_short(), true);
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_trap_evaluation_order() {
+        let positions = || {
+            vec![
+                LimitPosition::new(decimal(10.0), range(70.0, 100.0), range(200.0, 300.0), None),
+                LimitPosition::new(decimal(20.0), range(70.0, 92.0), range(200.0, 300.0), None),
+                LimitPosition::new(decimal(30.0), range(76.0, 84.0), range(200.0, 300.0), None),
+            ]
+        };
+
+        let trading = simple_trading();
+        let limit = Limit::with_positions(positions());
+        limit
+            .trap(&simple_prices(vec![80.0]), &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert_eq!(
+            trading.buying().amounts,
+            vec![decimal(10.0), decimal(20.0), decimal(30.0)]
+        );
+
+        let trading = simple_trading();
+        let limit =
+            Limit::with_positions(positions()).with_evaluation_order(EvaluationOrder::Proximity);
+        limit
+            .trap(&simple_prices(vec![80.0]), &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert_eq!(
+            trading.buying().amounts,
+            vec![decimal(30.0), decimal(20.0), decimal(10.0)]
+        );
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_trap_trading_window() {
+        let time = |h: u32, m: u32| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+        let at = |h: u32, m: u32| {
+            let date = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+            Utc.from_utc_datetime(&date.and_time(time(h, m)))
+        };
+
+        let trading = simple_trading();
+        let clock = Arc::new(MockClock::new(at(3, 0)));
+        let limit = single_none_position_limit()
+            .with_trading_window(vec![(time(8, 0), time(12, 0)), (time(22, 0), time(2, 0))])
+            .with_clock(clock.clone());
+
+        // Ticks outside the window do not even fetch the price
+        let price = simple_prices(vec![50.0, 250.0]);
+        limit
+            .trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert_eq!(trading.buying().count.load(Ordering::SeqCst), 0);
+        assert!(logs_contain("outside trading window, skip trap"));
+
+        clock.set(at(8, 0));
+        limit
+            .trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert_eq!(trading.buying().count.load(Ordering::SeqCst), 1);
+
+        // The end of a window is excluded
+        clock.set(at(12, 0));
+        assert_eq!(limit.is_within_trading_window(), false);
+
+        // Spanning midnight
+        clock.set(at(23, 30));
+        limit
+            .trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert_eq!(trading.selling().count.load(Ordering::SeqCst), 1);
+
+        clock.advance(chrono::Duration::try_hours(2).unwrap());
+        assert_eq!(limit.is_within_trading_window(), true);
+        clock.advance(chrono::Duration::try_minutes(30).unwrap());
+        assert_eq!(limit.is_within_trading_window(), false);
+
+        // Restored with the state
+        let json = serde_json::to_string(&limit).unwrap();
+        assert!(
+            json.contains(r#""trading_window":[["08:00:00","12:00:00"],["22:00:00","02:00:00"]]"#)
+        );
+        let restored: Limit = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.trading_window, limit.trading_window);
+
+        let json = json.replace("02:00:00", "26:00:00");
+        let error = serde_json::from_str::<Limit>(&json).unwrap_err();
+        assert!(error.to_string().contains("invalid time of day 26:00:00"));
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_trap_with_outcome() {
+        let trading = simple_trading();
+        let limit = single_none_position_limit();
+
+        let price = simple_prices(vec![150.0, 50.0, 250.0]);
+
+        let outcome = limit
+            .trap_with_outcome(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert_eq!(outcome, TrapOutcome::NoAction);
+
+        let outcome = limit
+            .trap_with_outcome(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert!(matches!(outcome, TrapOutcome::Bought(v) if v.value() == &decimal(1.0)));
+
+        let outcome = limit
+            .trap_with_outcome(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert!(matches!(outcome, TrapOutcome::Sold(v) if v.value() == &decimal(0.004)));
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_trap_sell_above_cost() {
+        let trading = simple_trading();
+        let position =
+            LimitPosition::new(decimal(50.0), range(90.0, 105.0), range(100.0, 300.0), None)
+                .with_sell_above_cost(decimal(0.002));
+        let limit = Limit::with_positions(vec![position]);
+
+        let prices = vec![105.0, 100.0, 105.1, 106.0];
+        let price = simple_prices(prices.clone());
+        for _ in 0..prices.len() {
+            limit
+                .trap(&price, &trading.buy, &trading.sell)
+                .await
+                .unwrap();
+        }
+
+        // Selling is deferred until the price covers the entry and the round-trip fee
+        assert_eq!(trading.buying().prices, vec![decimal(105.0)]);
+        assert_eq!(trading.selling().prices, vec![decimal(106.0)]);
+        assert!(logs_contain("below average cost"));
+        assert_eq!(*limit.positions[0].entry_price.lock().unwrap(), None);
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_trap_decision_log() {
+        let trading = simple_trading();
+        let decision_log = Arc::new(DecisionLog::new(16));
+        let limit = Limit::with_positions(vec![
+            LimitPosition::new(decimal(50.0), range(90.0, 105.0), range(100.0, 300.0), None)
+                .with_sell_above_cost(decimal(0.002)),
+            LimitPosition::new(decimal(50.0), range(95.0, 98.0), range(200.0, 300.0), None),
+        ])
+        .with_decision_log(decision_log.clone());
+
+        let price = simple_prices(vec![105.0, 100.0, 96.0]);
+        for _ in 0..3 {
+            limit
+                .trap(&price, &trading.buy, &trading.sell)
+                .await
+                .unwrap();
+        }
+        limit.pause();
+        limit
+            .trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+
+        let decisions: Vec<(Option<Price>, usize, Decision)> = decision_log
+            .entries()
+            .into_iter()
+            .map(|e| (e.price, e.level, e.decision))
+            .collect();
+        let skipped = |price: f64, level: usize, reason: SkipReason| {
+            (Some(decimal(price)), level, Decision::Skipped(reason))
+        };
+        assert_eq!(decisions.len(), 8);
+        assert!(matches!(
+            &decisions[0],
+            (_, 0, Decision::Traded(TrapOutcome::Bought(_)))
+        ));
+        assert_eq!(decisions[1], skipped(105.0, 1, SkipReason::OutOfRange));
+        assert_eq!(decisions[2], skipped(100.0, 0, SkipReason::BelowCost));
+        assert_eq!(decisions[3], skipped(100.0, 1, SkipReason::OutOfRange));
+        assert_eq!(decisions[4], skipped(96.0, 0, SkipReason::AlreadyHeld));
+        assert!(matches!(
+            &decisions[5],
+            (_, 1, Decision::Traded(TrapOutcome::Bought(_)))
+        ));
+        assert_eq!(
+            decisions[6],
+            (None, 0, Decision::Skipped(SkipReason::Paused))
+        );
+        assert_eq!(
+            decisions[7],
+            (None, 1, Decision::Skipped(SkipReason::Paused))
+        );
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_trap_cycle_completed() {
+        let (events, receiver) = std::sync::mpsc::channel();
+        let limit = Limit::with_positions(vec![
+            LimitPosition::new(decimal(50.0), range(90.0, 110.0), range(200.0, 300.0), None),
+            // An initial position, its first sell is not a cycle
+            LimitPosition::new(
+                decimal(50.0),
+                range(50.0, 60.0),
+                range(200.0, 300.0),
+                Some(decimal(0.5)),
+            ),
+        ])
+        .with_events(events);
+        let trading = simple_trading();
+        let sell = |price: Price, quantity: Quantity| -> PinFutureResult<AmountPoint> {
+            Box::pin(async move { Ok(AmountPoint::new(price * quantity)) })
+        };
+        let price = simple_prices(vec![100.0, 250.0]);
+
+        for _ in 0..2 {
+            limit.trap(&price, &trading.buy, &sell).await.unwrap();
+        }
+
+        assert_eq!(limit.positions[0].cycle_count(), 1);
+        assert_eq!(limit.positions[0].buying_count(), 1);
+        assert_eq!(limit.positions[1].cycle_count(), 0);
+        assert_eq!(limit.positions[1].selling_count(), 1);
+
+        // Bought 0.5 for 50, sold at 250 for 125
+        let events: Vec<StrategyEvent> = receiver.try_iter().collect();
+        assert_eq!(
+            events,
+            vec![
+                StrategyEvent::Bought {
+                    level: 0,
+                    price: decimal(100.0),
+                    quantity: decimal(0.5),
+                },
+                StrategyEvent::Sold {
+                    level: 0,
+                    price: decimal(250.0),
+                    amount: decimal(125.0),
+                },
+                StrategyEvent::CycleCompleted {
+                    level: 0,
+                    profit: decimal(75.0),
+                },
+                StrategyEvent::Sold {
+                    level: 1,
+                    price: decimal(250.0),
+                    amount: decimal(125.0),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_round_trips_capped() {
+        let position =
+            LimitPosition::new(decimal(50.0), range(90.0, 110.0), range(200.0, 300.0), None);
+        let round_trip = |i: i64| {
+            RoundTrip::new(
+                (i, decimal(100.0), decimal(50.0)),
+                (i + 1, decimal(250.0), decimal(124.75)),
+                decimal(0.5),
+            )
+        };
+
+        position.record_round_trips((0..MAX_ROUND_TRIPS as i64).map(round_trip));
+        position.record_round_trips([round_trip(MAX_ROUND_TRIPS as i64)]);
+
+        // Only the latest are kept, so the saved state stays bounded
+        let round_trips = position.round_trips();
+        assert_eq!(round_trips.len(), MAX_ROUND_TRIPS);
+        assert_eq!(round_trips[0].buy_timestamp, 1);
+        assert_eq!(
+            round_trips.last().unwrap().buy_timestamp,
+            MAX_ROUND_TRIPS as i64
+        );
+    }
+
+    #[tokio::test]
+    async fn test_round_trips() {
+        let limit = Limit::with_positions(vec![LimitPosition::new(
+            decimal(50.0),
+            range(90.0, 110.0),
+            range(200.0, 300.0),
+            None,
+        )]);
+
+        // Fills a second apart with a 0.1% commission on both sides
+        let clock = Arc::new(AtomicUsize::new(0));
+        let tick = move || 1_700_000_000_000 + 1000 * clock.fetch_add(1, Ordering::SeqCst) as i64;
+        let buy_tick = tick.clone();
+        let buy = move |price: Price, amount: Amount| -> PinFutureResult<QuantityPoint> {
+            let quantity = amount / price * decimal(0.999);
+            let point = QuantityPoint::with_timestamp(quantity, buy_tick())
+                .with_spent(amount - decimal(0.05));
+            Box::pin(async move { Ok(point) })
+        };
+        let sell = move |price: Price, quantity: Quantity| -> PinFutureResult<AmountPoint> {
+            let point = AmountPoint::with_timestamp(price * quantity * decimal(0.999), tick());
+            Box::pin(async move { Ok(point) })
+        };
+        let price = simple_prices(vec![100.0, 250.0, 95.0, 220.0]);
+
+        for _ in 0..4 {
+            limit.trap(&price, &buy, &sell).await.unwrap();
+        }
+
+        let round_trips = limit.round_trips();
+        assert_eq!(round_trips.len(), 2);
+
+        // Bought 0.4995 for the 49.95 spent of 50, sold at 250 for 124.750125
+        let first = &round_trips[0];
+        assert_eq!(first.buy_timestamp, 1_700_000_000_000);
+        assert_eq!(first.sell_timestamp, 1_700_000_001_000);
+        assert_eq!(first.quantity, decimal(0.4995));
+        assert_eq!(first.cost, decimal(49.95));
+        assert_eq!(first.profit, decimal(74.800125));
+        assert_eq!(first.fees, decimal(0.124875));
+
+        let second = &round_trips[1];
+        assert_eq!(second.buy_timestamp, 1_700_000_002_000);
+        assert_eq!(second.sell_timestamp, 1_700_000_003_000);
+        assert_eq!(second.buy_price, decimal(95.0));
+        assert_eq!(second.sell_price, decimal(220.0));
+        assert_eq!(second.profit, second.income - decimal(49.95));
+
+        let csv = round_trip::to_csv(&round_trips);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines[1],
+            "2023-11-14T22:13:20.000Z,100,0.4995,49.95,2023-11-14T22:13:21.000Z,250,124.750125,0.124875,74.800125"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_partial_fill() {
+        let limit = Limit::with_positions(vec![LimitPosition::new(
+            decimal(100.0),
+            range(40.0, 100.0),
+            range(140.0, 200.0),
+            None,
+        )]);
+
+        // The first buy fills half of the amount, the next ones fill fully
+        let buys = Arc::new(AtomicUsize::new(0));
+        let amounts = Arc::new(Mutex::new(Vec::new()));
+        let buy = {
+            let amounts = amounts.clone();
+            move |price: Price, amount: Amount| -> PinFutureResult<QuantityPoint> {
+                amounts.lock().unwrap().push(amount);
+                let point = match buys.fetch_add(1, Ordering::SeqCst) {
+                    0 => QuantityPoint::new(amount / Decimal::TWO / price)
+                        .with_unfilled(amount / Decimal::TWO),
+                    _ => QuantityPoint::new(amount / price),
+                };
+                Box::pin(async move { Ok(point) })
+            }
+        };
+        let trading = simple_trading();
+        let price = simple_prices(vec![100.0, 50.0, 60.0, 150.0]);
+        let position = &limit.positions()[0];
+
+        // Holds 0.5 of the intended 1
+        limit.trap(&price, &buy, &trading.sell).await.unwrap();
+        assert_eq!(*position.position.lock().unwrap(), Some(decimal(0.5)));
+        assert_eq!(*position.unfilled.lock().unwrap(), Some(decimal(50.0)));
+        assert_eq!(position.is_partially_filled(), true);
+
+        // Topped up with the remaining 50 at 50
+        limit.trap(&price, &buy, &trading.sell).await.unwrap();
+        assert_eq!(*position.position.lock().unwrap(), Some(decimal(1.5)));
+        assert_eq!(position.is_partially_filled(), false);
+        assert_eq!(position.cost(), Some(decimal(100.0)));
+        assert_eq!(
+            position.entry_price.lock().unwrap().unwrap().round_dp(6),
+            dec("66.666667")
+        );
+
+        // Filled, nothing more to buy
+        limit.trap(&price, &buy, &trading.sell).await.unwrap();
+        assert_eq!(
+            *amounts.lock().unwrap(),
+            vec![decimal(100.0), decimal(50.0)]
+        );
+        assert_eq!(position.buying_count(), 2);
+
+        limit.trap(&price, &buy, &trading.sell).await.unwrap();
+        assert_eq!(trading.selling().quantitys, vec![decimal(1.5)]);
+        assert_eq!(limit.round_trips()[0].cost, decimal(100.0));
+    }
+
+    #[test]
+    fn test_coverage_gaps() {
+        let position = |low: f64, high: f64| {
+            LimitPosition::new(decimal(10.0), range(low, high), range(200.0, 300.0), None)
+        };
+        // [90, 100] and [95, 105] overlap, [110, 120] leaves a hole at (105, 110)
+        let limit = Limit::with_positions(vec![
+            position(110.0, 120.0),
+            position(90.0, 100.0),
+            position(95.0, 105.0),
+        ]);
+
+        assert_eq!(
+            limit.coverage_gaps(&range(80.0, 130.0)),
+            vec![range(80.0, 90.0), range(105.0, 110.0), range(120.0, 130.0)]
+        );
+        assert_eq!(
+            limit.coverage_gaps(&range(92.0, 108.0)),
+            vec![range(105.0, 108.0)]
+        );
+        assert_eq!(limit.coverage_gaps(&range(92.0, 104.0)), vec![]);
+    }
+
+    #[test]
+    fn test_validate() {
+        // Every buying range starts at 0
+        let error = multi_position_limit().validate().unwrap_err().to_string();
+        assert_eq!(error.matches("overlap").count(), 6);
+        assert_eq!(
+            error.contains("buying ranges of positions 0 and 1 overlap"),
+            true
+        );
+        assert_eq!(
+            error.contains("buying ranges of positions 2 and 3 overlap"),
+            true
+        );
+
+        let limit = Limit::with_positions(vec![
+            LimitPosition::new(decimal(10.0), range(50.0, 55.0), range(65.0, 90.0), None),
+            LimitPosition::new(decimal(10.0), range(60.0, 65.0), range(75.0, 90.0), None),
+        ]);
+        assert_eq!(limit.validate().is_ok(), true);
+
+        let limit = Limit::with_positions(vec![LimitPosition::new(
+            decimal(10.0),
+            range(50.0, 70.0),
+            range(65.0, 90.0),
+            None,
+        )]);
+        assert_eq!(
+            limit.validate().unwrap_err().to_string(),
+            "position 0 buying range Range(50, 70) overlaps its selling range Range(65, 90)"
+        );
+    }
+
     #[tokio::test]
     #[traced_test]
     async fn test_trap_mix() {
@@ -1,8 +1,21 @@
+pub mod decision;
 pub mod grid;
+pub mod indicator;
 pub mod limit;
+#[cfg(feature = "client")]
+pub mod pool;
+pub mod recenter;
+pub mod replay;
+pub mod round_trip;
+#[cfg(feature = "client")]
+pub mod runner;
+pub mod snapshot;
+pub mod spacing;
+#[cfg(feature = "client")]
+pub mod store;
 // mod percentage;
 
-use std::{error::Error, future::Future, pin::Pin, sync::Arc};
+use std::{error::Error, future::Future, pin::Pin, sync::Arc, time::Duration};
 
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
@@ -12,13 +25,50 @@ use crate::noun::*;
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct Range(pub Decimal, pub Decimal);
 
+#[derive(Clone, Debug, PartialEq)]
+pub enum RangeError {
+    /// Both bounds are the same price
+    ZeroWidth(Decimal),
+}
+
+impl Error for RangeError {}
+
+impl std::fmt::Display for RangeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ZeroWidth(bound) => write!(f, "range {} has zero width", bound),
+        }
+    }
+}
+
 impl Range {
+    /// A range from low to high in either order, rejects a zero width.
+    pub fn try_new(a: Decimal, b: Decimal) -> Result<Self, RangeError> {
+        match a.cmp(&b) {
+            std::cmp::Ordering::Less => Ok(Self(a, b)),
+            std::cmp::Ordering::Greater => Ok(Self(b, a)),
+            std::cmp::Ordering::Equal => Err(RangeError::ZeroWidth(a)),
+        }
+    }
+
     pub fn is_within_inclusive(&self, value: &Decimal) -> bool {
-        value >= &self.low() && value <= &self.high()
+        value >= self.low() && value <= self.high()
     }
 
     pub fn is_within_exclusive(&self, value: &Decimal) -> bool {
-        value > &self.low() && value < &self.high()
+        value > self.low() && value < self.high()
+    }
+
+    /// Membership in `[low, high)`, adjacent ranges sharing a boundary never both contain it.
+    pub fn is_within_half_open(&self, value: &Decimal) -> bool {
+        value >= self.low() && value < self.high()
+    }
+
+    pub fn is_within(&self, value: &Decimal, interval: &Interval) -> bool {
+        match interval {
+            Interval::Inclusive => self.is_within_inclusive(value),
+            Interval::HalfOpen => self.is_within_half_open(value),
+        }
     }
 
     pub fn high(&self) -> &Decimal {
@@ -40,6 +90,57 @@ impl Range {
     pub fn length(&self) -> Decimal {
         self.high() - self.low()
     }
+
+    /// A zero width range only ever matches one exact price, almost always a config mistake.
+    pub fn is_zero_width(&self) -> bool {
+        self.0 == self.1
+    }
+
+    /// Whether the ranges share at least one price, a common boundary included.
+    pub fn overlaps(&self, other: &Range) -> bool {
+        self.low() <= other.high() && other.low() <= self.high()
+    }
+
+    /// The part both ranges cover, `None` when they share no more than a boundary.
+    pub fn intersection(&self, other: &Range) -> Option<Range> {
+        let low = self.low().max(other.low());
+        let high = self.high().min(other.high());
+
+        match low < high {
+            true => Some(Range(*low, *high)),
+            false => None,
+        }
+    }
+
+    pub fn midpoint(&self) -> Decimal {
+        (self.low() + self.high()) / Decimal::TWO
+    }
+
+    /// `n` contiguous sub-ranges of equal length from low to high, each one starts where the
+    /// previous one ends and the last one ends exactly at high.
+    pub fn split(&self, n: usize) -> Vec<Range> {
+        if n == 0 {
+            return Vec::new();
+        }
+
+        let interval = self.length() / Decimal::from(n);
+        let mut boundaries: Vec<Decimal> = (0..n)
+            .map(|i| self.low() + interval * Decimal::from(i))
+            .collect();
+        boundaries.push(*self.high());
+
+        boundaries.windows(2).map(|e| Range(e[0], e[1])).collect()
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub enum Interval {
+    /// `[low, high]`
+    #[default]
+    Inclusive,
+
+    /// `[low, high)`
+    HalfOpen,
 }
 
 pub type PinFutureResult<T> =
@@ -57,6 +158,29 @@ pub trait Strategy {
         B: Fn(Price, Amount) -> PinFutureResult<QuantityPoint>,
         S: Fn(Price, Quantity) -> PinFutureResult<AmountPoint>;
 
+    /// Prices to reach for the next trades, a buy trigger for every empty position
+    /// and a sell trigger for every held one. Read only over config and state.
+    fn next_actionable_prices(&self) -> Vec<(Price, TradeSide)> {
+        Vec::new()
+    }
+
+    /// Human readable summary of the config and state, for logs and status pages.
+    fn describe(&self) -> String {
+        let prices: Vec<String> = self
+            .next_actionable_prices()
+            .iter()
+            .map(|(price, side)| match side {
+                TradeSide::Buy => format!("buy at {}", price),
+                TradeSide::Sell => format!("sell at {}", price),
+            })
+            .collect();
+
+        match prices.is_empty() {
+            true => String::from("no actionable prices"),
+            false => format!("next {}", prices.join(", ")),
+        }
+    }
+
     fn spawn_price(source: PricePoint) -> impl Fn() -> PinFutureResult<PricePoint> {
         move || -> PinFutureResult<PricePoint> {
             let source = source.clone();
@@ -67,6 +191,139 @@ pub trait Strategy {
     }
 }
 
+/// What a single `trap` tick actually did.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TrapOutcome {
+    NoAction,
+    Bought(QuantityPoint),
+    Sold(AmountPoint),
+
+    /// More than one trade happened in the same tick, in execution order
+    MultipleActions(Vec<TrapOutcome>),
+}
+
+impl TrapOutcome {
+    /// Flatten the outcomes of several positions, dropping the ones without action.
+    pub fn from_outcomes(outcomes: Vec<TrapOutcome>) -> Self {
+        let mut actions = Vec::with_capacity(outcomes.len());
+        for outcome in outcomes.into_iter() {
+            match outcome {
+                Self::NoAction => {}
+                Self::MultipleActions(v) => actions.extend(v),
+                action => actions.push(action),
+            }
+        }
+
+        match actions.len() {
+            0 => Self::NoAction,
+            1 => actions.remove(0),
+            _ => Self::MultipleActions(actions),
+        }
+    }
+}
+
+/// Sent by a strategy for every trade of a position, `level` is the index of the position.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StrategyEvent {
+    Bought {
+        level: usize,
+        price: Price,
+        quantity: Quantity,
+    },
+    Sold {
+        level: usize,
+        price: Price,
+        amount: Amount,
+    },
+
+    /// A sell following a buy on the same level, `profit` is the amount received
+    /// minus the amount invested
+    CycleCompleted { level: usize, profit: Amount },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TradeSide {
+    Buy,
+    Sell,
+}
+
+/// What to do with a trade that failed inside `trap`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ErrorDirective {
+    /// Stop the tick and return the error, the default without a handler
+    Abort,
+
+    /// Leave this position for the next tick and go on with the others
+    Skip,
+
+    /// Trap the same position again after a backoff, skipped once the retries of the
+    /// handler are used up. Without the `client` feature there is no runtime to wait on,
+    /// the position is skipped right away.
+    Retry,
+}
+
+type ErrorHandlerFn =
+    dyn Fn(TradeSide, &Price, &(dyn Error + Send + Sync)) -> ErrorDirective + Send + Sync;
+
+/// Called with the side, price and error of every failed trade.
+#[derive(Clone)]
+pub struct ErrorHandler {
+    handler: Arc<ErrorHandlerFn>,
+
+    /// Retries of the same position within a tick
+    max_retries: u32,
+
+    /// Delay before the first retry, doubled on every next one
+    backoff: Duration,
+}
+
+impl ErrorHandler {
+    /// Retries three times, after 100ms, 200ms and 400ms.
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(TradeSide, &Price, &(dyn Error + Send + Sync)) -> ErrorDirective
+            + Send
+            + Sync
+            + 'static,
+    {
+        Self {
+            handler: Arc::new(f),
+            max_retries: 3,
+            backoff: Duration::from_millis(100),
+        }
+    }
+
+    pub fn with_retries(mut self, max_retries: u32, backoff: Duration) -> Self {
+        self.max_retries = max_retries;
+        self.backoff = backoff;
+        self
+    }
+
+    pub fn handle(
+        &self,
+        side: TradeSide,
+        price: &Price,
+        error: &(dyn Error + Send + Sync),
+    ) -> ErrorDirective {
+        (self.handler)(side, price, error)
+    }
+
+    /// Delay before the retry following `retries` earlier ones, `None` once they are used up.
+    /// Always `None` without the `client` feature, a retry could not wait out the backoff.
+    pub fn retry_delay(&self, retries: u32) -> Option<Duration> {
+        match cfg!(feature = "client") && retries < self.max_retries {
+            true => Some(self.backoff.saturating_mul(2u32.saturating_pow(retries))),
+            false => None,
+        }
+    }
+}
+
+impl std::fmt::Debug for ErrorHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ErrorHandler")
+    }
+}
+
 pub trait Exchanger {
     fn spawn_price(self: &Arc<Self>) -> impl Fn() -> PinFutureResult<PricePoint>;
     fn spawn_buy(self: &Arc<Self>) -> impl Fn(Price, Amount) -> PinFutureResult<QuantityPoint>;
@@ -87,6 +344,14 @@ impl PricePoint {
         }
     }
 
+    /// A price observed at `timestamp` milliseconds, e.g. from a recorded series.
+    pub fn with_timestamp(price: Price, timestamp: i64) -> Self {
+        Self {
+            value: price,
+            timestamp,
+        }
+    }
+
     pub fn value(&self) -> &Price {
         &self.value
     }
@@ -96,6 +361,12 @@ impl PricePoint {
     }
 }
 
+impl From<PricePoint> for Price {
+    fn from(value: PricePoint) -> Self {
+        value.value
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct AmountPoint {
     value: Amount,
@@ -110,6 +381,23 @@ impl AmountPoint {
         }
     }
 
+    /// Reject a negative amount coming from an untrusted source.
+    pub fn try_new(amount: Amount) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        if amount.is_sign_negative() && !amount.is_zero() {
+            return Err(format!("negative amount {}", amount).into());
+        }
+
+        Ok(Self::new(amount))
+    }
+
+    /// Filled at `timestamp` milliseconds, e.g. as reported by the exchange.
+    pub fn with_timestamp(amount: Amount, timestamp: i64) -> Self {
+        Self {
+            value: amount,
+            timestamp,
+        }
+    }
+
     pub fn value(&self) -> &Amount {
         &self.value
     }
@@ -117,12 +405,48 @@ impl AmountPoint {
     pub fn timestamp(&self) -> i64 {
         self.timestamp
     }
+
+    /// Above zero, a zero of either sign is neither positive nor negative.
+    pub fn is_positive(&self) -> bool {
+        self.value > Decimal::ZERO
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.value < Decimal::ZERO
+    }
+
+    pub fn abs(&self) -> Self {
+        Self {
+            value: self.value.abs(),
+            timestamp: self.timestamp,
+        }
+    }
+}
+
+/// Keeps the timestamp, e.g. to record a buy as an outflow in a signed cash flow.
+impl std::ops::Neg for AmountPoint {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self {
+            value: -self.value,
+            timestamp: self.timestamp,
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct QuantityPoint {
     value: Quantity,
     timestamp: i64,
+
+    /// Part of the amount a partial fill left unspent
+    #[serde(default)]
+    unfilled: Amount,
+
+    /// Amount the fill actually cost, commission included, when the exchange reports it
+    #[serde(default)]
+    spent: Option<Amount>,
 }
 
 impl QuantityPoint {
@@ -130,9 +454,50 @@ impl QuantityPoint {
         Self {
             value: quantity,
             timestamp: timestamp_millis(),
+            unfilled: Decimal::ZERO,
+            spent: None,
         }
     }
 
+    /// Reject a negative quantity coming from an untrusted source.
+    pub fn try_new(quantity: Quantity) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        if quantity.is_sign_negative() && !quantity.is_zero() {
+            return Err(format!("negative quantity {}", quantity).into());
+        }
+
+        Ok(Self::new(quantity))
+    }
+
+    /// Filled at `timestamp` milliseconds, e.g. as reported by the exchange.
+    pub fn with_timestamp(quantity: Quantity, timestamp: i64) -> Self {
+        Self {
+            value: quantity,
+            timestamp,
+            unfilled: Decimal::ZERO,
+            spent: None,
+        }
+    }
+
+    /// Only part of the amount filled, `unfilled` of it is left to buy.
+    pub fn with_unfilled(mut self, unfilled: Amount) -> Self {
+        self.unfilled = unfilled;
+        self
+    }
+
+    pub fn unfilled(&self) -> &Amount {
+        &self.unfilled
+    }
+
+    /// The fill cost `spent` rather than the amount it was given.
+    pub fn with_spent(mut self, spent: Amount) -> Self {
+        self.spent = Some(spent);
+        self
+    }
+
+    pub fn spent(&self) -> Option<&Amount> {
+        self.spent.as_ref()
+    }
+
     pub fn value(&self) -> &Quantity {
         &self.value
     }
@@ -140,6 +505,34 @@ impl QuantityPoint {
     pub fn timestamp(&self) -> i64 {
         self.timestamp
     }
+
+    /// Above zero, a zero of either sign is neither positive nor negative.
+    pub fn is_positive(&self) -> bool {
+        self.value > Decimal::ZERO
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.value < Decimal::ZERO
+    }
+
+    pub fn abs(&self) -> Self {
+        Self {
+            value: self.value.abs(),
+            ..self.clone()
+        }
+    }
+}
+
+/// Keeps the timestamp, e.g. to record a buy as an outflow in a signed cash flow.
+impl std::ops::Neg for QuantityPoint {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self {
+            value: -self.value,
+            ..self
+        }
+    }
 }
 
 fn timestamp_millis() -> i64 {
@@ -164,26 +557,155 @@ mod tests_range {
             true
         );
     }
+
+    #[test]
+    fn test_is_within_half_open() {
+        assert_eq!(range(60.0, 80.0).is_within_half_open(&decimal(60.0)), true);
+        assert_eq!(range(60.0, 80.0).is_within_half_open(&decimal(70.0)), true);
+        assert_eq!(range(60.0, 80.0).is_within_half_open(&decimal(80.0)), false);
+        assert_eq!(range(80.0, 60.0).is_within_half_open(&decimal(80.0)), false);
+        assert_eq!(
+            range(60.0, 80.0).is_within(&decimal(80.0), &Interval::Inclusive),
+            true
+        );
+    }
+
+    #[test]
+    fn test_intersection() {
+        assert_eq!(
+            range(60.0, 80.0).intersection(&range(90.0, 70.0)),
+            Some(range(70.0, 80.0))
+        );
+        assert_eq!(
+            range(60.0, 80.0).intersection(&range(65.0, 70.0)),
+            Some(range(65.0, 70.0))
+        );
+        assert_eq!(range(60.0, 80.0).intersection(&range(80.0, 90.0)), None);
+        assert_eq!(range(60.0, 80.0).intersection(&range(85.0, 90.0)), None);
+    }
+
+    #[test]
+    fn test_try_new() {
+        let error = Range::try_new(decimal(50.0), decimal(50.0)).unwrap_err();
+        assert_eq!(error, RangeError::ZeroWidth(decimal(50.0)));
+        assert_eq!(error.to_string(), "range 50 has zero width");
+
+        let range = Range::try_new(decimal(90.0), decimal(50.0)).unwrap();
+        assert_eq!(range, Range(decimal(50.0), decimal(90.0)));
+        assert_eq!(range.low(), &decimal(50.0));
+        assert_eq!(range.high(), &decimal(90.0));
+    }
+
+    #[test]
+    fn test_overlaps() {
+        assert_eq!(range(60.0, 80.0).overlaps(&range(90.0, 70.0)), true);
+        assert_eq!(range(60.0, 80.0).overlaps(&range(65.0, 70.0)), true);
+        assert_eq!(range(60.0, 80.0).overlaps(&range(80.0, 90.0)), true);
+        assert_eq!(range(60.0, 80.0).overlaps(&range(85.0, 90.0)), false);
+    }
+
+    #[test]
+    fn test_midpoint() {
+        assert_eq!(range(60.0, 80.0).midpoint(), decimal(70.0));
+        assert_eq!(range(80.0, 60.0).midpoint(), decimal(70.0));
+        assert_eq!(range(1.0, 2.0).midpoint(), decimal(1.5));
+    }
+
+    #[test]
+    fn test_split() {
+        assert_eq!(range(60.0, 80.0).split(0), vec![]);
+        assert_eq!(range(60.0, 80.0).split(1), vec![range(60.0, 80.0)]);
+        assert_eq!(
+            range(80.0, 60.0).split(4),
+            vec![
+                range(60.0, 65.0),
+                range(65.0, 70.0),
+                range(70.0, 75.0),
+                range(75.0, 80.0)
+            ]
+        );
+
+        // An interval that does not divide evenly still tiles the range
+        let parts = range(0.0, 100.0).split(3);
+        assert_eq!(parts.len(), 3);
+        assert_eq!(parts[0].low(), &decimal(0.0));
+        assert_eq!(parts[2].high(), &decimal(100.0));
+        for pair in parts.windows(2) {
+            assert_eq!(pair[0].high(), pair[1].low());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_point {
+    use super::*;
+    use tests_general::*;
+
+    #[test]
+    fn test_exact_helpers() {
+        // 0.1 + 0.2 is 0.30000000000000004 as f64, both helpers stay exact
+        assert_eq!(dec("0.1") + dec("0.2"), dec("0.3"));
+        assert_eq!(decimal(0.1) + decimal(0.2), decimal(0.3));
+        assert_eq!(decimal(0.0115684), dec("0.0115684"));
+        assert_eq!(decimal(0.1 + 0.2) == dec("0.3"), false);
+    }
+
+    #[test]
+    fn test_try_new() {
+        assert!(QuantityPoint::try_new(decimal(0.0138661)).is_ok());
+        assert!(QuantityPoint::try_new(decimal(0.0)).is_ok());
+        assert!(QuantityPoint::try_new(-decimal(0.0)).is_ok());
+        assert!(QuantityPoint::try_new(decimal(-0.0138661)).is_err());
+
+        assert!(AmountPoint::try_new(decimal(996.92208)).is_ok());
+        assert!(AmountPoint::try_new(decimal(0.0)).is_ok());
+        assert_eq!(
+            AmountPoint::try_new(decimal(-996.92208))
+                .unwrap_err()
+                .to_string(),
+            "negative amount -996.92208"
+        );
+    }
+
+    #[test]
+    fn test_neg() {
+        let spent = -AmountPoint::with_timestamp(decimal(996.92208), 1507725176595);
+        assert_eq!(*spent.value(), decimal(-996.92208));
+        assert_eq!(spent.timestamp(), 1507725176595);
+        assert_eq!(-spent.clone(), spent.abs());
+        assert_eq!(spent.abs().timestamp(), 1507725176595);
+
+        let quantity = QuantityPoint::with_timestamp(decimal(0.0138661), 1507725176595);
+        assert_eq!(*(-quantity.clone()).value(), decimal(-0.0138661));
+        assert_eq!((-quantity.clone()).timestamp(), quantity.timestamp());
+    }
+
+    #[test]
+    fn test_sign() {
+        let income = AmountPoint::new(decimal(996.92208));
+        assert_eq!(income.is_positive(), true);
+        assert_eq!(income.is_negative(), false);
+        assert_eq!((-income).is_negative(), true);
+
+        let zero = QuantityPoint::new(-decimal(0.0));
+        assert_eq!(zero.is_positive(), false);
+        assert_eq!(zero.is_negative(), false);
+        assert_eq!((-zero).is_negative(), false);
+    }
 }
 
 #[cfg(test)]
 pub(crate) mod tests_general {
-    use std::borrow::BorrowMut;
     use std::sync::atomic::{AtomicUsize, Ordering};
     use std::sync::{Arc, Mutex, MutexGuard};
 
-    use rust_decimal::prelude::FromPrimitive;
-    use rust_decimal::Decimal;
     pub(super) use tracing::debug;
     pub(super) use tracing_test::traced_test;
 
-    pub(super) use crate::extension::LockResultExt;
-
+    use super::replay::PriceReplay;
     use super::*;
 
-    pub(super) fn decimal(value: f64) -> Decimal {
-        Decimal::from_f64(value).unwrap()
-    }
+    pub(super) use crate::tests_general::{dec, decimal};
 
     pub(super) fn range(left: f64, right: f64) -> Range {
         Range(decimal(left), decimal(right))
@@ -269,14 +791,8 @@ pub(crate) mod tests_general {
     }
 
     pub(crate) fn simple_prices(prices: Vec<f64>) -> impl Fn() -> PinFutureResult<PricePoint> {
-        let iter = Mutex::new(prices.into_iter());
-        let price = move || -> PinFutureResult<PricePoint> {
-            let item = iter.lock().ignore_poison().borrow_mut().next().unwrap();
-            let f = async move { Ok(PricePoint::new(decimal(item))) };
-
-            Box::pin(f)
-        };
+        let prices = prices.into_iter().map(decimal).collect();
 
-        price
+        PriceReplay::new(prices).spawn_price()
     }
 }
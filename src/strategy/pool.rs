@@ -0,0 +1,234 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use tokio::{
+    runtime::Builder,
+    sync::{mpsc, oneshot, watch, Semaphore, SemaphorePermit},
+    task::LocalSet,
+    time::Instant,
+};
+use tracing::error;
+
+use super::{runner::StrategyRunner, Exchanger, Strategy};
+use crate::extension::LockResultExt;
+
+/// Limits shared by every runner of a pool, taken before each trap.
+#[derive(Debug)]
+pub(crate) struct TrapGate {
+    permits: Semaphore,
+
+    /// Minimum time between two traps starting anywhere in the pool
+    spacing: Duration,
+    next: tokio::sync::Mutex<Instant>,
+}
+
+impl TrapGate {
+    fn new(max_concurrency: usize, spacing: Duration) -> Self {
+        Self {
+            permits: Semaphore::new(max_concurrency),
+            spacing,
+            next: tokio::sync::Mutex::new(Instant::now()),
+        }
+    }
+
+    pub(crate) async fn acquire(&self) -> SemaphorePermit<'_> {
+        let permit = self
+            .permits
+            .acquire()
+            .await
+            .expect("trap gate is never closed");
+
+        let mut next = self.next.lock().await;
+        tokio::time::sleep_until(*next).await;
+        *next = Instant::now() + self.spacing;
+
+        permit
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RunnerStatus {
+    Pending,
+    Running,
+    Stopped,
+    Failed(String),
+}
+
+type Job = Box<dyn FnOnce() -> Pin<Box<dyn Future<Output = ()>>> + Send>;
+
+// ===== Runner Pool =====
+/// Runs many strategy runners, typically one per symbol, under shared limits.
+///
+/// Strategies hold plain mutexes across awaits, so their futures are not `Send`.
+/// The pool drives them on a dedicated thread with its own single threaded runtime.
+pub struct RunnerPool {
+    gate: Arc<TrapGate>,
+    jobs: mpsc::UnboundedSender<Job>,
+    shutdown: watch::Sender<bool>,
+    statuses: Arc<Mutex<Vec<RunnerStatus>>>,
+    done: oneshot::Receiver<()>,
+}
+
+impl RunnerPool {
+    /// At most `max_concurrency` traps run at once and two traps start at least `spacing` apart.
+    pub fn new(max_concurrency: usize, spacing: Duration) -> Self {
+        let (jobs, mut receiver) = mpsc::unbounded_channel::<Job>();
+        let (shutdown, _) = watch::channel(false);
+        let (finished, done) = oneshot::channel();
+
+        thread::spawn(move || {
+            let runtime = match Builder::new_current_thread().enable_all().build() {
+                Ok(runtime) => runtime,
+                Err(e) => {
+                    error!("runner pool runtime error: {}", e);
+                    return;
+                }
+            };
+
+            let local = LocalSet::new();
+            local.block_on(&runtime, async move {
+                while let Some(job) = receiver.recv().await {
+                    tokio::task::spawn_local(job());
+                }
+            });
+
+            // No more runners are coming, wait for the ones still stopping
+            runtime.block_on(local);
+            let _ = finished.send(());
+        });
+
+        Self {
+            gate: Arc::new(TrapGate::new(max_concurrency, spacing)),
+            jobs,
+            shutdown,
+            statuses: Arc::new(Mutex::new(Vec::new())),
+            done,
+        }
+    }
+
+    /// Start `runner` in the pool and return its index in [`RunnerPool::health`].
+    ///
+    /// The pool takes over the cancellation of the runner.
+    pub fn spawn<T, E>(&self, runner: StrategyRunner<T, E>) -> usize
+    where
+        T: Strategy + Send + 'static,
+        E: Exchanger + Send + Sync + 'static,
+    {
+        let runner = runner
+            .with_cancellation(self.shutdown.subscribe())
+            .with_gate(self.gate.clone());
+
+        let id = {
+            let mut statuses = self.statuses.lock().ignore_poison();
+            statuses.push(RunnerStatus::Pending);
+            statuses.len() - 1
+        };
+
+        let statuses = self.statuses.clone();
+        let job: Job = Box::new(move || {
+            Box::pin(async move {
+                statuses.lock().ignore_poison()[id] = RunnerStatus::Running;
+
+                let status = match runner.run().await {
+                    Ok(()) => RunnerStatus::Stopped,
+                    Err(e) => RunnerStatus::Failed(e.to_string()),
+                };
+                statuses.lock().ignore_poison()[id] = status;
+            })
+        });
+
+        if self.jobs.send(job).is_err() {
+            self.statuses.lock().ignore_poison()[id] =
+                RunnerStatus::Failed(String::from("runner pool is not running"));
+        }
+
+        id
+    }
+
+    pub fn health(&self) -> Vec<RunnerStatus> {
+        self.statuses.lock().ignore_poison().clone()
+    }
+
+    pub fn running(&self) -> usize {
+        let statuses = self.statuses.lock().ignore_poison();
+        statuses
+            .iter()
+            .filter(|e| **e == RunnerStatus::Running)
+            .count()
+    }
+
+    /// Cancel every runner and wait for all of them to stop.
+    pub async fn shutdown_all(self) -> Vec<RunnerStatus> {
+        let _ = self.shutdown.send(true);
+        drop(self.jobs);
+        let _ = self.done.await;
+
+        let statuses = self.statuses.lock().ignore_poison();
+        statuses.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests_pool {
+    use super::super::{
+        limit::{Limit, LimitPosition},
+        tests_general::*,
+        AmountPoint, PinFutureResult, PricePoint, QuantityPoint,
+    };
+    use super::*;
+    use crate::noun::*;
+
+    struct NoopExchanger;
+
+    impl Exchanger for NoopExchanger {
+        fn spawn_price(self: &Arc<Self>) -> impl Fn() -> PinFutureResult<PricePoint> {
+            move || -> PinFutureResult<PricePoint> {
+                Box::pin(async move { Ok(PricePoint::new(decimal(150.0))) })
+            }
+        }
+
+        fn spawn_buy(self: &Arc<Self>) -> impl Fn(Price, Amount) -> PinFutureResult<QuantityPoint> {
+            move |_: Price, _: Amount| -> PinFutureResult<QuantityPoint> {
+                Box::pin(async move { Err("no trading".into()) })
+            }
+        }
+
+        fn spawn_sell(
+            self: &Arc<Self>,
+        ) -> impl Fn(Price, Quantity) -> PinFutureResult<AmountPoint> {
+            move |_: Price, _: Quantity| -> PinFutureResult<AmountPoint> {
+                Box::pin(async move { Err("no trading".into()) })
+            }
+        }
+    }
+
+    fn noop_runner() -> StrategyRunner<Limit, NoopExchanger> {
+        let position =
+            LimitPosition::new(decimal(50.0), range(90.0, 110.0), range(200.0, 300.0), None);
+        let limit = Limit::with_positions(vec![position]);
+
+        StrategyRunner::new(limit, Arc::new(NoopExchanger), Duration::from_millis(5))
+    }
+
+    #[tokio::test]
+    async fn test_spawn_and_shutdown() {
+        let pool = RunnerPool::new(2, Duration::from_millis(1));
+        for _ in 0..4 {
+            pool.spawn(noop_runner());
+        }
+
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while pool.running() < 4 && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        assert_eq!(pool.running(), 4);
+
+        let statuses = pool.shutdown_all().await;
+        assert_eq!(statuses, vec![RunnerStatus::Stopped; 4]);
+    }
+}
@@ -0,0 +1,363 @@
+use std::error::Error;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, instrument};
+
+use super::{
+    grid::{Grid, GridOptions},
+    indicator::Ema,
+    limit::LimitPosition,
+    AmountPoint, PinFutureResult, PricePoint, QuantityPoint, Range, Strategy, TradeSide,
+    TrapOutcome,
+};
+use crate::{extension::LockResultExt, noun::*};
+
+/// A grid whose range follows the EMA of the price, for trending assets.
+///
+/// Every `recenter_ticks` ticks the empty levels are rebuilt around
+/// `EMA ± width`. Levels holding inventory are carried over unchanged and
+/// keep selling at their original range, their investment is left out of
+/// the rebuilt levels.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmaGrid {
+    #[serde(with = "current_grid")]
+    grid: Mutex<Arc<Grid>>,
+    range: Mutex<Range>,
+    ema: Mutex<Ema>,
+    options: EmaGridOptions,
+    ticks: AtomicUsize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmaGridOptions {
+    pub investment: Amount,
+    pub copies: usize,
+
+    /// Half width of the range as a ratio of the EMA, `0.1` spans the EMA ± 10%
+    pub width: Decimal,
+
+    /// Ticks between two re-centerings, keeps the levels from thrashing
+    pub recenter_ticks: usize,
+
+    #[serde(default)]
+    pub grid: Option<GridOptions>,
+}
+
+impl EmaGrid {
+    /// Start on `range` until the first re-centering.
+    pub fn try_new(
+        period: usize,
+        range: Range,
+        options: EmaGridOptions,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        if options.recenter_ticks == 0 {
+            return Err("recenter ticks must be greater than zero".into());
+        }
+
+        if options.width <= Decimal::ZERO || options.width >= Decimal::ONE {
+            return Err(format!("width {} must be between 0 and 1", options.width).into());
+        }
+
+        let grid = Grid::try_new(
+            options.investment,
+            range.clone(),
+            options.copies,
+            options.grid.clone(),
+        )?;
+
+        Ok(Self {
+            grid: Mutex::new(Arc::new(grid)),
+            range: Mutex::new(range),
+            ema: Mutex::new(Ema::new(period)),
+            options,
+            ticks: AtomicUsize::new(0),
+        })
+    }
+
+    /// Range of the levels built by the last re-centering.
+    pub fn range(&self) -> Range {
+        self.range.lock().ignore_poison().clone()
+    }
+
+    pub fn grid(&self) -> Arc<Grid> {
+        self.grid.lock().ignore_poison().clone()
+    }
+
+    fn recenter(&self, ema: Price) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let current = self.grid();
+        let held: Vec<LimitPosition> = current
+            .positions()
+            .iter()
+            .filter(|e| !e.is_short())
+            .map(LimitPosition::fork)
+            .collect();
+        let held_investment: Amount = held.iter().map(|e| e.investment).sum();
+
+        let investment = checked_sub(self.options.investment, held_investment)?;
+        if investment <= Decimal::ZERO {
+            debug!("all investment is held, skip recentering");
+            return Ok(());
+        }
+
+        let low = checked_mul(ema, Decimal::ONE - self.options.width)?;
+        let high = checked_mul(ema, Decimal::ONE + self.options.width)?;
+        let range = Range(low, high);
+
+        let grid = current.rebuild(investment, range.clone(), self.options.copies, held)?;
+        debug!("recenter grid to {:?} around ema {}", range, ema);
+
+        *self.grid.lock().ignore_poison() = Arc::new(grid);
+        *self.range.lock().ignore_poison() = range;
+
+        Ok(())
+    }
+}
+
+// The grid is swapped whole on every re-centering, serde has no `Arc` support without its `rc` feature
+mod current_grid {
+    use std::sync::{Arc, Mutex};
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::Grid;
+    use crate::extension::LockResultExt;
+
+    pub(super) fn serialize<S>(grid: &Mutex<Arc<Grid>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let grid = grid.lock().ignore_poison().clone();
+        grid.as_ref().serialize(serializer)
+    }
+
+    pub(super) fn deserialize<'de, D>(deserializer: D) -> Result<Mutex<Arc<Grid>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(Mutex::new(Arc::new(Grid::deserialize(deserializer)?)))
+    }
+}
+
+impl EmaGrid {
+    #[instrument(skip_all)]
+    pub async fn trap_with_outcome<P, B, S>(
+        &self,
+        price: &P,
+        buy: &B,
+        sell: &S,
+    ) -> Result<TrapOutcome, Box<dyn Error + Send + Sync>>
+    where
+        P: Fn() -> PinFutureResult<PricePoint>,
+        B: Fn(Price, Amount) -> PinFutureResult<QuantityPoint>,
+        S: Fn(Price, Quantity) -> PinFutureResult<AmountPoint>,
+    {
+        let price_point = price().await?;
+        let ema = {
+            let mut ema = self.ema.lock().ignore_poison();
+            ema.update(*price_point.value());
+            ema.value()
+        };
+
+        let ticks = self.ticks.fetch_add(1, Ordering::Relaxed) + 1;
+        if ticks.is_multiple_of(self.options.recenter_ticks) {
+            if let Some(ema) = ema {
+                self.recenter(ema)?;
+            }
+        }
+
+        let price = &Self::spawn_price(price_point);
+
+        self.grid().trap_with_outcome(price, buy, sell).await
+    }
+}
+
+impl Strategy for EmaGrid {
+    async fn trap<P, B, S>(
+        &self,
+        price: &P,
+        buy: &B,
+        sell: &S,
+    ) -> Result<(), Box<dyn Error + Send + Sync>>
+    where
+        P: Fn() -> PinFutureResult<PricePoint>,
+        B: Fn(Price, Amount) -> PinFutureResult<QuantityPoint>,
+        S: Fn(Price, Quantity) -> PinFutureResult<AmountPoint>,
+    {
+        self.trap_with_outcome(price, buy, sell).await?;
+
+        Ok(())
+    }
+
+    fn next_actionable_prices(&self) -> Vec<(Price, TradeSide)> {
+        self.grid().next_actionable_prices()
+    }
+
+    fn describe(&self) -> String {
+        self.grid().describe()
+    }
+}
+
+#[cfg(test)]
+mod tests_recenter {
+    use super::super::tests_general::*;
+    use super::*;
+
+    fn simple_options() -> EmaGridOptions {
+        EmaGridOptions {
+            investment: decimal(100.0),
+            copies: 4,
+            width: decimal(0.1),
+            recenter_ticks: 2,
+            grid: None,
+        }
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_recenter() {
+        let grid = EmaGrid::try_new(3, range(90.0, 110.0), simple_options()).unwrap();
+        let trading = simple_trading();
+        let price = simple_prices(vec![100.0, 104.0, 108.0, 112.0, 116.0, 120.0]);
+
+        // Buys at 100 on the level [100, 102.5)
+        grid.trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert_eq!(grid.range(), range(90.0, 110.0));
+        assert_eq!(trading.buying().prices, vec![decimal(100.0)]);
+
+        // The ema is 102, the held level is carried over next to three new ones
+        grid.trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert_eq!(grid.range(), range(91.8, 112.2));
+        let current = grid.grid();
+        assert_eq!(current.positions().len(), 4);
+        let carried = &current.positions()[3];
+        assert_eq!(carried.buying, range(100.0, 102.5));
+        assert_eq!(carried.position.lock().unwrap().is_some(), true);
+
+        // The new top level [102, 104.55) buys at 104 right away
+        assert_eq!(
+            trading.buying().prices,
+            vec![decimal(100.0), decimal(104.0)]
+        );
+
+        let mut lows = vec![*grid.range().low()];
+        for _ in 0..4 {
+            grid.trap(&price, &trading.buy, &trading.sell)
+                .await
+                .unwrap();
+            lows.push(*grid.range().low());
+        }
+
+        // Re-centered on every second tick only, each time higher
+        assert_eq!(lows[1], lows[0]);
+        assert!(lows[2] > lows[1]);
+        assert_eq!(lows[3], lows[2]);
+        assert!(lows[4] > lows[3]);
+    }
+
+    #[tokio::test]
+    async fn test_recenter_keeps_state() {
+        let grid = EmaGrid::try_new(3, range(90.0, 110.0), simple_options()).unwrap();
+        let trading = simple_trading();
+        let price = simple_prices(vec![100.0, 104.0]);
+
+        grid.trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        grid.grid().pause();
+
+        // Re-centered while paused, the new top level would buy at 104 otherwise
+        grid.trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert_eq!(grid.range(), range(91.8, 112.2));
+        assert_eq!(grid.grid().is_paused(), true);
+        assert_eq!(trading.buying().prices, vec![decimal(100.0)]);
+    }
+
+    #[tokio::test]
+    async fn test_recenter_keeps_history() {
+        let options = EmaGridOptions {
+            recenter_ticks: 3,
+            ..simple_options()
+        };
+        let grid = EmaGrid::try_new(3, range(90.0, 110.0), options).unwrap();
+        let trading = simple_trading();
+        let price = simple_prices(vec![100.0, 108.0, 108.0]);
+
+        // Bought at 100 and sold at 108, the level is empty again
+        for _ in 0..2 {
+            grid.trap(&price, &trading.buy, &trading.sell)
+                .await
+                .unwrap();
+        }
+        let round_trips = grid.grid().round_trips();
+        let pnl = grid.grid().pnl(&decimal(108.0));
+        assert_eq!(round_trips.len(), 1);
+        assert_eq!(pnl, round_trips[0].profit);
+
+        // Paused so that the re-centering is the only change
+        grid.grid().pause();
+        grid.trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert_ne!(grid.range(), range(90.0, 110.0));
+        assert_eq!(grid.grid().round_trips(), round_trips);
+        assert_eq!(grid.grid().pnl(&decimal(108.0)), pnl);
+        let cycles: usize = grid
+            .grid()
+            .positions()
+            .iter()
+            .map(|e| e.cycle_count())
+            .sum();
+        assert_eq!(cycles, 1);
+    }
+
+    #[tokio::test]
+    async fn test_serde() {
+        let grid = EmaGrid::try_new(3, range(90.0, 110.0), simple_options()).unwrap();
+        let trading = simple_trading();
+        let price = simple_prices(vec![100.0, 104.0, 108.0]);
+
+        for _ in 0..2 {
+            grid.trap(&price, &trading.buy, &trading.sell)
+                .await
+                .unwrap();
+        }
+
+        let json = serde_json::to_string(&grid).unwrap();
+        let restored: EmaGrid = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.range(), grid.range());
+        assert_eq!(restored.grid().positions().len(), 4);
+        assert_eq!(restored.grid().describe(), grid.grid().describe());
+
+        // The ema and the tick count carry over, the next tick does not re-center
+        let low = *restored.range().low();
+        restored
+            .trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert_eq!(*restored.range().low(), low);
+    }
+
+    #[test]
+    fn test_try_new() {
+        let options = EmaGridOptions {
+            recenter_ticks: 0,
+            ..simple_options()
+        };
+        assert!(EmaGrid::try_new(3, range(90.0, 110.0), options).is_err());
+
+        let options = EmaGridOptions {
+            width: decimal(1.0),
+            ..simple_options()
+        };
+        assert!(EmaGrid::try_new(3, range(90.0, 110.0), options).is_err());
+    }
+}
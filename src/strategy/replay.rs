@@ -0,0 +1,194 @@
+use std::error::Error;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use std::str::FromStr;
+
+use super::{PinFutureResult, PricePoint};
+use crate::noun::*;
+
+/// What a [`PriceReplay`] does after its last price.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ReplayEnd {
+    /// Every later tick fails with an exhaustion error
+    #[default]
+    Stop,
+
+    /// Start over from the first price
+    Cycle,
+}
+
+/// A recorded price series fed to a strategy as its price closure, for backtests.
+///
+/// Clones share the cursor, every closure from [`PriceReplay::spawn_price`] advances it.
+#[derive(Debug, Clone)]
+pub struct PriceReplay {
+    /// Prices with their timestamp, the tick time when unknown
+    prices: Arc<Vec<(Option<i64>, Price)>>,
+    end: ReplayEnd,
+    cursor: Arc<AtomicUsize>,
+}
+
+impl PriceReplay {
+    pub fn new(prices: Vec<Price>) -> Self {
+        Self::from_points(prices.into_iter().map(|e| (None, e)).collect())
+    }
+
+    /// Replay `prices` with their recorded timestamps in milliseconds.
+    pub fn with_timestamps(prices: Vec<(i64, Price)>) -> Self {
+        Self::from_points(prices.into_iter().map(|(t, e)| (Some(t), e)).collect())
+    }
+
+    fn from_points(prices: Vec<(Option<i64>, Price)>) -> Self {
+        Self {
+            prices: Arc::new(prices),
+            end: ReplayEnd::default(),
+            cursor: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    pub fn with_end(mut self, end: ReplayEnd) -> Self {
+        self.end = end;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.prices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.prices.is_empty()
+    }
+
+    /// Prices replayed so far, cycles included.
+    pub fn replayed(&self) -> usize {
+        self.cursor.load(Ordering::Relaxed)
+    }
+
+    pub fn reset(&self) {
+        self.cursor.store(0, Ordering::Relaxed);
+    }
+
+    /// The next price point, an error once the series is exhausted.
+    pub fn next_point(&self) -> Result<PricePoint, Box<dyn Error + Send + Sync>> {
+        let index = self.cursor.fetch_add(1, Ordering::Relaxed);
+        let index = match self.end {
+            ReplayEnd::Cycle if !self.prices.is_empty() => index % self.prices.len(),
+            _ => index,
+        };
+
+        match self.prices.get(index) {
+            Some((Some(timestamp), price)) => Ok(PricePoint::with_timestamp(*price, *timestamp)),
+            Some((None, price)) => Ok(PricePoint::new(*price)),
+            None => Err(format!("price feed exhausted after {} prices", self.len()).into()),
+        }
+    }
+
+    pub fn spawn_price(&self) -> impl Fn() -> PinFutureResult<PricePoint> {
+        let replay = self.clone();
+        move || -> PinFutureResult<PricePoint> {
+            let point = replay.next_point();
+            Box::pin(async move { point })
+        }
+    }
+}
+
+impl From<Vec<Price>> for PriceReplay {
+    fn from(prices: Vec<Price>) -> Self {
+        Self::new(prices)
+    }
+}
+
+impl From<Vec<(i64, Price)>> for PriceReplay {
+    fn from(prices: Vec<(i64, Price)>) -> Self {
+        Self::with_timestamps(prices)
+    }
+}
+
+/// Each price is parsed from its `to_string`, so `0.1` is exactly `0.1`. Fails on a price that is not finite or out of the decimal range.
+impl TryFrom<Vec<f64>> for PriceReplay {
+    type Error = Box<dyn Error + Send + Sync>;
+
+    fn try_from(prices: Vec<f64>) -> Result<Self, Self::Error> {
+        let prices = prices
+            .into_iter()
+            .map(|e| {
+                Decimal::from_str(&e.to_string())
+                    .map_err(|_| format!("price {} is not representable", e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self::new(prices))
+    }
+}
+
+#[cfg(test)]
+mod tests_replay {
+    use super::super::tests_general::*;
+    use super::*;
+
+    #[tokio::test]
+    async fn test_replay() {
+        let replay = PriceReplay::from(vec![(1000, decimal(100.0)), (2000, decimal(101.5))]);
+        let price = replay.spawn_price();
+
+        let point = price().await.unwrap();
+        assert_eq!(*point.value(), decimal(100.0));
+        assert_eq!(point.timestamp(), 1000);
+        let point = price().await.unwrap();
+        assert_eq!(*point.value(), decimal(101.5));
+        assert_eq!(point.timestamp(), 2000);
+        assert_eq!(replay.replayed(), 2);
+
+        let replay = PriceReplay::try_from(vec![1.5, 2.0])
+            .unwrap()
+            .with_end(ReplayEnd::Cycle);
+        let price = replay.spawn_price();
+        let mut values = Vec::new();
+        for _ in 0..5 {
+            values.push(*price().await.unwrap().value());
+        }
+        assert_eq!(
+            values,
+            vec![
+                decimal(1.5),
+                decimal(2.0),
+                decimal(1.5),
+                decimal(2.0),
+                decimal(1.5)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_exhausted() {
+        let replay = PriceReplay::try_from(vec![100.0]).unwrap();
+        let price = replay.spawn_price();
+        assert!(price().await.is_ok());
+
+        let error = price().await.unwrap_err();
+        assert_eq!(error.to_string(), "price feed exhausted after 1 prices");
+        assert!(price().await.is_err());
+
+        replay.reset();
+        assert_eq!(*price().await.unwrap().value(), decimal(100.0));
+
+        let empty = PriceReplay::new(Vec::new()).with_end(ReplayEnd::Cycle);
+        assert!(empty.spawn_price()().await.is_err());
+
+        let error = PriceReplay::try_from(vec![100.0, f64::NAN]).unwrap_err();
+        assert_eq!(error.to_string(), "price NaN is not representable");
+    }
+
+    #[tokio::test]
+    async fn test_over_polling() {
+        // A loop polling once too often gets an error from the test prices as well
+        let price = simple_prices(vec![100.0, 101.0]);
+        for _ in 0..2 {
+            price().await.unwrap();
+        }
+
+        let error = price().await.unwrap_err();
+        assert_eq!(error.to_string(), "price feed exhausted after 2 prices");
+    }
+}
@@ -0,0 +1,102 @@
+use chrono::{DateTime, SecondsFormat};
+use serde::{Deserialize, Serialize};
+
+use crate::noun::*;
+
+/// One completed buy and sell of a position, the lot a FIFO cost basis report needs.
+///
+/// Amounts are in the quote asset, timestamps in milliseconds of the fills.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct RoundTrip {
+    pub buy_timestamp: i64,
+    pub buy_price: Price,
+
+    /// Quantity held after the buying commission, all of it sold
+    pub quantity: Quantity,
+
+    /// Amount spent on the buy, commission included
+    pub cost: Amount,
+
+    pub sell_timestamp: i64,
+    pub sell_price: Price,
+
+    /// Amount received from the sell, commission deducted
+    pub income: Amount,
+
+    /// Commissions and rounding dust of both sides, implied by the prices
+    pub fees: Amount,
+
+    /// Realized profit, negative for a loss
+    pub profit: Amount,
+}
+
+impl RoundTrip {
+    pub fn new(
+        (buy_timestamp, buy_price, cost): (i64, Price, Amount),
+        (sell_timestamp, sell_price, income): (i64, Price, Amount),
+        quantity: Quantity,
+    ) -> Self {
+        let buy_fee = cost - buy_price * quantity;
+        let sell_fee = sell_price * quantity - income;
+
+        Self {
+            buy_timestamp,
+            buy_price,
+            quantity,
+            cost,
+            sell_timestamp,
+            sell_price,
+            income,
+            fees: buy_fee + sell_fee,
+            profit: income - cost,
+        }
+    }
+
+    /// Milliseconds between the buy and the sell.
+    pub fn holding_time_millis(&self) -> i64 {
+        self.sell_timestamp - self.buy_timestamp
+    }
+}
+
+/// Mean time between the buy and the sell of `round_trips`, `None` without any.
+pub fn average_holding_time_millis(round_trips: &[RoundTrip]) -> Option<i64> {
+    if round_trips.is_empty() {
+        return None;
+    }
+
+    let total: i64 = round_trips.iter().map(RoundTrip::holding_time_millis).sum();
+    Some(total / round_trips.len() as i64)
+}
+
+const CSV_HEADER: &str = "buy_time,buy_price,quantity,cost,sell_time,sell_price,income,fees,profit";
+
+/// Round trips as CSV with a header row, times in RFC 3339 UTC.
+pub fn to_csv(round_trips: &[RoundTrip]) -> String {
+    let mut csv = String::from(CSV_HEADER);
+    csv.push('\n');
+
+    for e in round_trips {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{}\n",
+            rfc3339(e.buy_timestamp),
+            e.buy_price.normalize(),
+            e.quantity.normalize(),
+            e.cost.normalize(),
+            rfc3339(e.sell_timestamp),
+            e.sell_price.normalize(),
+            e.income.normalize(),
+            e.fees.normalize(),
+            e.profit.normalize(),
+        ));
+    }
+
+    csv
+}
+
+// Out of range timestamps keep their milliseconds rather than failing the export
+fn rfc3339(timestamp: i64) -> String {
+    match DateTime::from_timestamp_millis(timestamp) {
+        Some(time) => time.to_rfc3339_opts(SecondsFormat::Millis, true),
+        None => timestamp.to_string(),
+    }
+}
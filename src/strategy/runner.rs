@@ -0,0 +1,450 @@
+use std::{
+    error::Error,
+    fmt::Display,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, OnceLock,
+    },
+    time::Duration,
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::{sync::watch, time::Instant};
+use tracing::{error, info, instrument};
+
+use super::{pool::TrapGate, store::StateStore, Exchanger, PinFutureResult, Strategy};
+use crate::market::client::SystemStatus;
+
+type SystemStatusFn = Box<dyn Fn() -> PinFutureResult<SystemStatus> + Send + Sync>;
+
+struct StatePersistence<T> {
+    store: Arc<dyn StateStore>,
+    key: String,
+    interval: Duration,
+    serialize: fn(&T) -> serde_json::Result<Vec<u8>>,
+    deserialize: fn(&[u8]) -> serde_json::Result<T>,
+}
+
+/// Returned from a `trap` to stop the runner instead of logging and continuing.
+#[derive(Debug)]
+pub struct Fatal(pub String);
+
+impl Error for Fatal {}
+
+impl Display for Fatal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+// ===== Strategy Runner =====
+pub struct StrategyRunner<T, E> {
+    strategy: T,
+    /// Loaded from the state store when the runner starts
+    restored: OnceLock<T>,
+    exchanger: Arc<E>,
+    interval: Duration,
+    cancellation: Option<watch::Receiver<bool>>,
+    system_status: Option<SystemStatusFn>,
+    is_maintenance: AtomicBool,
+    gate: Option<Arc<TrapGate>>,
+    persistence: Option<StatePersistence<T>>,
+}
+
+impl<T, E> StrategyRunner<T, E>
+where
+    T: Strategy,
+    E: Exchanger,
+{
+    pub fn new(strategy: T, exchanger: Arc<E>, interval: Duration) -> Self {
+        Self {
+            strategy,
+            restored: OnceLock::new(),
+            exchanger,
+            interval,
+            cancellation: None,
+            system_status: None,
+            is_maintenance: AtomicBool::new(false),
+            gate: None,
+            persistence: None,
+        }
+    }
+
+    /// Share the concurrency and rate limits of a pool.
+    pub(crate) fn with_gate(mut self, gate: Arc<TrapGate>) -> Self {
+        self.gate = Some(gate);
+        self
+    }
+
+    /// Check the exchange status before every trap, trading pauses during maintenance.
+    pub fn with_system_status<F>(mut self, system_status: F) -> Self
+    where
+        F: Fn() -> PinFutureResult<SystemStatus> + Send + Sync + 'static,
+    {
+        self.system_status = Some(Box::new(system_status));
+        self
+    }
+
+    pub fn is_maintenance(&self) -> bool {
+        self.is_maintenance.load(Ordering::SeqCst)
+    }
+
+    /// Stop running once `true` is sent through the channel.
+    pub fn with_cancellation(mut self, cancellation: watch::Receiver<bool>) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    pub fn strategy(&self) -> &T {
+        self.restored.get().unwrap_or(&self.strategy)
+    }
+
+    /// Run `trap` on a fixed schedule until cancelled or a [`Fatal`] error occurs.
+    ///
+    /// Ticks follow `tokio::time::interval`, so a slow `trap` does not make the
+    /// schedule drift. Other errors are logged and the next tick goes on.
+    ///
+    /// With a state store, the state saved there replaces the strategy before the first
+    /// tick. A state that fails to load stops the runner rather than trade from scratch.
+    #[instrument(skip_all)]
+    pub async fn run(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.restore_state().await?;
+
+        let price = self.exchanger.spawn_price();
+        let buy = self.exchanger.spawn_buy();
+        let sell = self.exchanger.spawn_sell();
+
+        let mut interval = tokio::time::interval(self.interval);
+        let mut cancellation = self.cancellation.clone();
+        let mut last_saved = Instant::now();
+
+        loop {
+            // A cancellation wins over a tick that is due at the same time
+            tokio::select! {
+                biased;
+                _ = Self::cancelled(&mut cancellation) => {
+                    self.save_state(&mut last_saved).await;
+                    return Ok(());
+                }
+                _ = interval.tick() => {}
+            }
+
+            let _permit = match &self.gate {
+                Some(gate) => Some(gate.acquire().await),
+                None => None,
+            };
+
+            if self.check_maintenance().await {
+                continue;
+            }
+
+            if let Err(e) = self.strategy().trap(&price, &buy, &sell).await {
+                if e.is::<Fatal>() {
+                    self.save_state(&mut last_saved).await;
+                    return Err(e);
+                }
+
+                error!("trap error: {}", e);
+            }
+
+            if let Some(persistence) = &self.persistence {
+                if last_saved.elapsed() >= persistence.interval {
+                    self.save_state(&mut last_saved).await;
+                }
+            }
+        }
+    }
+
+    // Only the first run loads, the strategy in memory is never older than the store
+    async fn restore_state(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let Some(persistence) = &self.persistence else {
+            return Ok(());
+        };
+        if self.restored.get().is_some() {
+            return Ok(());
+        }
+
+        if let Some(bytes) = persistence.store.load(&persistence.key).await? {
+            let strategy = (persistence.deserialize)(&bytes)?;
+            info!("restore state of {}", persistence.key);
+            let _ = self.restored.set(strategy);
+        }
+
+        Ok(())
+    }
+
+    // A failed save is logged, trading goes on and the next interval tries again
+    async fn save_state(&self, last_saved: &mut Instant) {
+        let Some(persistence) = &self.persistence else {
+            return;
+        };
+
+        *last_saved = Instant::now();
+        let result = match (persistence.serialize)(self.strategy()) {
+            Ok(bytes) => persistence.store.save(&persistence.key, bytes).await,
+            Err(e) => Err(e.into()),
+        };
+
+        if let Err(e) = result {
+            error!("save state of {} error: {}", persistence.key, e);
+        }
+    }
+
+    // An unreachable status endpoint does not stop trading, the last known status is kept
+    async fn check_maintenance(&self) -> bool {
+        let Some(system_status) = &self.system_status else {
+            return false;
+        };
+
+        let is_maintenance = match system_status().await {
+            Ok(status) => status == SystemStatus::Maintenance,
+            Err(e) => {
+                error!("system status error: {}", e);
+                return self.is_maintenance();
+            }
+        };
+
+        if self.is_maintenance.swap(is_maintenance, Ordering::SeqCst) != is_maintenance {
+            match is_maintenance {
+                true => info!("exchange under maintenance, pause trading"),
+                false => info!("exchange back to normal, resume trading"),
+            }
+        }
+
+        is_maintenance
+    }
+
+    async fn cancelled(cancellation: &mut Option<watch::Receiver<bool>>) {
+        if let Some(receiver) = cancellation {
+            loop {
+                if *receiver.borrow_and_update() {
+                    return;
+                }
+
+                // The sender is gone, nobody can cancel anymore
+                if receiver.changed().await.is_err() {
+                    break;
+                }
+            }
+        }
+
+        std::future::pending::<()>().await
+    }
+}
+
+impl<T, E> StrategyRunner<T, E>
+where
+    T: Strategy + Serialize + DeserializeOwned,
+    E: Exchanger,
+{
+    /// Save the strategy under `key` every `interval` and once more when the runner stops.
+    ///
+    /// A state already saved under `key` is restored when the runner starts. Settings
+    /// skipped by serde, such as clocks and error handlers, come back as defaults.
+    pub fn with_state_store(
+        mut self,
+        store: Arc<dyn StateStore>,
+        key: impl Into<String>,
+        interval: Duration,
+    ) -> Self {
+        self.persistence = Some(StatePersistence {
+            store,
+            key: key.into(),
+            interval,
+            serialize: |strategy| serde_json::to_vec(strategy),
+            deserialize: |bytes| serde_json::from_slice(bytes),
+        });
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests_runner {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::super::{
+        limit::{Limit, LimitPosition},
+        store::{load_state, FileStateStore},
+        tests_general::*,
+        AmountPoint, PinFutureResult, PricePoint, QuantityPoint,
+    };
+    use super::*;
+    use crate::noun::*;
+
+    #[derive(Default)]
+    struct MockExchanger {
+        prices: AtomicUsize,
+        is_fatal: bool,
+
+        /// Cancel the runner once this many prices were requested
+        cancel_after: Option<(usize, watch::Sender<bool>)>,
+    }
+
+    impl MockExchanger {
+        fn cancel_after(prices: usize) -> (Self, watch::Receiver<bool>) {
+            let (sender, receiver) = watch::channel(false);
+            let exchanger = Self {
+                cancel_after: Some((prices, sender)),
+                ..Self::default()
+            };
+
+            (exchanger, receiver)
+        }
+    }
+
+    impl Exchanger for MockExchanger {
+        fn spawn_price(self: &Arc<Self>) -> impl Fn() -> PinFutureResult<PricePoint> {
+            move || -> PinFutureResult<PricePoint> {
+                let count = self.prices.fetch_add(1, Ordering::SeqCst);
+                let is_fatal = self.is_fatal;
+                if let Some((prices, sender)) = &self.cancel_after {
+                    if count + 1 == *prices {
+                        sender.send(true).unwrap();
+                    }
+                }
+
+                Box::pin(async move {
+                    if is_fatal {
+                        return Err(Fatal(String::from("price feed closed")).into());
+                    }
+
+                    // Every other price fails, the runner must keep going
+                    if count % 2 == 1 {
+                        return Err("price unavailable".into());
+                    }
+
+                    Ok(PricePoint::new(decimal(100.0)))
+                })
+            }
+        }
+
+        fn spawn_buy(self: &Arc<Self>) -> impl Fn(Price, Amount) -> PinFutureResult<QuantityPoint> {
+            move |price: Price, amount: Amount| -> PinFutureResult<QuantityPoint> {
+                Box::pin(async move { Ok(QuantityPoint::new(amount / price)) })
+            }
+        }
+
+        fn spawn_sell(
+            self: &Arc<Self>,
+        ) -> impl Fn(Price, Quantity) -> PinFutureResult<AmountPoint> {
+            move |price: Price, quantity: Quantity| -> PinFutureResult<AmountPoint> {
+                Box::pin(async move { Ok(AmountPoint::new(quantity * price)) })
+            }
+        }
+    }
+
+    fn simple_limit() -> Limit {
+        let position =
+            LimitPosition::new(decimal(50.0), range(90.0, 110.0), range(200.0, 300.0), None);
+
+        Limit::with_positions(vec![position])
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_run_interval() {
+        let (exchanger, receiver) = MockExchanger::cancel_after(6);
+        let exchanger = Arc::new(exchanger);
+        let runner =
+            StrategyRunner::new(simple_limit(), exchanger.clone(), Duration::from_millis(20))
+                .with_cancellation(receiver);
+
+        let start = Instant::now();
+        runner.run().await.unwrap();
+
+        // The first tick completes immediately, then one every 20 milliseconds
+        assert_eq!(exchanger.prices.load(Ordering::SeqCst), 6);
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        assert_eq!(runner.strategy().positions()[0].buying_count(), 1);
+        assert!(logs_contain("price unavailable"));
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_run_fatal() {
+        let exchanger = Arc::new(MockExchanger {
+            is_fatal: true,
+            ..MockExchanger::default()
+        });
+        let runner =
+            StrategyRunner::new(simple_limit(), exchanger.clone(), Duration::from_millis(1));
+
+        let error = runner.run().await.unwrap_err();
+        assert_eq!(error.to_string(), "price feed closed");
+        assert_eq!(exchanger.prices.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_run_maintenance() {
+        let (exchanger, receiver) = MockExchanger::cancel_after(1);
+        let exchanger = Arc::new(exchanger);
+        let checks = Arc::new(std::sync::Mutex::new(Vec::new()));
+
+        // Maintenance for the first three ticks, normal afterwards
+        let (status_checks, status_exchanger) = (checks.clone(), exchanger.clone());
+        let runner =
+            StrategyRunner::new(simple_limit(), exchanger.clone(), Duration::from_millis(5))
+                .with_cancellation(receiver)
+                .with_system_status(move || -> PinFutureResult<SystemStatus> {
+                    let mut checks = status_checks.lock().unwrap();
+                    checks.push(status_exchanger.prices.load(Ordering::SeqCst));
+                    let count = checks.len();
+                    Box::pin(async move {
+                        match count <= 3 {
+                            true => Ok(SystemStatus::Maintenance),
+                            false => Ok(SystemStatus::Normal),
+                        }
+                    })
+                });
+        runner.run().await.unwrap();
+
+        // No price during maintenance, the first one after it cancels the runner
+        assert_eq!(*checks.lock().unwrap(), vec![0, 0, 0, 0]);
+        assert_eq!(runner.is_maintenance(), false);
+        assert_eq!(exchanger.prices.load(Ordering::SeqCst), 1);
+        assert!(logs_contain("exchange under maintenance, pause trading"));
+        assert!(logs_contain("exchange back to normal, resume trading"));
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_run_state_store() {
+        let directory =
+            std::env::temp_dir().join(format!("rust-binance-runner-{}", std::process::id()));
+        let store = Arc::new(FileStateStore::new(&directory));
+        let (exchanger, receiver) = MockExchanger::cancel_after(1);
+        let runner = StrategyRunner::new(
+            simple_limit(),
+            Arc::new(exchanger),
+            Duration::from_millis(20),
+        )
+        .with_cancellation(receiver)
+        .with_state_store(store.clone(), "limit", Duration::from_secs(3600));
+        runner.run().await.unwrap();
+
+        // The interval is not reached, the state is saved once on cancellation
+        let limit: Limit = load_state(store.as_ref(), "limit").await.unwrap().unwrap();
+        assert_eq!(limit.positions()[0].buying_count(), 1);
+        assert_eq!(limit.snapshot(), runner.strategy().snapshot());
+
+        // A new runner trades on from the saved state instead of its own
+        let position =
+            LimitPosition::new(decimal(50.0), range(10.0, 20.0), range(200.0, 300.0), None);
+        let (exchanger, receiver) = MockExchanger::cancel_after(1);
+        let runner = StrategyRunner::new(
+            Limit::with_positions(vec![position]),
+            Arc::new(exchanger),
+            Duration::from_millis(20),
+        )
+        .with_cancellation(receiver)
+        .with_state_store(store.clone(), "limit", Duration::from_secs(3600));
+        runner.run().await.unwrap();
+
+        assert_eq!(runner.strategy().positions()[0].buying, range(90.0, 110.0));
+        assert_eq!(runner.strategy().positions()[0].buying_count(), 1);
+        assert!(logs_contain("restore state of limit"));
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,167 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    limit::{Limit, LimitPosition, Position},
+    Range,
+};
+use crate::{extension::LockResultExt, noun::*};
+
+/// A point-in-time view of one level.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct LevelSnapshot {
+    pub buying: Range,
+    pub selling: Range,
+    pub investment: Amount,
+    pub position: Position,
+    pub entry_price: Option<Price>,
+    pub buying_count: usize,
+    pub selling_count: usize,
+}
+
+impl LevelSnapshot {
+    pub fn from_position(position: &LimitPosition) -> Self {
+        Self {
+            buying: position.buying.clone(),
+            selling: position.selling.clone(),
+            investment: position.investment,
+            position: *position.position.lock().ignore_poison(),
+            entry_price: *position.entry_price.lock().ignore_poison(),
+            buying_count: position.buying_count(),
+            selling_count: position.selling_count(),
+        }
+    }
+}
+
+/// A point-in-time view of every level of a strategy.
+///
+/// Levels are read one by one, take snapshots between ticks to get a consistent view.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct StrategySnapshot {
+    pub levels: Vec<LevelSnapshot>,
+    pub paused: bool,
+
+    /// Quantity held across all levels
+    pub holding: Quantity,
+}
+
+impl StrategySnapshot {
+    pub fn from_limit(limit: &Limit) -> Self {
+        let levels: Vec<LevelSnapshot> = limit
+            .positions()
+            .iter()
+            .map(LevelSnapshot::from_position)
+            .collect();
+        let holding = levels.iter().filter_map(|e| e.position).sum();
+
+        Self {
+            levels,
+            paused: limit.is_paused(),
+            holding,
+        }
+    }
+
+    /// What changed from `self` to `other`, levels are matched by index.
+    pub fn diff(&self, other: &Self) -> StrategyDiff {
+        let mut diff = StrategyDiff::default();
+
+        for (index, (before, after)) in self.levels.iter().zip(other.levels.iter()).enumerate() {
+            if before != after {
+                diff.changed.push(LevelChange {
+                    index,
+                    before: before.clone(),
+                    after: after.clone(),
+                });
+            }
+        }
+
+        diff.removed = self
+            .levels
+            .iter()
+            .skip(other.levels.len())
+            .cloned()
+            .collect();
+        diff.added = other
+            .levels
+            .iter()
+            .skip(self.levels.len())
+            .cloned()
+            .collect();
+
+        if self.paused != other.paused {
+            diff.paused = Some(other.paused);
+        }
+
+        diff
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct LevelChange {
+    pub index: usize,
+    pub before: LevelSnapshot,
+    pub after: LevelSnapshot,
+}
+
+impl LevelChange {
+    pub fn is_position_changed(&self) -> bool {
+        self.before.position != self.after.position
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct StrategyDiff {
+    pub changed: Vec<LevelChange>,
+    pub added: Vec<LevelSnapshot>,
+    pub removed: Vec<LevelSnapshot>,
+
+    /// New paused state when it was toggled
+    pub paused: Option<bool>,
+}
+
+impl StrategyDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty()
+            && self.added.is_empty()
+            && self.removed.is_empty()
+            && self.paused.is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests_snapshot {
+    use super::super::{tests_general::*, Strategy};
+    use super::*;
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_diff() {
+        let limit = Limit::with_positions(vec![
+            LimitPosition::new(decimal(50.0), range(90.0, 110.0), range(200.0, 300.0), None),
+            LimitPosition::new(decimal(50.0), range(50.0, 60.0), range(200.0, 300.0), None),
+        ]);
+        let trading = simple_trading();
+        let price = simple_prices(vec![100.0, 100.0]);
+
+        let before = limit.snapshot();
+        limit
+            .trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        let after = limit.snapshot();
+
+        let diff = before.diff(&after);
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].index, 0);
+        assert_eq!(diff.changed[0].is_position_changed(), true);
+        assert_eq!(diff.changed[0].after.position, Some(decimal(0.5)));
+        assert_eq!(diff.changed[0].after.buying_count, 1);
+        assert_eq!(after.holding, decimal(0.5));
+
+        // Nothing to do at the same price again
+        limit
+            .trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+        assert_eq!(after.diff(&limit.snapshot()).is_empty(), true);
+    }
+}
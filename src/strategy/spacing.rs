@@ -0,0 +1,138 @@
+use std::error::Error;
+
+use super::{
+    grid::{Grid, GridOptions, Spacing},
+    replay::PriceReplay,
+    AmountPoint, PinFutureResult, QuantityPoint, Range, Strategy,
+};
+use crate::noun::*;
+
+/// The grid both spacings are built from in [`compare_grid_spacing`].
+#[derive(Debug, Clone)]
+pub struct SpacingConfig {
+    pub investment: Amount,
+    pub range: Range,
+    pub copies: usize,
+
+    /// Commission rate charged on both sides of every trade
+    pub commission: Commission,
+
+    /// The spacing is overridden for each run
+    pub options: Option<GridOptions>,
+}
+
+/// What one spacing realized over the price series.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SpacingReport {
+    pub realized_profit: Amount,
+
+    /// Completed round trips
+    pub cycles: usize,
+
+    /// Commissions paid on the completed round trips
+    pub fees: Amount,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpacingComparison {
+    pub arithmetic: SpacingReport,
+    pub geometric: SpacingReport,
+}
+
+impl SpacingComparison {
+    /// The spacing that realized more profit, arithmetic on a tie.
+    pub fn better(&self) -> Spacing {
+        match self.geometric.realized_profit > self.arithmetic.realized_profit {
+            true => Spacing::Geometric,
+            false => Spacing::Arithmetic,
+        }
+    }
+}
+
+/// Replay `prices` through an arithmetic and a geometric grid with the same capital.
+///
+/// Orders fill at the replayed price less the commission, levels still held at the
+/// end of the series are left out of the report.
+pub async fn compare_grid_spacing(
+    prices: &[Price],
+    config: &SpacingConfig,
+) -> Result<SpacingComparison, Box<dyn Error + Send + Sync>> {
+    Ok(SpacingComparison {
+        arithmetic: replay(prices, config, Spacing::Arithmetic).await?,
+        geometric: replay(prices, config, Spacing::Geometric).await?,
+    })
+}
+
+async fn replay(
+    prices: &[Price],
+    config: &SpacingConfig,
+    spacing: Spacing,
+) -> Result<SpacingReport, Box<dyn Error + Send + Sync>> {
+    let options = GridOptions {
+        spacing,
+        ..config.options.clone().unwrap_or_default()
+    };
+    let grid = Grid::try_new(
+        config.investment,
+        config.range.clone(),
+        config.copies,
+        Some(options),
+    )?;
+
+    let net = Decimal::ONE - config.commission;
+    let buy = move |price: Price, amount: Amount| -> PinFutureResult<QuantityPoint> {
+        Box::pin(async move { Ok(QuantityPoint::new(checked_div(amount * net, price)?)) })
+    };
+    let sell = move |price: Price, quantity: Quantity| -> PinFutureResult<AmountPoint> {
+        Box::pin(async move { Ok(AmountPoint::new(price * quantity * net)) })
+    };
+
+    let replay = PriceReplay::new(prices.to_vec());
+    let price = replay.spawn_price();
+    for _ in 0..replay.len() {
+        grid.trap(&price, &buy, &sell).await?;
+    }
+
+    let round_trips = grid.round_trips();
+    Ok(SpacingReport {
+        realized_profit: round_trips.iter().map(|e| e.profit).sum(),
+        cycles: round_trips.len(),
+        fees: round_trips.iter().map(|e| e.fees).sum(),
+    })
+}
+
+#[cfg(test)]
+mod tests_spacing {
+    use super::super::tests_general::*;
+    use super::*;
+
+    #[tokio::test]
+    async fn test_compare_grid_spacing() {
+        let config = SpacingConfig {
+            investment: decimal(100.0),
+            range: range(50.0, 90.0),
+            copies: 4,
+            commission: decimal(0.001),
+            options: None,
+        };
+        let prices: Vec<Price> = [54.0, 88.0, 52.0, 88.0, 60.0, 88.0]
+            .into_iter()
+            .map(decimal)
+            .collect();
+
+        let comparison = compare_grid_spacing(&prices, &config).await.unwrap();
+        for report in [&comparison.arithmetic, &comparison.geometric] {
+            assert_eq!(report.cycles > 0, true);
+            assert_eq!(report.fees > Decimal::ZERO, true);
+            assert_eq!(report.realized_profit > Decimal::ZERO, true);
+        }
+
+        // 54 is only within the lowest arithmetic buying range [50, 55)
+        assert_eq!(comparison.arithmetic.cycles, 3);
+        assert_eq!(comparison.geometric.cycles, 2);
+        assert_eq!(
+            comparison.better() == Spacing::Geometric,
+            comparison.geometric.realized_profit > comparison.arithmetic.realized_profit
+        );
+    }
+}
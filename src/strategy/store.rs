@@ -0,0 +1,137 @@
+use std::{error::Error, path::PathBuf};
+
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::io::AsyncWriteExt;
+
+use super::PinFutureResult;
+
+/// Durable key value storage for strategy state.
+///
+/// Futures are boxed so a store can be shared as `Arc<dyn StateStore>`.
+pub trait StateStore: Send + Sync {
+    fn save(&self, key: &str, bytes: Vec<u8>) -> PinFutureResult<()>;
+
+    /// `None` when nothing was saved under `key` yet.
+    fn load(&self, key: &str) -> PinFutureResult<Option<Vec<u8>>>;
+}
+
+/// Serialize `state` as JSON and save it under `key`.
+pub async fn save_state<T>(
+    store: &dyn StateStore,
+    key: &str,
+    state: &T,
+) -> Result<(), Box<dyn Error + Send + Sync>>
+where
+    T: Serialize,
+{
+    store.save(key, serde_json::to_vec(state)?).await
+}
+
+/// Load the state saved under `key`.
+///
+/// Settings skipped by serde, such as clocks and error handlers, come back as
+/// defaults and have to be applied again through the builders.
+pub async fn load_state<T>(
+    store: &dyn StateStore,
+    key: &str,
+) -> Result<Option<T>, Box<dyn Error + Send + Sync>>
+where
+    T: DeserializeOwned,
+{
+    match store.load(key).await? {
+        Some(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+        None => Ok(None),
+    }
+}
+
+// ===== File State Store =====
+/// One `<key>.json` file per key in `directory`.
+#[derive(Debug, Clone)]
+pub struct FileStateStore {
+    directory: PathBuf,
+}
+
+impl FileStateStore {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+
+    pub fn path(&self, key: &str) -> PathBuf {
+        self.directory.join(format!("{}.json", key))
+    }
+}
+
+impl StateStore for FileStateStore {
+    // Written and synced to a temporary file first, the rename never leaves a half written
+    // state behind
+    fn save(&self, key: &str, bytes: Vec<u8>) -> PinFutureResult<()> {
+        let path = self.path(key);
+        let temporary = self.directory.join(format!("{}.json.tmp", key));
+        let directory = self.directory.clone();
+
+        Box::pin(async move {
+            tokio::fs::create_dir_all(&directory).await?;
+            let mut file = tokio::fs::File::create(&temporary).await?;
+            file.write_all(&bytes).await?;
+            file.sync_all().await?;
+            tokio::fs::rename(&temporary, &path).await?;
+
+            Ok(())
+        })
+    }
+
+    fn load(&self, key: &str) -> PinFutureResult<Option<Vec<u8>>> {
+        let path = self.path(key);
+
+        Box::pin(async move {
+            match tokio::fs::read(&path).await {
+                Ok(bytes) => Ok(Some(bytes)),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(e.into()),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests_store {
+    use super::super::{grid::Grid, tests_general::*, Range, Strategy};
+    use super::*;
+
+    fn temporary_directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+
+        directory
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_save_and_load_grid() {
+        let directory = temporary_directory("rust-binance-store");
+        let store = FileStateStore::new(&directory);
+
+        let grid = Grid::new(decimal(100.0), Range(decimal(50.0), decimal(90.0)), 4, None);
+        let trading = simple_trading();
+        let price = simple_prices(vec![52.0]);
+        grid.trap(&price, &trading.buy, &trading.sell)
+            .await
+            .unwrap();
+
+        let loaded: Option<Grid> = load_state(&store, "grid").await.unwrap();
+        assert!(loaded.is_none());
+
+        save_state(&store, "grid", &grid).await.unwrap();
+        assert!(store.path("grid").exists());
+        assert!(!directory.join("grid.json.tmp").exists());
+
+        let loaded: Grid = load_state(&store, "grid").await.unwrap().unwrap();
+        assert_eq!(loaded.snapshot(), grid.snapshot());
+        assert_eq!(loaded.snapshot().levels[0].buying_count, 1);
+        assert!(loaded.snapshot().levels[0].position.is_some());
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
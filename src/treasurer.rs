@@ -0,0 +1,276 @@
+use std::error::Error;
+use std::sync::Mutex;
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::{extension::LockResultExt, noun::*};
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum TransferSide {
+    In,
+    Out,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Transfer {
+    pub side: TransferSide,
+
+    /// Amount after commission
+    pub amount: Amount,
+
+    /// Commission paid for this transfer
+    pub commission: Commission,
+
+    pub timestamp: i64,
+}
+
+/// A consistent point-in-time view of the treasurer.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct TreasurerSnapshot {
+    pub balance: Amount,
+    pub total_in: Amount,
+    pub total_out: Amount,
+    pub commission: Commission,
+    pub history: Vec<Transfer>,
+
+    /// Held by pending orders, part of the balance but not available.
+    /// Not persisted: reservations belong to in-flight orders and do not outlive the process
+    #[serde(skip)]
+    pub reserved: Amount,
+}
+
+#[derive(Debug, Default)]
+pub struct Prosperity {
+    // All fields live behind one lock so that readers never see a half applied transfer
+    state: Mutex<TreasurerSnapshot>,
+}
+
+impl Prosperity {
+    pub fn new(balance: Option<Amount>) -> Self {
+        let state = TreasurerSnapshot {
+            balance: balance.unwrap_or_default(),
+            ..TreasurerSnapshot::default()
+        };
+
+        Self {
+            state: Mutex::new(state),
+        }
+    }
+
+    pub async fn balance(&self) -> Amount {
+        self.state.lock().ignore_poison().balance
+    }
+
+    /// Balance not held by a reservation.
+    pub async fn available(&self) -> Amount {
+        let state = self.state.lock().ignore_poison();
+        state.balance - state.reserved
+    }
+
+    pub async fn transfer_in(&self, amount: Amount, commission: Commission) {
+        let mut state = self.state.lock().ignore_poison();
+        Self::transfer(&mut state, TransferSide::In, amount, commission);
+    }
+
+    pub async fn transfer_out(&self, amount: Amount, commission: Commission) {
+        let mut state = self.state.lock().ignore_poison();
+        Self::transfer(&mut state, TransferSide::Out, amount, commission);
+    }
+
+    /// Hold `amount` for a pending order so that no one else can spend it.
+    ///
+    /// The reservation ends with [`ReservationGuard::commit`] once the order fills,
+    /// it is released when the guard is dropped otherwise.
+    pub async fn reserve(
+        &self,
+        amount: Amount,
+    ) -> Result<ReservationGuard<'_>, Box<dyn Error + Send + Sync>> {
+        let mut state = self.state.lock().ignore_poison();
+        let available = state.balance - state.reserved;
+        if amount > available {
+            return Err(format!(
+                "insufficient available balance {} to reserve {}",
+                available, amount
+            )
+            .into());
+        }
+
+        state.reserved += amount;
+
+        Ok(ReservationGuard {
+            treasurer: self,
+            amount,
+            is_settled: false,
+        })
+    }
+
+    /// Copy every field under a single lock acquisition.
+    pub async fn snapshot(&self) -> TreasurerSnapshot {
+        self.state.lock().ignore_poison().clone()
+    }
+
+    fn transfer(
+        state: &mut TreasurerSnapshot,
+        side: TransferSide,
+        amount: Amount,
+        commission: Commission,
+    ) {
+        match side {
+            TransferSide::In => {
+                state.balance += amount;
+                state.total_in += amount;
+            }
+            TransferSide::Out => {
+                state.balance -= amount;
+                state.total_out += amount;
+            }
+        }
+
+        state.commission += commission;
+        state.history.push(Transfer {
+            side,
+            amount,
+            commission,
+            timestamp: timestamp_millis(),
+        });
+    }
+}
+
+/// Funds held for a pending order, released on drop unless committed.
+#[derive(Debug)]
+pub struct ReservationGuard<'a> {
+    treasurer: &'a Prosperity,
+    amount: Amount,
+    is_settled: bool,
+}
+
+impl ReservationGuard<'_> {
+    pub fn amount(&self) -> &Amount {
+        &self.amount
+    }
+
+    /// The order filled, transfer out what was actually spent in place of the reservation.
+    pub async fn commit(mut self, amount: Amount, commission: Commission) {
+        let mut state = self.treasurer.state.lock().ignore_poison();
+        state.reserved -= self.amount;
+        Prosperity::transfer(&mut state, TransferSide::Out, amount, commission);
+        self.is_settled = true;
+    }
+
+    /// The order failed, make the amount available again.
+    pub async fn release(self) {
+        // Dropping the guard gives the reservation back
+    }
+}
+
+impl Drop for ReservationGuard<'_> {
+    fn drop(&mut self) {
+        if !self.is_settled {
+            self.treasurer.state.lock().ignore_poison().reserved -= self.amount;
+        }
+    }
+}
+
+fn timestamp_millis() -> i64 {
+    let now = Utc::now();
+
+    now.timestamp_millis()
+}
+
+#[cfg(test)]
+mod tests_treasurer {
+    use std::sync::Arc;
+
+    use super::*;
+
+    use crate::tests_general::decimal;
+
+    #[tokio::test]
+    async fn test_transfer() {
+        let treasurer = Prosperity::new(Some(decimal(100.0)));
+        treasurer.transfer_out(decimal(50.0), decimal(0.05)).await;
+        treasurer.transfer_in(decimal(60.5), decimal(0.06)).await;
+
+        let snapshot = treasurer.snapshot().await;
+        assert_eq!(snapshot.balance, decimal(110.5));
+        assert_eq!(snapshot.total_in, decimal(60.5));
+        assert_eq!(snapshot.total_out, decimal(50.0));
+        assert_eq!(snapshot.commission, decimal(0.11));
+        assert_eq!(snapshot.history.len(), 2);
+        assert_eq!(treasurer.balance().await, decimal(110.5));
+    }
+
+    #[tokio::test]
+    async fn test_reserve() {
+        let treasurer = Prosperity::new(Some(decimal(100.0)));
+
+        let reservation = treasurer.reserve(decimal(60.0)).await.unwrap();
+        assert_eq!(treasurer.available().await, decimal(40.0));
+        assert_eq!(treasurer.balance().await, decimal(100.0));
+        assert!(treasurer.reserve(decimal(50.0)).await.is_err());
+
+        reservation.release().await;
+        assert_eq!(treasurer.available().await, decimal(100.0));
+
+        // Dropped without a commit, e.g. a failed order returning early
+        {
+            let _reservation = treasurer.reserve(decimal(30.0)).await.unwrap();
+            assert_eq!(treasurer.available().await, decimal(70.0));
+        }
+        assert_eq!(treasurer.available().await, decimal(100.0));
+
+        // Filled for less than reserved
+        let reservation = treasurer.reserve(decimal(30.0)).await.unwrap();
+        reservation.commit(decimal(29.5), decimal(0.03)).await;
+        let snapshot = treasurer.snapshot().await;
+        assert_eq!(snapshot.reserved, decimal(0.0));
+        assert_eq!(snapshot.balance, decimal(70.5));
+        assert_eq!(treasurer.available().await, decimal(70.5));
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_skips_reserved() {
+        let treasurer = Prosperity::new(Some(decimal(100.0)));
+        let _reservation = treasurer.reserve(decimal(60.0)).await.unwrap();
+
+        let snapshot = treasurer.snapshot().await;
+        assert_eq!(snapshot.reserved, decimal(60.0));
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert!(!json.contains("reserved"));
+
+        let restored: TreasurerSnapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.reserved, decimal(0.0));
+        assert_eq!(restored.balance, decimal(100.0));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_snapshot_concurrent() {
+        let treasurer = Arc::new(Prosperity::new(None));
+        let mut handles = Vec::new();
+
+        for i in 0..100 {
+            let treasurer = treasurer.clone();
+            handles.push(tokio::spawn(async move {
+                if i % 2 == 0 {
+                    treasurer.transfer_in(decimal(1.5), decimal(0.01)).await;
+                } else {
+                    treasurer.transfer_out(decimal(1.0), decimal(0.01)).await;
+                }
+
+                let snapshot = treasurer.snapshot().await;
+                assert_eq!(snapshot.balance, snapshot.total_in - snapshot.total_out);
+            }));
+        }
+
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        let snapshot = treasurer.snapshot().await;
+        assert_eq!(snapshot.balance, snapshot.total_in - snapshot.total_out);
+        assert_eq!(snapshot.balance, decimal(25.0));
+        assert_eq!(snapshot.history.len(), 100);
+    }
+}
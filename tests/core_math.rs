@@ -0,0 +1,14 @@
+// Only the pure math is used here, so this also builds with `--no-default-features`
+use rust_binance::{
+    noun::*,
+    strategy::{grid::Grid, Range},
+};
+
+#[test]
+fn test_grid_split() {
+    let range = Range(Decimal::from(50), Decimal::from(90));
+    let grid = Grid::new(Decimal::from(100), range, 4, None);
+
+    assert_eq!(grid.predictive_lowest_profit_price().len(), 6);
+    assert!(grid.is_all_short());
+}